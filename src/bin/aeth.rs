@@ -1,5 +1,5 @@
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use serde::{Deserialize, Serialize};
 
@@ -170,12 +170,12 @@ impl AetherConfig {
         }
     }
 
-    fn save(&self, path: &PathBuf) -> std::io::Result<()> {
+    fn save(&self, path: &Path) -> std::io::Result<()> {
         let content = toml::to_string_pretty(self).unwrap();
         fs::write(path.join("aether.toml"), content)
     }
 
-    fn load(path: &PathBuf) -> std::io::Result<Self> {
+    fn load(path: &Path) -> std::io::Result<Self> {
         let content = fs::read_to_string(path.join("aether.toml"))?;
         Ok(toml::from_str(&content).unwrap())
     }
@@ -252,8 +252,8 @@ async fn main() {
                                 std::process::exit(1);
                             }
                         }
-                        Err(e) => {
-                            eprintln!("Compilation error: {}", e);
+                        Err(diagnostics) => {
+                            eprint!("{}", compiler.render_diagnostics(&diagnostics));
                             std::process::exit(1);
                         }
                     }
@@ -14,7 +14,7 @@ struct Cli {
     output: Option<PathBuf>,
 
     /// Enable optimization
-    #[arg(short, long)]
+    #[arg(short = 'O', long)]
     optimize: bool,
 
     /// Generate WebAssembly output
@@ -44,8 +44,8 @@ fn main() {
                 println!("WebAssembly output not yet implemented");
             }
         }
-        Err(e) => {
-            eprintln!("Compilation error: {}", e);
+        Err(diagnostics) => {
+            eprint!("{}", compiler.render_diagnostics(&diagnostics));
             std::process::exit(1);
         }
     }
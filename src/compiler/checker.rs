@@ -0,0 +1,2143 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::diagnostics::{Diagnostic, Label};
+use super::modules::ModuleGraph;
+use super::parser::{
+    ASTNode, BinaryOperator, EnumVariant, Field, MatchArm, MethodSignature, Parameter, Pattern, StringPart, TypeExpr,
+    TypeParameter, UnaryOperator,
+};
+use super::prelude;
+use super::source_map::Span;
+use super::types::Type;
+
+/// Semantic checks over a whole program that parsing alone cannot catch:
+/// interface conformance, name resolution and types. `modules` is the
+/// graph the program was linked from, which decides the names each file
+/// can see. Returns every problem found.
+///
+/// Items containing one of `errors` are left unchecked, since their trees
+/// are incomplete, but still declare their names to the rest.
+pub fn check_program(program: &ASTNode, modules: &ModuleGraph, errors: &[Diagnostic]) -> Vec<Diagnostic> {
+    let items = match program {
+        ASTNode::Program { items, .. } => items.as_slice(),
+        other => std::slice::from_ref(other),
+    };
+    let checked: Vec<&ASTNode> = items
+        .iter()
+        .filter(|item| !errors.iter().any(|error| error.is_error() && item.span().contains(error.primary.span)))
+        .collect();
+    let mut checker = Checker::new(items, modules);
+    checker.check_conformance(&checked);
+    checker.check_items(&checked);
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    classes: HashMap<&'a str, &'a ASTNode>,
+    interfaces: HashMap<&'a str, &'a ASTNode>,
+    structs: HashMap<&'a str, &'a ASTNode>,
+    enums: HashMap<&'a str, &'a ASTNode>,
+    functions: HashMap<&'a str, &'a ASTNode>,
+    constants: HashMap<&'a str, Type>,
+    /// Top-level names each file may use, by file id: those it declares
+    /// and those it imports.
+    visible: HashMap<usize, HashSet<&'a str>>,
+    /// The type parameters in scope in the declaration being checked:
+    /// those of a generic type and then those of a generic method.
+    generics: Vec<&'a TypeParameter>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// What the arguments of a call are checked against.
+struct Signature<'a> {
+    params: &'a [Parameter],
+    /// The type parameters to infer from the arguments.
+    generics: Vec<&'a TypeParameter>,
+    /// Type parameters already decided, such as those of a method's class
+    /// by the type of its receiver.
+    bindings: HashMap<String, Type>,
+}
+
+/// A name in scope in a body. Only a variable declared with `var` may be
+/// reassigned; parameters, `let`s and names bound by patterns may not.
+#[derive(Clone)]
+struct Local {
+    ty: Type,
+    mutable: bool,
+}
+
+impl Local {
+    fn fixed(ty: Type) -> Self {
+        Local { ty, mutable: false }
+    }
+}
+
+/// What is in scope while checking one body.
+#[derive(Default, Clone)]
+struct Context<'a> {
+    scopes: Vec<HashMap<&'a str, Local>>,
+    service_functions: HashMap<&'a str, &'a ASTNode>,
+    service_constants: HashMap<&'a str, Type>,
+    /// The class declaring the method being checked.
+    class: Option<&'a str>,
+    /// Whether the body is an instance method or constructor, with `self`.
+    has_self: bool,
+    /// The declared result of the body and what declares it.
+    returns: Option<(Type, String)>,
+    /// Whether the body may `await`: an `async` function or an endpoint.
+    is_async: bool,
+}
+
+impl<'a> Context<'a> {
+    fn local(&self, name: &str) -> Option<&Type> {
+        self.binding(name).map(|local| &local.ty)
+    }
+
+    fn binding(&self, name: &str) -> Option<&Local> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn define(&mut self, name: &'a str, local: Local) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, local);
+        }
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn new(items: &'a [ASTNode], modules: &'a ModuleGraph) -> Self {
+        let mut classes = HashMap::new();
+        let mut interfaces = HashMap::new();
+        let mut structs = HashMap::new();
+        let mut enums = HashMap::new();
+        let mut functions = HashMap::new();
+        // The prelude comes first so that a program may shadow its names
+        for item in prelude::items().iter().chain(items) {
+            match item {
+                ASTNode::Class { name, .. } => {
+                    classes.insert(name.as_str(), item);
+                }
+                ASTNode::Interface { name, .. } => {
+                    interfaces.insert(name.as_str(), item);
+                }
+                ASTNode::Struct { name, .. } => {
+                    structs.insert(name.as_str(), item);
+                }
+                ASTNode::Enum { name, .. } => {
+                    enums.insert(name.as_str(), item);
+                }
+                ASTNode::Function { name, .. } => {
+                    functions.insert(name.as_str(), item);
+                }
+                _ => {}
+            }
+        }
+
+        let mut visible = HashMap::new();
+        for module in &modules.modules {
+            let ASTNode::Program { items, .. } = &module.ast else {
+                continue;
+            };
+            // Every file sees the prelude
+            let mut names: HashSet<&str> = prelude::items().iter().filter_map(declared_name).collect();
+            for item in items {
+                if let ASTNode::Import { names: imported, .. } = item {
+                    names.extend(imported.iter().map(|imported| imported.name.as_str()));
+                }
+            }
+            visible.insert(module.file_id, names);
+        }
+        // What each file declares goes by its linked name, which differs for
+        // the private items of an imported file
+        for item in items {
+            if let Some(names) = visible.get_mut(&item.span().file_id) {
+                names.extend(declared_name(item));
+            }
+        }
+
+        Checker {
+            classes,
+            interfaces,
+            structs,
+            enums,
+            functions,
+            constants: HashMap::new(),
+            visible,
+            generics: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// The class `name` followed by its known ancestors, nearest first.
+    fn lineage(&self, name: &str) -> Vec<&'a ASTNode> {
+        let mut lineage = Vec::new();
+        let mut seen = HashSet::new();
+        let mut current = self.classes.get(name).copied();
+        while let Some(class @ ASTNode::Class { name, superclass, .. }) = current {
+            if !seen.insert(name.as_str()) {
+                break;
+            }
+            lineage.push(class);
+            current = superclass.as_deref().and_then(|parent| self.classes.get(parent).copied());
+        }
+        lineage
+    }
+
+    /// The instance method `method` of class `class`, inherited or not.
+    fn find_method(&self, class: &str, method: &str) -> Option<&'a ASTNode> {
+        self.lineage(class).into_iter().find_map(|class| match class {
+            ASTNode::Class { methods, .. } => methods
+                .iter()
+                .find(|candidate| matches!(candidate, ASTNode::Function { name, .. } if name == method)),
+            _ => None,
+        })
+    }
+
+    /// The static method `method` of class `class`, inherited or not.
+    fn find_static(&self, class: &str, method: &str) -> Option<&'a ASTNode> {
+        self.lineage(class).into_iter().find_map(|class| match class {
+            ASTNode::Class { static_methods, .. } => static_methods
+                .iter()
+                .find(|candidate| matches!(candidate, ASTNode::Function { name, .. } if name == method)),
+            _ => None,
+        })
+    }
+
+    /// Whether a value of class `found` can be used where `expected` is
+    /// declared: the same type, a subclass, or a class implementing it.
+    fn is_subtype(&self, found: &str, expected: &str) -> bool {
+        found == expected
+            || self.lineage(found).into_iter().any(|class| match class {
+                ASTNode::Class { name, interfaces, .. } => {
+                    name == expected || interfaces.iter().any(|interface| interface == expected)
+                }
+                _ => false,
+            })
+    }
+
+    /// Whether a value of type `found` can be used where `expected` is
+    /// declared. Only optional types accept `null`.
+    fn is_assignable(&self, found: &Type, expected: &Type) -> bool {
+        match (found, expected) {
+            (Type::Dynamic, _) | (_, Type::Dynamic) | (Type::Int, Type::Float) => true,
+            (Type::Null, Type::Optional(_)) => true,
+            (Type::Optional(found), Type::Optional(expected)) => self.is_assignable(found, expected),
+            (found, Type::Optional(expected)) => self.is_assignable(found, expected),
+            (Type::List(found), Type::List(expected)) | (Type::Future(found), Type::Future(expected)) => {
+                self.is_assignable(found, expected)
+            }
+            (Type::Map(found_key, found), Type::Map(expected_key, expected)) => {
+                self.is_assignable(found_key, expected_key) && self.is_assignable(found, expected)
+            }
+            // Type arguments are compared like the element types of a list
+            (Type::Named(found, found_arguments), Type::Named(expected, expected_arguments)) if found == expected => {
+                found_arguments
+                    .iter()
+                    .zip(expected_arguments)
+                    .all(|(found, expected)| self.is_assignable(found, expected))
+            }
+            (Type::Named(found, _), Type::Named(expected, _)) => self.is_subtype(found, expected),
+            (Type::Param(found), Type::Named(expected, _)) => {
+                self.bounds_of(found).iter().any(|bound| bound.name == *expected)
+            }
+            (found, expected) => found == expected,
+        }
+    }
+
+    /// Whether the file containing `span` can refer to the top-level `name`.
+    fn is_visible(&self, name: &str, span: Span) -> bool {
+        self.visible
+            .get(&span.file_id)
+            .is_none_or(|names| names.contains(name))
+    }
+
+    fn class(&self, name: &str, span: Span) -> Option<&'a ASTNode> {
+        self.classes.get(name).copied().filter(|_| self.is_visible(name, span))
+    }
+
+    /// The fields of the struct `name`, if it is one visible at `span`.
+    fn struct_fields(&self, name: &str, span: Span) -> Option<&'a [Field]> {
+        match self.structs.get(name).copied() {
+            Some(ASTNode::Struct { fields, .. }) if self.is_visible(name, span) => Some(fields),
+            _ => None,
+        }
+    }
+
+    fn enum_variants(&self, name: &str, span: Span) -> Option<&'a [EnumVariant]> {
+        match self.enums.get(name).copied() {
+            Some(ASTNode::Enum { variants, .. }) if self.is_visible(name, span) => Some(variants),
+            _ => None,
+        }
+    }
+
+    /// The enum `object` names, unless a local shadows it, with its variants.
+    fn enum_of(&self, object: &ASTNode, cx: &Context<'a>) -> Option<(String, &'a [EnumVariant])> {
+        match object {
+            ASTNode::Identifier { name, span } if cx.local(name).is_none() => {
+                self.enum_variants(name, *span).map(|variants| (name.clone(), variants))
+            }
+            _ => None,
+        }
+    }
+
+    /// The type parameters the class, struct or enum `name` declares.
+    fn type_params_of(&self, name: &str) -> &'a [TypeParameter] {
+        let declaration = self.classes.get(name).or_else(|| self.structs.get(name)).or_else(|| self.enums.get(name));
+        match declaration.copied() {
+            Some(
+                ASTNode::Class { type_params, .. } | ASTNode::Struct { type_params, .. } | ASTNode::Enum { type_params, .. },
+            ) => type_params,
+            _ => &[],
+        }
+    }
+
+    /// The interfaces bounding the type parameter `name` in scope.
+    fn bounds_of(&self, name: &str) -> &'a [TypeExpr] {
+        self.generics
+            .iter()
+            .rev()
+            .find(|param| param.name == name)
+            .map_or(&[], |param| param.bounds.as_slice())
+    }
+
+    /// Bindings for the type parameters of the class `class` and of its
+    /// ancestors, in an instance with type arguments `arguments`. Classes
+    /// are extended without type arguments, so those of ancestors are
+    /// dynamic.
+    fn class_bindings(&self, class: &str, arguments: &[Type]) -> HashMap<String, Type> {
+        let mut bindings = HashMap::new();
+        for ancestor in self.lineage(class).into_iter().rev() {
+            if let ASTNode::Class { name, type_params, .. } = ancestor {
+                let arguments = if name == class { arguments } else { &[] };
+                bindings.extend(type_bindings(type_params, arguments));
+            }
+        }
+        bindings
+    }
+
+    fn interface_method(&self, interface: &str, method: &str) -> Option<&'a MethodSignature> {
+        match self.interfaces.get(interface) {
+            Some(ASTNode::Interface { methods, .. }) => methods.iter().find(|signature| signature.name == method),
+            _ => None,
+        }
+    }
+
+    fn function(&self, name: &str, span: Span, cx: &Context<'a>) -> Option<&'a ASTNode> {
+        cx.service_functions.get(name).copied().or_else(|| {
+            self.functions.get(name).copied().filter(|_| self.is_visible(name, span))
+        })
+    }
+
+    /// The class a class `extends` must exist, and every interface it names
+    /// in `implements` must exist. The class must provide each of their
+    /// methods with a compatible signature.
+    fn check_conformance(&mut self, items: &[&'a ASTNode]) {
+        for &item in items {
+            let ASTNode::Class { name: class_name, superclass, interfaces, span, .. } = item else {
+                continue;
+            };
+            if let Some(superclass) = superclass {
+                self.check_superclass(class_name, superclass, *span);
+            }
+            for interface_name in interfaces {
+                let Some(ASTNode::Interface { methods: required, .. }) = self.interfaces.get(interface_name.as_str()).copied()
+                else {
+                    let diagnostic = if self.classes.contains_key(interface_name.as_str()) {
+                        Diagnostic::error(
+                            "E0200",
+                            format!("`{}` is a class, not an interface", interface_name),
+                            Label::new(*span).with_message(format!("`{}` cannot be implemented", interface_name)),
+                        )
+                        .with_suggestion("inherit from a class with `extends`", None)
+                    } else {
+                        Diagnostic::error(
+                            "E0200",
+                            format!("cannot find interface `{}`", interface_name),
+                            Label::new(*span).with_message("implemented here"),
+                        )
+                    };
+                    self.diagnostics.push(diagnostic);
+                    continue;
+                };
+
+                for signature in required {
+                    let expected = format_signature(&signature.name, &signature.params, &signature.return_type);
+                    let Some(method @ ASTNode::Function { params, return_type, span: method_span, .. }) =
+                        self.find_method(class_name, &signature.name)
+                    else {
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                "E0201",
+                                format!(
+                                    "class `{}` is missing method `{}` required by interface `{}`",
+                                    class_name, signature.name, interface_name
+                                ),
+                                Label::new(*span).with_message(format!("missing `{}`", signature.name)),
+                            )
+                            .with_secondary(Label::new(signature.span).with_message("required by this signature"))
+                            .with_suggestion("implement the method", Some(format!("{} {{ ... }}", expected))),
+                        );
+                        continue;
+                    };
+
+                    let params_match = params.len() == signature.params.len()
+                        && params.iter().zip(&signature.params).all(|(found, required)| {
+                            Type::from_annotation(&found.param_type) == Type::from_annotation(&required.param_type)
+                        });
+                    let returns_match = self.is_assignable(&call_result_of(method), &return_type_of(&signature.return_type));
+                    if !(params_match && returns_match) {
+                        let found = format_signature(&signature.name, params, return_type);
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                "E0202",
+                                format!(
+                                    "method `{}` of class `{}` does not match interface `{}`",
+                                    signature.name, class_name, interface_name
+                                ),
+                                Label::new(*method_span).with_message("incompatible signature"),
+                            )
+                            .with_secondary(Label::new(signature.span).with_message("required by this signature"))
+                            .with_note(format!("expected `{}`", expected))
+                            .with_note(format!("   found `{}`", found)),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Report a superclass that is not a class visible at `span`, or that
+    /// descends from the class `name` extending it.
+    fn check_superclass(&mut self, name: &str, superclass: &str, span: Span) {
+        if self.class(superclass, span).is_none() {
+            let diagnostic = if self.interfaces.contains_key(superclass) {
+                Diagnostic::error(
+                    "E0217",
+                    format!("`{}` is an interface, not a class", superclass),
+                    Label::new(span).with_message(format!("`{}` cannot be extended", superclass)),
+                )
+                .with_suggestion("conform to an interface with `implements`", None)
+            } else {
+                let diagnostic = Diagnostic::error(
+                    "E0217",
+                    format!("cannot find class `{}`", superclass),
+                    Label::new(span).with_message("extended here"),
+                );
+                match self.classes.contains_key(superclass) {
+                    true => diagnostic.with_suggestion(format!("import `{}` from the file declaring it", superclass), None),
+                    false => diagnostic,
+                }
+            };
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        let cyclic = self
+            .lineage(superclass)
+            .into_iter()
+            .any(|ancestor| matches!(ancestor, ASTNode::Class { name: ancestor, .. } if ancestor == name));
+        if cyclic {
+            self.diagnostics.push(Diagnostic::error(
+                "E0217",
+                format!("class `{}` inherits from itself", name),
+                Label::new(span).with_message(format!("`{}` is a descendant of `{}`", superclass, name)),
+            ));
+        }
+    }
+
+    /// Check every declaration and body. Constants come first, in order,
+    /// since any function may use them but a constant only sees those
+    /// declared before it.
+    fn check_items(&mut self, items: &[&'a ASTNode]) {
+        for &item in items {
+            if let ASTNode::Constant { .. } = item {
+                let ty = self.check_constant(item, &mut Context::default());
+                if let Some((name, ty)) = ty {
+                    self.constants.insert(name, ty);
+                }
+            }
+        }
+
+        for &item in items {
+            match item {
+                ASTNode::Function { .. } => self.check_function(item, &Context::default(), false),
+                ASTNode::Class { .. } => self.check_class(item),
+                ASTNode::Struct { type_params, fields, .. } => {
+                    self.check_type_params(type_params);
+                    self.generics = type_params.iter().collect();
+                    self.check_fields(fields);
+                    self.generics.clear();
+                }
+                ASTNode::Enum { type_params, variants, .. } => {
+                    self.check_type_params(type_params);
+                    self.generics = type_params.iter().collect();
+                    for field in variants.iter().flat_map(|variant| &variant.fields) {
+                        self.resolve_type(&field.param_type);
+                    }
+                    self.generics.clear();
+                }
+                ASTNode::Interface { methods, .. } => {
+                    for signature in methods {
+                        for param in &signature.params {
+                            self.resolve_type(&param.param_type);
+                        }
+                        if let Some(return_type) = &signature.return_type {
+                            self.resolve_type(return_type);
+                        }
+                    }
+                }
+                ASTNode::Service { .. } => self.check_service(item),
+                _ => {}
+            }
+        }
+    }
+
+    /// The type an annotation names, reporting unknown names and misused
+    /// type arguments.
+    fn resolve_type(&mut self, annotation: &TypeExpr) -> Type {
+        if !self.is_valid_type(annotation) {
+            return Type::Dynamic;
+        }
+        let generics: Vec<&str> = self.generics.iter().map(|param| param.name.as_str()).collect();
+        let ty = Type::from_annotation_in(annotation, &generics);
+        if let Type::Map(key, _) = ty.non_null() {
+            if !matches!(**key, Type::String | Type::Dynamic) {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0209",
+                    format!("map keys must be `String`, found `{}`", key),
+                    Label::new(annotation.arguments[0].span).with_message("unsupported key type"),
+                ));
+                return Type::Dynamic;
+            }
+        }
+        ty
+    }
+
+    fn is_valid_type(&mut self, annotation: &TypeExpr) -> bool {
+        let name = annotation.name.as_str();
+        if annotation.arguments.is_empty() && self.generics.iter().any(|param| param.name == name) {
+            return true;
+        }
+        let declared = self.classes.contains_key(name)
+            || self.interfaces.contains_key(name)
+            || self.structs.contains_key(name)
+            || self.enums.contains_key(name);
+        let arity = match Type::builtin_arity(name) {
+            Some(arity) => arity,
+            None if declared && self.is_visible(name, annotation.span) => self.type_params_of(name).len(),
+            None => {
+                let mut diagnostic = Diagnostic::error(
+                    "E0203",
+                    format!("cannot find type `{}`", name),
+                    Label::new(annotation.span).with_message("unknown type"),
+                );
+                if declared {
+                    diagnostic = diagnostic.with_suggestion(format!("import `{}` from the file declaring it", name), None);
+                }
+                self.diagnostics.push(diagnostic);
+                return false;
+            }
+        };
+        if annotation.arguments.len() != arity {
+            self.diagnostics.push(Diagnostic::error(
+                "E0209",
+                format!(
+                    "`{}` takes {} type argument{} but {} were supplied",
+                    name,
+                    arity,
+                    if arity == 1 { "" } else { "s" },
+                    annotation.arguments.len()
+                ),
+                Label::new(annotation.span).with_message(format!("expected {} type argument{}", arity, if arity == 1 { "" } else { "s" })),
+            ));
+            return false;
+        }
+        if !annotation.arguments.iter().all(|argument| self.is_valid_type(argument)) {
+            return false;
+        }
+        let generics: Vec<&str> = self.generics.iter().map(|param| param.name.as_str()).collect();
+        for (param, argument) in self.type_params_of(name).iter().zip(&annotation.arguments) {
+            let ty = Type::from_annotation_in(argument, &generics);
+            self.check_bounds(param, &ty, argument.span);
+        }
+        true
+    }
+
+    /// Each bound on a type parameter must name an interface.
+    fn check_type_params(&mut self, params: &'a [TypeParameter]) {
+        for bound in params.iter().flat_map(|param| &param.bounds) {
+            let name = bound.name.as_str();
+            if self.interfaces.contains_key(name) && self.is_visible(name, bound.span) && bound.arguments.is_empty() {
+                continue;
+            }
+            let diagnostic = if self.interfaces.contains_key(name) && bound.arguments.is_empty() {
+                Diagnostic::error(
+                    "E0203",
+                    format!("cannot find interface `{}`", name),
+                    Label::new(bound.span).with_message("unknown interface"),
+                )
+                .with_suggestion(format!("import `{}` from the file declaring it", name), None)
+            } else if self.interfaces.contains_key(name) {
+                Diagnostic::error(
+                    "E0209",
+                    format!("`{}` takes 0 type arguments but {} were supplied", name, bound.arguments.len()),
+                    Label::new(bound.span).with_message("expected 0 type arguments"),
+                )
+            } else {
+                Diagnostic::error(
+                    "E0203",
+                    format!("cannot find interface `{}`", name),
+                    Label::new(bound.span).with_message("not an interface"),
+                )
+                .with_note("type parameters can only be bounded by interfaces")
+            };
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Report `ty`, given for the type parameter `param`, if it does not
+    /// implement every interface bounding it.
+    fn check_bounds(&mut self, param: &TypeParameter, ty: &Type, span: Span) {
+        for bound in &param.bounds {
+            if !self.interfaces.contains_key(bound.name.as_str()) || self.is_assignable(ty, &Type::named(bound.name.clone())) {
+                continue;
+            }
+            self.diagnostics.push(
+                Diagnostic::error(
+                    "E0214",
+                    format!("`{}` does not implement `{}`", ty, bound.name),
+                    Label::new(span).with_message(format!("required by the bound `{}: {}`", param.name, bound.name)),
+                )
+                .with_secondary(Label::new(param.span).with_message("bound declared here")),
+            );
+        }
+    }
+
+    /// Check a constant's value against its annotation, returning the
+    /// constant's name and type.
+    fn check_constant(&mut self, constant: &'a ASTNode, cx: &mut Context<'a>) -> Option<(&'a str, Type)> {
+        let ASTNode::Constant { name, type_annotation, value, .. } = constant else {
+            return None;
+        };
+        let found = self.infer(value, cx);
+        let ty = match type_annotation {
+            Some(annotation) => {
+                let declared = self.resolve_type(annotation);
+                self.expect_type(&found, &declared, value.span(), None);
+                declared
+            }
+            None => found,
+        };
+        Some((name.as_str(), ty))
+    }
+
+    fn check_service(&mut self, service: &'a ASTNode) {
+        let ASTNode::Service { endpoints, functions, constants, .. } = service else {
+            return;
+        };
+        let mut cx = Context::default();
+        for function in functions {
+            if let ASTNode::Function { name, .. } = function {
+                cx.service_functions.insert(name.as_str(), function);
+            }
+        }
+        for constant in constants {
+            let mut scope = cx.clone();
+            if let Some((name, ty)) = self.check_constant(constant, &mut scope) {
+                cx.service_constants.insert(name, ty);
+            }
+        }
+        for function in functions {
+            self.check_function(function, &cx, false);
+        }
+
+        for endpoint in endpoints {
+            let ASTNode::Endpoint { method, path, params, return_type, body, .. } = endpoint else {
+                continue;
+            };
+            let mut cx = cx.clone();
+            let returns = self.resolve_type(return_type);
+            let mut scope = HashMap::new();
+            let mut body_param: Option<&Parameter> = None;
+            for param in params {
+                scope.insert(param.name.as_str(), Local::fixed(self.resolve_type(&param.param_type)));
+                // A struct parameter is read from the request body, so there can only be one
+                if self.structs.contains_key(param.param_type.name.as_str()) {
+                    match body_param {
+                        Some(first) => self.diagnostics.push(
+                            Diagnostic::error(
+                                "E0212",
+                                format!("endpoint `{} {}` has more than one request body parameter", method.to_uppercase(), path),
+                                Label::new(param.span).with_message("second body parameter"),
+                            )
+                            .with_secondary(Label::new(first.span).with_message("the request body is bound here"))
+                            .with_note("struct-typed parameters are read from the JSON request body"),
+                        ),
+                        None => body_param = Some(param),
+                    }
+                }
+            }
+            let declared_by = format!("endpoint `{} {}`", method.to_uppercase(), path);
+            cx.scopes.push(scope);
+            cx.returns = Some((returns.clone(), declared_by.clone()));
+            cx.is_async = true;
+            self.check_statement(body, &mut cx);
+            self.check_falls_through(body, &returns, return_type, &declared_by);
+        }
+    }
+
+    /// Check a function or method body. Instance methods and constructors
+    /// see their instance as `self`.
+    fn check_function(&mut self, function: &'a ASTNode, cx: &Context<'a>, has_self: bool) {
+        let ASTNode::Function { name, type_params, params, return_type, body, is_async, .. } = function else {
+            return;
+        };
+        self.check_type_params(type_params);
+        let outer = self.generics.len();
+        self.generics.extend(type_params);
+        let mut cx = cx.clone();
+        let mut scope = HashMap::new();
+        cx.has_self = has_self && cx.class.is_some();
+        if let Some(class) = cx.class.filter(|_| cx.has_self) {
+            let arguments = self.type_params_of(class).iter().map(|param| Type::Param(param.name.clone())).collect();
+            scope.insert("self", Local::fixed(Type::Named(class.to_string(), arguments)));
+        }
+        for param in params {
+            scope.insert(param.name.as_str(), Local::fixed(self.resolve_type(&param.param_type)));
+        }
+        let returns = match return_type {
+            Some(return_type) => self.resolve_type(return_type),
+            None => Type::Void,
+        };
+        cx.scopes = vec![scope];
+        cx.returns = Some((returns.clone(), format!("`{}`", name)));
+        cx.is_async = *is_async;
+        self.check_statement(body, &mut cx);
+        if let Some(return_type) = return_type {
+            self.check_falls_through(body, &returns, return_type, &format!("`{}`", name));
+        }
+        self.generics.truncate(outer);
+    }
+
+    /// Report an assignment that rebinds a name which cannot be: one not
+    /// declared with `var`, or a constant. Updating a field through such a
+    /// name is allowed if it holds a class instance, since instances are
+    /// shared rather than copied.
+    fn check_assignment_target(&mut self, target: &ASTNode, cx: &Context<'a>) {
+        let mut root = target;
+        while let ASTNode::MemberExpression { object, .. } | ASTNode::IndexExpression { object, .. } = root {
+            root = object;
+        }
+        let ASTNode::Identifier { name, .. } = root else {
+            return;
+        };
+        let (ty, constant) = match cx.binding(name) {
+            Some(Local { mutable: true, .. }) => return,
+            Some(local) => (local.ty.clone(), false),
+            None => match cx.service_constants.get(name.as_str()).or_else(|| self.constants.get(name.as_str())) {
+                Some(ty) => (ty.clone(), true),
+                // Undefined names are reported when the target is inferred
+                None => return,
+            },
+        };
+        let shared = match ty.non_null() {
+            Type::Named(name, _) => self.classes.contains_key(name.as_str()) || self.interfaces.contains_key(name.as_str()),
+            Type::Dynamic | Type::Param(_) => true,
+            _ => false,
+        };
+        if !std::ptr::eq(root, target) && shared {
+            return;
+        }
+        let diagnostic = if constant {
+            Diagnostic::error(
+                "E0216",
+                format!("cannot assign to constant `{}`", name),
+                Label::new(target.span()).with_message("cannot assign"),
+            )
+        } else {
+            Diagnostic::error(
+                "E0216",
+                format!("cannot assign to immutable variable `{}`", name),
+                Label::new(target.span()).with_message("cannot assign"),
+            )
+            .with_note("only variables declared with `var` can be reassigned")
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Report a body declared by `declared_by` to return `returns` that can
+    /// run to its end without returning a value, which would return `null`.
+    fn check_falls_through(&mut self, body: &ASTNode, returns: &Type, return_type: &TypeExpr, declared_by: &str) {
+        if *returns == Type::Void || always_returns(body) {
+            return;
+        }
+        let end = body.span();
+        self.diagnostics.push(
+            Diagnostic::error(
+                "E0215",
+                format!("{} may reach its end without returning a value", declared_by),
+                Label::new(Span::new(end.file_id, end.end.saturating_sub(1), end.end))
+                    .with_message(format!("expected `{}` to be returned before here", returns)),
+            )
+            .with_secondary(Label::new(return_type.span).with_message("declared return type"))
+            .with_suggestion("add a `return` or `throw` on every path", None),
+        );
+    }
+
+    fn check_class(&mut self, class: &'a ASTNode) {
+        let ASTNode::Class { name, type_params, fields, constructor, methods, static_methods, .. } = class else {
+            return;
+        };
+        self.check_type_params(type_params);
+        self.generics = type_params.iter().collect();
+        self.check_fields(fields);
+
+        let cx = Context {
+            class: Some(name.as_str()),
+            ..Context::default()
+        };
+        for method in constructor.as_deref().into_iter().chain(methods) {
+            self.check_function(method, &cx, true);
+        }
+        // Static methods have no instance to take the type arguments from
+        self.generics.clear();
+        for method in static_methods {
+            self.check_function(method, &cx, false);
+        }
+    }
+
+    /// Field types must exist and defaults must match them.
+    fn check_fields(&mut self, fields: &'a [Field]) {
+        for field in fields {
+            let declared = self.resolve_type(&field.field_type);
+            if let Some(default) = &field.default {
+                let found = self.infer(default, &mut Context::default());
+                self.expect_type(&found, &declared, default.span(), None);
+            }
+        }
+    }
+
+    fn check_block(&mut self, statements: &'a [ASTNode], cx: &mut Context<'a>) {
+        cx.scopes.push(HashMap::new());
+        for statement in statements {
+            self.check_statement(statement, cx);
+        }
+        cx.scopes.pop();
+    }
+
+    fn check_statement(&mut self, statement: &'a ASTNode, cx: &mut Context<'a>) {
+        match statement {
+            ASTNode::Block { statements, .. } => self.check_block(statements, cx),
+            ASTNode::VariableDeclaration { name, mutable, type_annotation, initializer, span } => {
+                let declared = type_annotation
+                    .as_ref()
+                    .map(|annotation| self.resolve_type(annotation));
+                let found = initializer.as_ref().map(|initializer| self.infer(initializer, cx));
+                let ty = match (declared, found) {
+                    (Some(declared), Some(found)) => {
+                        let initializer = initializer.as_deref().map(ASTNode::span).unwrap_or(*span);
+                        self.expect_type(&found, &declared, initializer, None);
+                        declared
+                    }
+                    (Some(declared), None) => declared,
+                    // A variable starting out as `null` may later hold anything
+                    (None, Some(Type::Null)) | (None, None) => Type::Dynamic,
+                    (None, Some(found)) => found,
+                };
+                cx.define(name, Local { ty, mutable: *mutable });
+            }
+            ASTNode::Assignment { target, operator, value, .. } => {
+                self.check_assignment_target(target, cx);
+                let target_type = self.infer(target, cx);
+                let mut found = self.infer(value, cx);
+                if let Some(operator) = operator {
+                    found = self.binary_type(*operator, &target_type, &found, statement.span());
+                }
+                self.expect_type(&found, &target_type, value.span(), None);
+            }
+            ASTNode::ExpressionStatement { expression, .. } => {
+                self.infer(expression, cx);
+            }
+            ASTNode::ReturnStatement { expression, span } => {
+                let (found, span) = match expression {
+                    Some(expression) => (self.infer(expression, cx), expression.span()),
+                    None => (Type::Void, *span),
+                };
+                if let Some((expected, declared_by)) = cx.returns.clone() {
+                    let note = format!("{} is declared to return `{}`", declared_by, expected);
+                    self.expect_type(&found, &expected, span, Some(note));
+                }
+            }
+            ASTNode::IfStatement { condition, then_branch, else_branch, .. } => {
+                self.check_condition(condition, cx);
+                // Comparing an optional local against `null` narrows it to
+                // its non-null type in the branch where it cannot be null
+                let narrowed = null_check(condition).and_then(|(name, is_null)| match cx.binding(name) {
+                    Some(Local { ty: Type::Optional(inner), mutable }) => {
+                        Some((name, Local { ty: (**inner).clone(), mutable: *mutable }, is_null))
+                    }
+                    _ => None,
+                });
+                let narrow = |applies: bool| match &narrowed {
+                    Some((name, inner, _)) if applies => HashMap::from([(*name, inner.clone())]),
+                    _ => HashMap::new(),
+                };
+                let is_null = narrowed.as_ref().is_some_and(|(_, _, is_null)| *is_null);
+                cx.scopes.push(narrow(!is_null));
+                self.check_statement(then_branch, cx);
+                cx.scopes.pop();
+                if let Some(else_branch) = else_branch {
+                    cx.scopes.push(narrow(is_null));
+                    self.check_statement(else_branch, cx);
+                    cx.scopes.pop();
+                } else if is_null && always_returns(then_branch) {
+                    // `if x == null { return ...; }` leaves `x` non-null after
+                    if let Some((name, inner, _)) = narrowed {
+                        cx.define(name, inner);
+                    }
+                }
+            }
+            ASTNode::WhileStatement { condition, body, .. } => {
+                self.check_condition(condition, cx);
+                self.check_statement(body, cx);
+            }
+            ASTNode::ForStatement { variable, iterable, body, .. } => {
+                let iterable_type = self.infer(iterable, cx);
+                let element = match self.unwrap_optional(iterable_type, iterable.span()) {
+                    Type::List(element) => *element,
+                    Type::Map(key, _) => *key,
+                    Type::String => Type::String,
+                    Type::Dynamic => Type::Dynamic,
+                    other => {
+                        self.diagnostics.push(Diagnostic::error(
+                            "E0208",
+                            format!("cannot iterate over `{}`", other),
+                            Label::new(iterable.span()).with_message("not a List, Map or String"),
+                        ));
+                        Type::Dynamic
+                    }
+                };
+                cx.scopes.push(HashMap::from([(variable.as_str(), Local::fixed(element))]));
+                self.check_statement(body, cx);
+                cx.scopes.pop();
+            }
+            ASTNode::Match { subject, arms, span } => {
+                self.check_match(subject, arms, *span, true, cx);
+            }
+            ASTNode::TryStatement { body, catches, .. } => {
+                self.check_statement(body, cx);
+                for clause in catches {
+                    let error = Type::named("Error");
+                    let ty = match &clause.error_type {
+                        Some(annotation) => {
+                            let ty = self.resolve_type(annotation);
+                            let note = "only `Error` and its subclasses can be caught".to_string();
+                            self.expect_type(&ty, &error, annotation.span, Some(note));
+                            ty
+                        }
+                        None => error,
+                    };
+                    cx.scopes.push(HashMap::from([(clause.name.as_str(), Local::fixed(ty))]));
+                    self.check_statement(&clause.body, cx);
+                    cx.scopes.pop();
+                }
+            }
+            ASTNode::DeferStatement { statement, .. } => {
+                // Names the deferred statement declares end with it
+                cx.scopes.push(HashMap::new());
+                self.check_statement(statement, cx);
+                cx.scopes.pop();
+            }
+            ASTNode::BreakStatement { .. } | ASTNode::ContinueStatement { .. } => {}
+            expression => {
+                self.infer(expression, cx);
+            }
+        }
+    }
+
+    fn check_condition(&mut self, condition: &'a ASTNode, cx: &mut Context<'a>) {
+        let found = self.infer(condition, cx);
+        self.expect_type(&found, &Type::Bool, condition.span(), None);
+    }
+
+    /// Report `found` where `expected` is required, unless assignable.
+    fn expect_type(&mut self, found: &Type, expected: &Type, span: Span, note: Option<String>) {
+        if self.is_assignable(found, expected) {
+            return;
+        }
+        let mut diagnostic = Diagnostic::error(
+            "E0204",
+            "mismatched types",
+            Label::new(span).with_message(format!("expected `{}`, found `{}`", expected, found)),
+        );
+        if let Some(note) = note {
+            diagnostic = diagnostic.with_note(note);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The static type of `expression`, reporting any problem in it.
+    fn infer(&mut self, expression: &'a ASTNode, cx: &mut Context<'a>) -> Type {
+        match expression {
+            ASTNode::StringLiteral { .. } => Type::String,
+            ASTNode::InterpolatedString { parts, .. } => {
+                for part in parts {
+                    let StringPart::Expression(expression) = part else {
+                        continue;
+                    };
+                    if self.infer(expression, cx) == Type::Void {
+                        self.diagnostics.push(Diagnostic::error(
+                            "E0208",
+                            "cannot interpolate a value of type `Void`",
+                            Label::new(expression.span()).with_message("this has no value"),
+                        ));
+                    }
+                }
+                Type::String
+            }
+            ASTNode::NumberLiteral { is_float: false, .. } => Type::Int,
+            ASTNode::NumberLiteral { is_float: true, .. } => Type::Float,
+            ASTNode::BooleanLiteral { .. } => Type::Bool,
+            ASTNode::NullLiteral { .. } => Type::Null,
+            ASTNode::ArrayLiteral { elements, .. } => {
+                let element = self.join_all(elements.iter(), cx);
+                Type::List(Box::new(element))
+            }
+            ASTNode::ObjectLiteral { fields, .. } => {
+                let value = self.join_all(fields.iter().map(|(_, value)| value), cx);
+                Type::Map(Box::new(Type::String), Box::new(value))
+            }
+            ASTNode::StructLiteral { name, fields, span } => self.struct_literal_type(name, fields, *span, cx),
+            ASTNode::GroupingExpression { expression, .. } => self.infer(expression, cx),
+            ASTNode::Identifier { name, span } => self.identifier_type(name, *span, cx),
+            ASTNode::UnaryExpression { operator, operand, span } => {
+                let operand = self.infer(operand, cx);
+                let (expected, result) = match operator {
+                    UnaryOperator::Not => (Type::Bool, Type::Bool),
+                    UnaryOperator::Negate if operand == Type::Int => (Type::Int, Type::Int),
+                    UnaryOperator::Negate => (Type::Float, Type::Float),
+                };
+                if !self.is_assignable(&operand, &expected) {
+                    self.diagnostics.push(Diagnostic::error(
+                        "E0208",
+                        format!("cannot apply `{}` to `{}`", operator, operand),
+                        Label::new(*span).with_message(format!("expected `{}`", expected)),
+                    ));
+                }
+                result
+            }
+            ASTNode::BinaryExpression { operator, left, right, span } => {
+                let left = self.infer(left, cx);
+                let right = self.infer(right, cx);
+                self.binary_type(*operator, &left, &right, *span)
+            }
+            ASTNode::MemberExpression { object: target, member, span } => {
+                if let Some((name, variants)) = self.enum_of(target, cx) {
+                    // Nothing decides the type arguments of a variant without fields
+                    let arguments = vec![Type::Dynamic; self.type_params_of(&name).len()];
+                    let ty = Type::Named(name, arguments);
+                    match variants.iter().find(|variant| variant.name == *member) {
+                        Some(variant) if !variant.fields.is_empty() => {
+                            let arity = variant.fields.len();
+                            self.wrong_arity(member, arity, arity, 0, *span);
+                        }
+                        Some(_) => {}
+                        None => {
+                            self.no_member(&ty, member, "variant", *span);
+                        }
+                    }
+                    return ty;
+                }
+                let object = self.infer(target, cx);
+                let object = self.unwrap_optional(object, target.span());
+                self.member_type(&object, member, *span)
+            }
+            ASTNode::IndexExpression { object: target, index, span } => {
+                let object = self.infer(target, cx);
+                let object = self.unwrap_optional(object, target.span());
+                let index = self.infer(index, cx);
+                match object {
+                    Type::List(element) => {
+                        self.expect_type(&index, &Type::Int, *span, None);
+                        *element
+                    }
+                    Type::String => {
+                        self.expect_type(&index, &Type::Int, *span, None);
+                        Type::String
+                    }
+                    Type::Map(key, value) => {
+                        self.expect_type(&index, &key, *span, None);
+                        *value
+                    }
+                    Type::Dynamic => Type::Dynamic,
+                    other => {
+                        self.diagnostics.push(Diagnostic::error(
+                            "E0208",
+                            format!("cannot index into `{}`", other),
+                            Label::new(*span).with_message("not a List, Map or String"),
+                        ));
+                        Type::Dynamic
+                    }
+                }
+            }
+            ASTNode::CallExpression { callee, arguments, span } => self.call_type(callee, arguments, *span, cx),
+            ASTNode::Match { subject, arms, span } => self.check_match(subject, arms, *span, false, cx),
+            ASTNode::SuperExpression { span } => {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0208",
+                    "`super` can only be used to call a method",
+                    Label::new(*span),
+                ));
+                Type::Dynamic
+            }
+            ASTNode::ThrowExpression { error, .. } => {
+                let found = self.infer(error, cx);
+                let note = "only `Error` and its subclasses can be thrown".to_string();
+                self.expect_type(&found, &Type::named("Error"), error.span(), Some(note));
+                // Control never continues past a `throw`, so it fits wherever a value is expected
+                Type::Dynamic
+            }
+            ASTNode::PropagateExpression { expression: operand, span } => self.propagate_type(operand, *span, cx),
+            ASTNode::AwaitExpression { future, span } => {
+                let found = self.infer(future, cx);
+                if !cx.is_async {
+                    let mut diagnostic = Diagnostic::error(
+                        "E0208",
+                        "`await` is only allowed in `async` functions and endpoints",
+                        Label::new(*span).with_message("cannot wait here"),
+                    );
+                    if let Some((_, declared_by)) = &cx.returns {
+                        diagnostic = diagnostic.with_suggestion(format!("make {} an `async fn`", declared_by), None);
+                    }
+                    self.diagnostics.push(diagnostic);
+                }
+                match found {
+                    Type::Future(value) => *value,
+                    Type::Dynamic => Type::Dynamic,
+                    other => {
+                        self.diagnostics.push(Diagnostic::error(
+                            "E0208",
+                            format!("cannot `await` a value of type `{}`", other),
+                            Label::new(future.span()).with_message("not a `Future`"),
+                        ));
+                        Type::Dynamic
+                    }
+                }
+            }
+            _ => Type::Dynamic,
+        }
+    }
+
+    /// The type of `operand?`: what its `Ok` or `Some` holds. The `Err` or
+    /// `None` is returned instead, so the body must return the same kind
+    /// of value, with a compatible error.
+    fn propagate_type(&mut self, operand: &'a ASTNode, span: Span, cx: &mut Context<'a>) -> Type {
+        let found = self.infer(operand, cx);
+        let (kind, value, error) = match &found {
+            Type::Named(name, arguments) if name == "Result" || name == "Option" => {
+                let argument = |index: usize| arguments.get(index).cloned().unwrap_or(Type::Dynamic);
+                (name.as_str(), argument(0), (name == "Result").then(|| argument(1)))
+            }
+            Type::Dynamic => return Type::Dynamic,
+            other => {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0208",
+                    "the `?` operator can only be applied to a `Result` or an `Option`",
+                    Label::new(span).with_message(format!("found `{}`", other)),
+                ));
+                return Type::Dynamic;
+            }
+        };
+        match cx.returns.clone() {
+            Some((Type::Dynamic, _)) => {}
+            Some((Type::Named(name, arguments), declared_by)) if name == kind => {
+                if let Some(error) = error {
+                    let expected = arguments.get(1).cloned().unwrap_or(Type::Dynamic);
+                    let note = format!("{} is declared to return `{}`", declared_by, Type::Named(name, arguments));
+                    self.expect_type(&error, &expected, span, Some(note));
+                }
+            }
+            returns => {
+                let mut diagnostic = Diagnostic::error(
+                    "E0208",
+                    format!("the `?` operator can only be used in a body that returns `{}`", kind),
+                    Label::new(span).with_message(format!("this returns the `{}` early", kind)),
+                );
+                if let Some((returns, declared_by)) = returns {
+                    diagnostic = diagnostic.with_note(format!("{} is declared to return `{}`", declared_by, returns));
+                }
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        value
+    }
+
+    /// Check a struct literal's fields against the struct's declaration:
+    /// each must exist and match its type, and only fields with a default
+    /// or an optional type may be left out.
+    fn struct_literal_type(
+        &mut self,
+        name: &str,
+        given: &'a [(String, ASTNode)],
+        span: Span,
+        cx: &mut Context<'a>,
+    ) -> Type {
+        let Some(declared) = self.struct_fields(name, span) else {
+            let mut diagnostic = Diagnostic::error(
+                "E0203",
+                format!("cannot find struct `{}`", name),
+                Label::new(span).with_message("not a struct"),
+            );
+            if self.classes.contains_key(name) {
+                diagnostic = diagnostic.with_suggestion(format!("`{}` is a class; create it with `{}(...)`", name, name), None);
+            } else if self.structs.contains_key(name) {
+                diagnostic = diagnostic.with_suggestion(format!("import `{}` from the file declaring it", name), None);
+            }
+            self.diagnostics.push(diagnostic);
+            for (_, value) in given {
+                self.infer(value, cx);
+            }
+            return Type::Dynamic;
+        };
+
+        let type_params = self.type_params_of(name);
+        let generics: Vec<&str> = type_params.iter().map(|param| param.name.as_str()).collect();
+        let mut checked = Vec::new();
+        let mut seen = HashSet::new();
+        for (field_name, value) in given {
+            let found = self.infer(value, cx);
+            if !seen.insert(field_name.as_str()) {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0211",
+                    format!("field `{}` is given more than once", field_name),
+                    Label::new(value.span()).with_message("given again here"),
+                ));
+                continue;
+            }
+            match declared.iter().find(|field| field.name == *field_name) {
+                Some(field) => {
+                    let expected = Type::from_annotation_in(&field.field_type, &generics);
+                    checked.push((found, expected, value.span()));
+                }
+                None => {
+                    self.no_member(&Type::named(name), field_name, "field", value.span());
+                }
+            }
+        }
+        let bindings = self.infer_bindings(type_params.iter().collect(), HashMap::new(), &checked, span);
+
+        let missing: Vec<&str> = declared
+            .iter()
+            .filter(|field| field.default.is_none() && !field.field_type.optional && !seen.contains(field.name.as_str()))
+            .map(|field| field.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            let listed = missing.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ");
+            self.diagnostics.push(Diagnostic::error(
+                "E0211",
+                format!("missing field{} {} in `{}`", if missing.len() == 1 { "" } else { "s" }, listed, name),
+                Label::new(span).with_message(format!("`{}` has no default", missing[0])),
+            ));
+        }
+        instance_type(name, type_params, &bindings)
+    }
+
+    /// Check a `match` and return its type: the join of its arms' types
+    /// when used as an expression. Every value of the subject's type must
+    /// be covered by some arm.
+    fn check_match(
+        &mut self,
+        subject: &'a ASTNode,
+        arms: &'a [MatchArm],
+        span: Span,
+        statement: bool,
+        cx: &mut Context<'a>,
+    ) -> Type {
+        let subject_type = self.infer(subject, cx);
+        let mut rows = Vec::new();
+        let mut result: Option<Type> = None;
+        for arm in arms {
+            cx.scopes.push(HashMap::new());
+            rows.push(vec![self.check_pattern(&arm.pattern, &subject_type, cx)]);
+            if statement {
+                self.check_statement(&arm.body, cx);
+            } else {
+                let ty = self.infer(&arm.body, cx);
+                result = Some(match result {
+                    None => ty,
+                    Some(expected) if self.is_assignable(&ty, &expected) => expected,
+                    Some(expected) if self.is_assignable(&expected, &ty) => ty,
+                    Some(expected) => match expected.clone().join(ty.clone()) {
+                        Type::Dynamic => {
+                            self.diagnostics.push(
+                                Diagnostic::error(
+                                    "E0204",
+                                    "`match` arms have incompatible types",
+                                    Label::new(arm.body.span()).with_message(format!("expected `{}`, found `{}`", expected, ty)),
+                                )
+                                .with_note("every arm of a `match` used as a value must produce the same type"),
+                            );
+                            expected
+                        }
+                        joined => joined,
+                    },
+                });
+            }
+            cx.scopes.pop();
+        }
+
+        if let Some(witness) = self.uncovered(rows, std::slice::from_ref(&subject_type)) {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    "E0213",
+                    format!("non-exhaustive match: `{}` not covered", witness[0]),
+                    Label::new(subject.span()).with_message(format!("pattern `{}` not covered", witness[0])),
+                )
+                .with_secondary(Label::new(span))
+                .with_suggestion("add an arm for it, or a `_` wildcard arm", None),
+            );
+        }
+        if statement {
+            Type::Void
+        } else {
+            result.unwrap_or(Type::Dynamic)
+        }
+    }
+
+    /// Check `pattern` against the type of the value it matches, defining
+    /// the names it binds, and reduce it to its shape.
+    fn check_pattern(&mut self, pattern: &'a Pattern, expected: &Type, cx: &mut Context<'a>) -> Shape {
+        match pattern {
+            Pattern::Wildcard { .. } => Shape::Any,
+            Pattern::Binding { name, .. } => {
+                cx.define(name, Local::fixed(expected.clone()));
+                Shape::Any
+            }
+            Pattern::Literal { value, span } => {
+                let found = self.infer(value, cx);
+                self.expect_type(&found, expected, *span, Some(format!("the value matched is a `{}`", expected)));
+                let name = match value {
+                    ASTNode::NumberLiteral { value, .. } => value.to_string(),
+                    ASTNode::StringLiteral { value, .. } => format!("{:?}", value),
+                    ASTNode::BooleanLiteral { value, .. } => value.to_string(),
+                    _ => "null".to_string(),
+                };
+                Shape::Constructor(name, Vec::new())
+            }
+            Pattern::Variant { enum_name, variant, fields, span } => {
+                let Some(variants) = self.enum_variants(enum_name, *span) else {
+                    let mut diagnostic = Diagnostic::error(
+                        "E0203",
+                        format!("cannot find enum `{}`", enum_name),
+                        Label::new(*span).with_message("not an enum"),
+                    );
+                    if self.enums.contains_key(enum_name.as_str()) {
+                        diagnostic = diagnostic.with_suggestion(format!("import `{}` from the file declaring it", enum_name), None);
+                    }
+                    self.diagnostics.push(diagnostic);
+                    for field in fields {
+                        self.check_pattern(field, &Type::Dynamic, cx);
+                    }
+                    return Shape::Any;
+                };
+                let type_params = self.type_params_of(enum_name);
+                let bindings = match expected.non_null() {
+                    Type::Named(name, arguments) if name == enum_name => type_bindings(type_params, arguments),
+                    _ => type_bindings(type_params, &[]),
+                };
+                let ty = instance_type(enum_name, type_params, &bindings);
+                self.expect_type(&ty, expected.non_null(), *span, Some(format!("the value matched is a `{}`", expected)));
+                let Some(declared) = variants.iter().find(|declared| declared.name == *variant) else {
+                    self.no_member(&ty, variant, "variant", *span);
+                    for field in fields {
+                        self.check_pattern(field, &Type::Dynamic, cx);
+                    }
+                    return Shape::Any;
+                };
+                if fields.len() != declared.fields.len() {
+                    let count = |n: usize| format!("{} field{}", n, if n == 1 { "" } else { "s" });
+                    self.diagnostics.push(Diagnostic::error(
+                        "E0206",
+                        format!("variant `{}.{}` has {} but the pattern has {}", enum_name, variant, count(declared.fields.len()), count(fields.len())),
+                        Label::new(*span).with_message(format!("expected {}", count(declared.fields.len()))),
+                    ));
+                    for field in fields {
+                        self.check_pattern(field, &Type::Dynamic, cx);
+                    }
+                    return Shape::Any;
+                }
+                let shapes = fields
+                    .iter()
+                    .zip(&declared.fields)
+                    .map(|(field, param)| self.check_pattern(field, &instantiate(&param.param_type, &bindings), cx))
+                    .collect();
+                Shape::Constructor(format!("{}.{}", enum_name, variant), shapes)
+            }
+        }
+    }
+
+    /// Every constructor of `ty` with the types of its fields, if there are
+    /// finitely many: the two booleans, an enum's variants, and `null` for
+    /// an optional one of those.
+    fn constructors(&self, ty: &Type) -> Option<Vec<(String, Vec<Type>)>> {
+        match ty {
+            Type::Bool => Some(vec![("true".to_string(), Vec::new()), ("false".to_string(), Vec::new())]),
+            Type::Null => Some(vec![("null".to_string(), Vec::new())]),
+            Type::Optional(inner) => {
+                let mut constructors = self.constructors(inner)?;
+                constructors.push(("null".to_string(), Vec::new()));
+                Some(constructors)
+            }
+            Type::Named(name, arguments) => match self.enums.get(name.as_str()) {
+                Some(ASTNode::Enum { type_params, variants, .. }) => Some(
+                    variants
+                        .iter()
+                        .map(|variant| {
+                            let bindings = type_bindings(type_params, arguments);
+                            let fields = variant.fields.iter().map(|field| instantiate(&field.param_type, &bindings)).collect();
+                            (format!("{}.{}", name, variant.name), fields)
+                        })
+                        .collect(),
+                ),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Values of `types` that no row matches, as one shape per column, or
+    /// `None` if the rows cover every value. Each column is split by the
+    /// constructors of its type; a type with infinitely many values is
+    /// only covered by `_` or a binding.
+    fn uncovered(&self, rows: Vec<Vec<Shape>>, types: &[Type]) -> Option<Vec<Shape>> {
+        let Some((ty, rest)) = types.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        if rows.is_empty() {
+            return Some(vec![Shape::Any; types.len()]);
+        }
+        let split = rows.iter().any(|row| matches!(row[0], Shape::Constructor(..)));
+        match self.constructors(ty) {
+            Some(constructors) if split => {
+                for (name, fields) in constructors {
+                    let specialized = rows
+                        .iter()
+                        .filter_map(|row| {
+                            let head = match &row[0] {
+                                Shape::Any => vec![Shape::Any; fields.len()],
+                                Shape::Constructor(other, shapes) if *other == name => shapes.clone(),
+                                Shape::Constructor(..) => return None,
+                            };
+                            Some(head.into_iter().chain(row[1..].iter().cloned()).collect())
+                        })
+                        .collect();
+                    let types: Vec<Type> = fields.iter().chain(rest).cloned().collect();
+                    if let Some(mut witness) = self.uncovered(specialized, &types) {
+                        let tail = witness.split_off(fields.len());
+                        return Some(std::iter::once(Shape::Constructor(name, witness)).chain(tail).collect());
+                    }
+                }
+                None
+            }
+            _ => {
+                let rows = rows
+                    .into_iter()
+                    .filter(|row| matches!(row[0], Shape::Any))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let witness = self.uncovered(rows, rest)?;
+                Some(std::iter::once(Shape::Any).chain(witness).collect())
+            }
+        }
+    }
+
+    /// The join of the types of `expressions`; dynamic if there are none.
+    fn join_all(&mut self, expressions: impl Iterator<Item = &'a ASTNode>, cx: &mut Context<'a>) -> Type {
+        let mut joined: Option<Type> = None;
+        for expression in expressions {
+            let ty = self.infer(expression, cx);
+            joined = Some(match joined {
+                Some(joined) => joined.join(ty),
+                None => ty,
+            });
+        }
+        joined.unwrap_or(Type::Dynamic)
+    }
+
+    /// The type under an optional one, reporting that the value at `span`
+    /// may be `null` where it is used as if it were not.
+    fn unwrap_optional(&mut self, ty: Type, span: Span) -> Type {
+        match ty {
+            Type::Optional(inner) => {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        "E0210",
+                        format!("value of type `{}` may be null", Type::Optional(inner.clone())),
+                        Label::new(span).with_message("used here as if it were not null"),
+                    )
+                    .with_suggestion("check it against `null` first, or give a default with `??`", None),
+                );
+                *inner
+            }
+            Type::Null => {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0210",
+                    "value is always null",
+                    Label::new(span).with_message("used here as if it were not null"),
+                ));
+                Type::Dynamic
+            }
+            other => other,
+        }
+    }
+
+    fn identifier_type(&mut self, name: &str, span: Span, cx: &Context<'a>) -> Type {
+        if let Some(ty) = cx.local(name) {
+            return ty.clone();
+        }
+        if let Some(ty) = cx.service_constants.get(name) {
+            return ty.clone();
+        }
+        if let Some(ty) = self.constants.get(name).filter(|_| self.is_visible(name, span)) {
+            return ty.clone();
+        }
+        let mut diagnostic = Diagnostic::error(
+            "E0205",
+            format!("cannot find value `{}` in this scope", name),
+            Label::new(span).with_message("not found in this scope"),
+        );
+        if self.function(name, span, cx).is_some() || self.class(name, span).is_some() {
+            diagnostic = diagnostic.with_note(format!("`{}` can only be called", name));
+        } else if self.constants.contains_key(name) || self.functions.contains_key(name) {
+            diagnostic = diagnostic.with_suggestion(format!("import `{}` from the file declaring it", name), None);
+        }
+        self.diagnostics.push(diagnostic);
+        Type::Dynamic
+    }
+
+    fn binary_type(&mut self, operator: BinaryOperator, left: &Type, right: &Type, span: Span) -> Type {
+        use BinaryOperator::*;
+        let dynamic = *left == Type::Dynamic || *right == Type::Dynamic;
+        let result = match operator {
+            Equal | NotEqual => Some(Type::Bool),
+            And | Or => [left, right]
+                .iter()
+                .all(|operand| matches!(operand, Type::Bool | Type::Dynamic))
+                .then_some(Type::Bool),
+            NullCoalesce => Some(match left {
+                Type::Optional(inner) => inner.as_ref().clone().join(right.clone()),
+                Type::Null => right.clone(),
+                left => left.clone(),
+            }),
+            Add if matches!(left, Type::String) || matches!(right, Type::String) => Some(Type::String),
+            Add if matches!((left, right), (Type::List(_), Type::List(_))) => Some(left.clone().join(right.clone())),
+            Add | Subtract | Multiply | Modulo if left.is_numeric() && right.is_numeric() => {
+                Some(if dynamic {
+                    Type::Dynamic
+                } else if *left == Type::Int && *right == Type::Int {
+                    Type::Int
+                } else {
+                    Type::Float
+                })
+            }
+            Divide if left.is_numeric() && right.is_numeric() => Some(Type::Float),
+            Less | LessEqual | Greater | GreaterEqual
+                if (left.is_numeric() && right.is_numeric())
+                    || matches!((left, right), (Type::String | Type::Dynamic, Type::String | Type::Dynamic)) =>
+            {
+                Some(Type::Bool)
+            }
+            Range if left.is_numeric() && right.is_numeric() => Some(Type::List(Box::new(Type::Int))),
+            _ if dynamic => Some(Type::Dynamic),
+            _ => None,
+        };
+        result.unwrap_or_else(|| {
+            self.diagnostics.push(Diagnostic::error(
+                "E0208",
+                format!("cannot apply `{}` to `{}` and `{}`", operator, left, right),
+                Label::new(span).with_message("unsupported operand types"),
+            ));
+            Type::Dynamic
+        })
+    }
+
+    fn member_type(&mut self, object: &Type, member: &str, span: Span) -> Type {
+        match (object, member) {
+            (Type::String | Type::List(_), "length") => Type::Int,
+            (Type::Map(_, value), _) => (**value).clone(),
+            (Type::Dynamic, _) => Type::Dynamic,
+            (Type::Named(class, arguments), _) if self.classes.contains_key(class.as_str()) => {
+                let field = self.lineage(class).into_iter().find_map(|ancestor| match ancestor {
+                    ASTNode::Class { fields, .. } => fields.iter().find(|field| field.name == member),
+                    _ => None,
+                });
+                match field {
+                    Some(field) => instantiate(&field.field_type, &self.class_bindings(class, arguments)),
+                    None => self.no_member(object, member, "field", span),
+                }
+            }
+            (Type::Named(name, arguments), _) if self.structs.contains_key(name.as_str()) => {
+                let (field, type_params) = match self.structs[name.as_str()] {
+                    ASTNode::Struct { fields, type_params, .. } => {
+                        (fields.iter().find(|field| field.name == member), type_params.as_slice())
+                    }
+                    _ => (None, [].as_slice()),
+                };
+                match field {
+                    Some(field) => instantiate(&field.field_type, &type_bindings(type_params, arguments)),
+                    None => self.no_member(object, member, "field", span),
+                }
+            }
+            _ => self.no_member(object, member, "field", span),
+        }
+    }
+
+    fn no_member(&mut self, object: &Type, member: &str, kind: &str, span: Span) -> Type {
+        self.diagnostics.push(Diagnostic::error(
+            "E0207",
+            format!("no {} `{}` on type `{}`", kind, member, object),
+            Label::new(span).with_message(format!("unknown {}", kind)),
+        ));
+        Type::Dynamic
+    }
+
+    fn call_type(&mut self, callee: &'a ASTNode, arguments: &'a [ASTNode], span: Span, cx: &mut Context<'a>) -> Type {
+        match callee {
+            ASTNode::Identifier { name, span: name_span } => {
+                if self.class(name, *name_span).is_some() {
+                    let init = self.lineage(name).into_iter().find_map(|class| match class {
+                        ASTNode::Class { constructor, .. } => constructor.as_deref(),
+                        _ => None,
+                    });
+                    let params = match init {
+                        Some(ASTNode::Function { params, .. }) => params.as_slice(),
+                        _ => &[],
+                    };
+                    // The class's own type arguments are inferred from the
+                    // constructor's arguments
+                    let type_params = self.type_params_of(name);
+                    let mut bindings = self.class_bindings(name, &[]);
+                    for param in type_params {
+                        bindings.remove(&param.name);
+                    }
+                    let signature = Signature {
+                        params,
+                        generics: type_params.iter().collect(),
+                        bindings,
+                    };
+                    let bindings = self.check_arguments(name, signature, arguments, span, cx);
+                    return instance_type(name, type_params, &bindings);
+                }
+                if let Some(function) = self.function(name, *name_span, cx) {
+                    return self.check_call(function, name, arguments, span, cx, HashMap::new());
+                }
+                if let Some(ty) = self.builtin_call_type(name, arguments, span, cx) {
+                    return ty;
+                }
+                self.infer_all(arguments, cx);
+                let mut diagnostic = Diagnostic::error(
+                    "E0205",
+                    format!("cannot find function `{}` in this scope", name),
+                    Label::new(*name_span).with_message("not found in this scope"),
+                );
+                if cx.local(name).is_some() {
+                    diagnostic = Diagnostic::error(
+                        "E0208",
+                        format!("`{}` is not a function", name),
+                        Label::new(*name_span).with_message("a variable cannot be called"),
+                    );
+                } else if self.functions.contains_key(name.as_str()) || self.classes.contains_key(name.as_str()) {
+                    diagnostic = diagnostic.with_suggestion(format!("import `{}` from the file declaring it", name), None);
+                }
+                self.diagnostics.push(diagnostic);
+                Type::Dynamic
+            }
+            ASTNode::MemberExpression { object, member, span: member_span } => {
+                // `Enum.Variant(...)`, `super.method(...)`,
+                // `Class.staticMethod(...)` or a method call
+                if let Some((name, variants)) = self.enum_of(object, cx) {
+                    let type_params = self.type_params_of(&name);
+                    return match variants.iter().find(|variant| variant.name == *member) {
+                        Some(variant) => {
+                            let signature = Signature {
+                                params: &variant.fields,
+                                generics: type_params.iter().collect(),
+                                bindings: HashMap::new(),
+                            };
+                            let bindings = self.check_arguments(member, signature, arguments, span, cx);
+                            instance_type(&name, type_params, &bindings)
+                        }
+                        None => {
+                            self.infer_all(arguments, cx);
+                            self.no_member(&Type::named(name.clone()), member, "variant", *member_span);
+                            instance_type(&name, type_params, &HashMap::new())
+                        }
+                    };
+                }
+                if let ASTNode::SuperExpression { .. } = &**object {
+                    let superclass = cx.class.and_then(|class| match self.classes.get(class) {
+                        Some(ASTNode::Class { superclass, .. }) => superclass.as_deref(),
+                        _ => None,
+                    });
+                    let Some(superclass) = superclass else {
+                        self.infer_all(arguments, cx);
+                        return Type::Dynamic;
+                    };
+                    let method = match (cx.has_self, member.as_str()) {
+                        (true, "init") => self.lineage(superclass).into_iter().find_map(|class| match class {
+                            ASTNode::Class { constructor, .. } => constructor.as_deref(),
+                            _ => None,
+                        }),
+                        (true, _) => self.find_method(superclass, member),
+                        (false, _) => self.find_static(superclass, member),
+                    };
+                    return match method {
+                        Some(method) => {
+                            let bindings = self.class_bindings(superclass, &[]);
+                            self.check_call(method, member, arguments, span, cx, bindings)
+                        }
+                        None => {
+                            self.infer_all(arguments, cx);
+                            self.no_member(&Type::named(superclass), member, "method", *member_span)
+                        }
+                    };
+                }
+                if let ASTNode::Identifier { name, span: name_span } = &**object {
+                    if cx.local(name).is_none() && self.class(name, *name_span).is_some() {
+                        return match self.find_static(name, member) {
+                            Some(method) => self.check_call(method, member, arguments, span, cx, HashMap::new()),
+                            None => {
+                                self.infer_all(arguments, cx);
+                                self.no_member(&Type::named(name.clone()), member, "static method", *member_span)
+                            }
+                        };
+                    }
+                }
+
+                let receiver = self.infer(object, cx);
+                self.method_type(&receiver, member, arguments, span, *member_span, cx)
+            }
+            other => {
+                self.infer(other, cx);
+                self.infer_all(arguments, cx);
+                self.diagnostics.push(Diagnostic::error(
+                    "E0208",
+                    "expression is not callable",
+                    Label::new(other.span()),
+                ));
+                Type::Dynamic
+            }
+        }
+    }
+
+    fn method_type(
+        &mut self,
+        receiver: &Type,
+        member: &str,
+        arguments: &'a [ASTNode],
+        span: Span,
+        member_span: Span,
+        cx: &mut Context<'a>,
+    ) -> Type {
+        match receiver {
+            Type::Named(class, type_arguments) if self.classes.contains_key(class.as_str()) => {
+                match self.find_method(class, member) {
+                    Some(method) => {
+                        let bindings = self.class_bindings(class, type_arguments);
+                        self.check_call(method, member, arguments, span, cx, bindings)
+                    }
+                    None => {
+                        self.infer_all(arguments, cx);
+                        self.no_member(receiver, member, "method", member_span)
+                    }
+                }
+            }
+            Type::Named(..) | Type::Param(_) => {
+                // A type parameter has the methods of the interfaces bounding it
+                let signature = match receiver {
+                    Type::Param(param) => self
+                        .bounds_of(param)
+                        .iter()
+                        .find_map(|bound| self.interface_method(&bound.name, member)),
+                    Type::Named(interface, _) => self.interface_method(interface, member),
+                    _ => None,
+                };
+                match signature {
+                    Some(signature) => {
+                        let signature_params = Signature {
+                            params: &signature.params,
+                            generics: Vec::new(),
+                            bindings: HashMap::new(),
+                        };
+                        self.check_arguments(member, signature_params, arguments, span, cx);
+                        return_type_of(&signature.return_type)
+                    }
+                    None => {
+                        self.infer_all(arguments, cx);
+                        self.no_member(receiver, member, "method", member_span)
+                    }
+                }
+            }
+            Type::String => {
+                let Some((params, required, result)) = string_method(member) else {
+                    self.infer_all(arguments, cx);
+                    return self.no_member(receiver, member, "method", member_span);
+                };
+                let found: Vec<Type> = arguments.iter().map(|argument| self.infer(argument, cx)).collect();
+                if found.len() < required || found.len() > params.len() {
+                    self.wrong_arity(member, required, params.len(), found.len(), span);
+                }
+                for ((found, expected), argument) in found.iter().zip(&params).zip(arguments) {
+                    self.expect_type(found, expected, argument.span(), None);
+                }
+                result
+            }
+            Type::Dynamic => {
+                self.infer_all(arguments, cx);
+                Type::Dynamic
+            }
+            _ => {
+                self.infer_all(arguments, cx);
+                self.no_member(receiver, member, "method", member_span)
+            }
+        }
+    }
+
+    /// Check a call to a declared function or method and return what it
+    /// produces. `bindings` are the type arguments of the method's class.
+    fn check_call(
+        &mut self,
+        function: &'a ASTNode,
+        name: &str,
+        arguments: &'a [ASTNode],
+        span: Span,
+        cx: &mut Context<'a>,
+        mut bindings: HashMap<String, Type>,
+    ) -> Type {
+        let ASTNode::Function { type_params, params, return_type, is_async, .. } = function else {
+            return Type::Dynamic;
+        };
+        // A method's own type parameters shadow its class's
+        for param in type_params {
+            bindings.remove(&param.name);
+        }
+        let signature = Signature {
+            params,
+            generics: type_params.iter().collect(),
+            bindings,
+        };
+        let bindings = self.check_arguments(name, signature, arguments, span, cx);
+        let returns = return_type.as_ref().map_or(Type::Void, |return_type| instantiate(return_type, &bindings));
+        if *is_async {
+            Type::Future(Box::new(returns))
+        } else {
+            returns
+        }
+    }
+
+    /// The type of a call to one of the built-in functions on futures, or
+    /// `None` if `name` is not one of them:
+    ///
+    /// - `all(futures: List<Future<T>>): Future<List<T>>`
+    /// - `race(futures: List<Future<T>>): Future<T>`
+    /// - `timeout(future: Future<T>, ms: Int): Future<T>`
+    /// - `sleep(ms: Int): Future<Void>`
+    fn builtin_call_type(&mut self, name: &str, arguments: &'a [ASTNode], span: Span, cx: &mut Context<'a>) -> Option<Type> {
+        let params = match name {
+            "all" | "race" => vec![Type::List(Box::new(Type::Future(Box::new(Type::Dynamic))))],
+            "timeout" => vec![Type::Future(Box::new(Type::Dynamic)), Type::Int],
+            "sleep" => vec![Type::Int],
+            _ => return None,
+        };
+        let found = self.infer_all(arguments, cx);
+        if found.len() != params.len() {
+            self.wrong_arity(name, params.len(), params.len(), found.len(), span);
+            return Some(Type::Future(Box::new(Type::Dynamic)));
+        }
+        for ((found, expected), argument) in found.iter().zip(&params).zip(arguments) {
+            self.expect_type(found, expected, argument.span(), None);
+        }
+        let value = match (name, &found[0]) {
+            ("sleep", _) => Type::Void,
+            ("all", Type::List(element)) => Type::List(Box::new(future_value(element))),
+            ("all", _) => Type::List(Box::new(Type::Dynamic)),
+            ("race", Type::List(element)) => future_value(element),
+            (_, future) => future_value(future),
+        };
+        Some(Type::Future(Box::new(value)))
+    }
+
+    /// Check the arguments of a call against `signature`, returning the
+    /// type arguments of the call.
+    fn check_arguments(
+        &mut self,
+        name: &str,
+        signature: Signature<'a>,
+        arguments: &'a [ASTNode],
+        span: Span,
+        cx: &mut Context<'a>,
+    ) -> HashMap<String, Type> {
+        let Signature { params, generics, bindings } = signature;
+        let found = self.infer_all(arguments, cx);
+        if found.len() != params.len() {
+            self.wrong_arity(name, params.len(), params.len(), found.len(), span);
+            return self.infer_bindings(generics, bindings, &[], span);
+        }
+        let in_scope: Vec<String> = generics.iter().map(|param| param.name.clone()).chain(bindings.keys().cloned()).collect();
+        let in_scope: Vec<&str> = in_scope.iter().map(String::as_str).collect();
+        let checked: Vec<(Type, Type, Span)> = found
+            .into_iter()
+            .zip(params)
+            .zip(arguments)
+            .map(|((found, param), argument)| {
+                (found, Type::from_annotation_in(&param.param_type, &in_scope), argument.span())
+            })
+            .collect();
+        self.infer_bindings(generics, bindings, &checked, span)
+    }
+
+    /// Infer the type parameters among `generics` from each type found
+    /// where another is expected, then check those types with the
+    /// parameters substituted. Parameters nothing decides are dynamic.
+    fn infer_bindings(
+        &mut self,
+        generics: Vec<&'a TypeParameter>,
+        mut bindings: HashMap<String, Type>,
+        checked: &[(Type, Type, Span)],
+        span: Span,
+    ) -> HashMap<String, Type> {
+        for (found, expected, _) in checked {
+            self.unify(expected, found, &generics, &mut bindings);
+        }
+        for param in &generics {
+            bindings.entry(param.name.clone()).or_insert(Type::Dynamic);
+        }
+        for (found, expected, span) in checked {
+            self.expect_type(found, &expected.substitute(&bindings), *span, None);
+        }
+        for param in generics {
+            let ty = bindings[&param.name].clone();
+            self.check_bounds(param, &ty, span);
+        }
+        bindings
+    }
+
+    /// Bind the type parameters among `generics` that `expected` mentions
+    /// to the parts of `found` in the same place. A parameter met more
+    /// than once takes the wider of its types, if one covers the other.
+    fn unify(&self, expected: &Type, found: &Type, generics: &[&TypeParameter], bindings: &mut HashMap<String, Type>) {
+        match (expected, found) {
+            (Type::Param(name), found) if generics.iter().any(|param| param.name == *name) => {
+                if *found == Type::Null {
+                    return;
+                }
+                let ty = match bindings.remove(name) {
+                    Some(bound) if self.is_assignable(&bound, found) && !self.is_assignable(found, &bound) => found.clone(),
+                    Some(bound) => bound,
+                    None => found.clone(),
+                };
+                bindings.insert(name.clone(), ty);
+            }
+            (Type::List(expected), Type::List(found))
+            | (Type::Optional(expected), Type::Optional(found))
+            | (Type::Future(expected), Type::Future(found)) => {
+                self.unify(expected, found, generics, bindings);
+            }
+            (Type::Optional(expected), found) => self.unify(expected, found, generics, bindings),
+            (Type::Map(expected_key, expected), Type::Map(found_key, found)) => {
+                self.unify(expected_key, found_key, generics, bindings);
+                self.unify(expected, found, generics, bindings);
+            }
+            (Type::Named(expected_name, expected), Type::Named(found_name, found)) if expected_name == found_name => {
+                for (expected, found) in expected.iter().zip(found) {
+                    self.unify(expected, found, generics, bindings);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn infer_all(&mut self, expressions: &'a [ASTNode], cx: &mut Context<'a>) -> Vec<Type> {
+        expressions.iter().map(|expression| self.infer(expression, cx)).collect()
+    }
+
+    fn wrong_arity(&mut self, name: &str, min: usize, max: usize, found: usize, span: Span) {
+        let expected = if min == max {
+            format!("{} argument{}", min, if min == 1 { "" } else { "s" })
+        } else {
+            format!("{} to {} arguments", min, max)
+        };
+        self.diagnostics.push(Diagnostic::error(
+            "E0206",
+            format!("`{}` takes {} but {} were supplied", name, expected, found),
+            Label::new(span).with_message(format!("expected {}", expected)),
+        ));
+    }
+}
+
+/// A pattern reduced to what decides which values it matches.
+#[derive(Debug, Clone)]
+enum Shape {
+    /// `_` or a binding.
+    Any,
+    /// A literal or an enum variant, by name, with shapes for its fields.
+    Constructor(String, Vec<Shape>),
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shape::Any => write!(f, "_"),
+            Shape::Constructor(name, fields) if fields.is_empty() => write!(f, "{}", name),
+            Shape::Constructor(name, fields) => {
+                let fields = fields.iter().map(Shape::to_string).collect::<Vec<_>>().join(", ");
+                write!(f, "{}({})", name, fields)
+            }
+        }
+    }
+}
+
+/// Parameter types, the number of required arguments and the result of the
+/// built-in `String` method `name`.
+fn string_method(name: &str) -> Option<(Vec<Type>, usize, Type)> {
+    let method = match name {
+        "toUpper" | "toLower" | "trim" => (vec![], 0, Type::String),
+        "concat" => (vec![Type::String], 1, Type::String),
+        "substring" => (vec![Type::Int, Type::Int], 1, Type::String),
+        "contains" | "startsWith" | "endsWith" => (vec![Type::String], 1, Type::Bool),
+        _ => return None,
+    };
+    Some(method)
+}
+
+/// The local a condition compares against `null`, and whether it holds
+/// when the local is `null` (`==`) rather than when it is not (`!=`).
+fn null_check(condition: &ASTNode) -> Option<(&str, bool)> {
+    let ASTNode::BinaryExpression { left, operator, right, .. } = condition else {
+        return None;
+    };
+    let is_null = match operator {
+        BinaryOperator::Equal => true,
+        BinaryOperator::NotEqual => false,
+        _ => return None,
+    };
+    match (left.as_ref(), right.as_ref()) {
+        (ASTNode::Identifier { name, .. }, ASTNode::NullLiteral { .. })
+        | (ASTNode::NullLiteral { .. }, ASTNode::Identifier { name, .. }) => Some((name, is_null)),
+        _ => None,
+    }
+}
+
+/// The name a top-level declaration introduces, if it is one.
+fn declared_name(item: &ASTNode) -> Option<&str> {
+    match item {
+        ASTNode::Function { name, .. }
+        | ASTNode::Class { name, .. }
+        | ASTNode::Interface { name, .. }
+        | ASTNode::Struct { name, .. }
+        | ASTNode::Enum { name, .. }
+        | ASTNode::Constant { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// Whether `statement` ends in a `return` or `throw` on every path.
+fn always_returns(statement: &ASTNode) -> bool {
+    match statement {
+        ASTNode::ReturnStatement { .. } => true,
+        ASTNode::ExpressionStatement { expression, .. } => matches!(**expression, ASTNode::ThrowExpression { .. }),
+        ASTNode::TryStatement { body, catches, .. } => {
+            always_returns(body) && catches.iter().all(|clause| always_returns(&clause.body))
+        }
+        ASTNode::Block { statements, .. } => statements.last().is_some_and(always_returns),
+        ASTNode::IfStatement { then_branch, else_branch: Some(else_branch), .. } => {
+            always_returns(then_branch) && always_returns(else_branch)
+        }
+        // A match that leaves a case uncovered is reported on its own
+        ASTNode::Match { arms, .. } => !arms.is_empty() && arms.iter().all(|arm| always_returns(&arm.body)),
+        // A loop only `break` can leave never completes
+        ASTNode::WhileStatement { condition, body, .. } => {
+            matches!(**condition, ASTNode::BooleanLiteral { value: true, .. }) && !breaks(body)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `statement` has a `break` that leaves the loop around it, rather
+/// than one nested in it.
+fn breaks(statement: &ASTNode) -> bool {
+    match statement {
+        ASTNode::BreakStatement { .. } => true,
+        ASTNode::Block { statements, .. } => statements.iter().any(breaks),
+        ASTNode::IfStatement { then_branch, else_branch, .. } => {
+            breaks(then_branch) || else_branch.as_deref().is_some_and(breaks)
+        }
+        ASTNode::TryStatement { body, catches, .. } => {
+            breaks(body) || catches.iter().any(|clause| breaks(&clause.body))
+        }
+        ASTNode::Match { arms, .. } => arms.iter().any(|arm| breaks(&arm.body)),
+        ASTNode::DeferStatement { statement, .. } => breaks(statement),
+        _ => false,
+    }
+}
+
+/// Bindings for the type parameters `params` from the type arguments
+/// `arguments`, dynamic where there are too few.
+fn type_bindings(params: &[TypeParameter], arguments: &[Type]) -> HashMap<String, Type> {
+    params
+        .iter()
+        .enumerate()
+        .map(|(index, param)| (param.name.clone(), arguments.get(index).cloned().unwrap_or(Type::Dynamic)))
+        .collect()
+}
+
+/// The type `annotation` names where the type parameters in `bindings`
+/// stand for the types they are bound to.
+fn instantiate(annotation: &TypeExpr, bindings: &HashMap<String, Type>) -> Type {
+    let params: Vec<&str> = bindings.keys().map(String::as_str).collect();
+    Type::from_annotation_in(annotation, &params).substitute(bindings)
+}
+
+/// The user type `name`, generic over `params`, with its type arguments
+/// taken from `bindings`.
+fn instance_type(name: &str, params: &[TypeParameter], bindings: &HashMap<String, Type>) -> Type {
+    let arguments = params
+        .iter()
+        .map(|param| bindings.get(&param.name).cloned().unwrap_or(Type::Dynamic))
+        .collect();
+    Type::Named(name.to_string(), arguments)
+}
+
+/// What a function declared with `return_type` produces.
+fn return_type_of(return_type: &Option<TypeExpr>) -> Type {
+    return_type.as_ref().map_or(Type::Void, Type::from_annotation)
+}
+
+/// What calling `function` gives: a `Future` of its result if it is `async`.
+fn call_result_of(function: &ASTNode) -> Type {
+    match function {
+        ASTNode::Function { return_type, is_async: true, .. } => Type::Future(Box::new(return_type_of(return_type))),
+        ASTNode::Function { return_type, .. } => return_type_of(return_type),
+        _ => Type::Dynamic,
+    }
+}
+
+/// The type a `Future` of type `ty` resolves to.
+fn future_value(ty: &Type) -> Type {
+    match ty {
+        Type::Future(value) => (**value).clone(),
+        _ => Type::Dynamic,
+    }
+}
+
+fn format_signature(name: &str, params: &[Parameter], return_type: &Option<TypeExpr>) -> String {
+    let params = params
+        .iter()
+        .map(|param| format!("{}: {}", param.name, param.param_type))
+        .collect::<Vec<_>>()
+        .join(", ");
+    match return_type {
+        Some(return_type) => format!("fn {}({}): {}", name, params, return_type),
+        None => format!("fn {}({})", name, params),
+    }
+}
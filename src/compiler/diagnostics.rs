@@ -0,0 +1,202 @@
+use std::fmt;
+
+use super::source_map::{SourceMap, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A span of source that a diagnostic points at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: Option<String>,
+}
+
+impl Label {
+    pub fn new(span: Span) -> Self {
+        Label {
+            span,
+            message: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub message: String,
+    pub replacement: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, primary: Label) -> Self {
+        Diagnostic {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            primary,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn with_secondary(mut self, label: Label) -> Self {
+        self.secondary.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, message: impl Into<String>, replacement: Option<String>) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            replacement,
+        });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Render the diagnostic rustc-style, quoting the offending source lines
+    /// and underlining the labelled spans with carets.
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        struct Resolved<'a> {
+            line: usize,
+            column: usize,
+            width: usize,
+            text: &'a str,
+            message: Option<&'a String>,
+            primary: bool,
+        }
+
+        let mut labels: Vec<Resolved> = std::iter::once((&self.primary, true))
+            .chain(self.secondary.iter().map(|l| (l, false)))
+            .map(|(label, primary)| {
+                let start = source_map.span_start(label.span);
+                let text = source_map.file(label.span.file_id).line_text(start.line);
+                // Multi-line spans are underlined up to the end of their first line.
+                let available = text.chars().count().saturating_sub(start.column - 1);
+                let width = source_map.snippet(label.span).chars().count().min(available);
+                Resolved {
+                    line: start.line,
+                    column: start.column - 1,
+                    width: width.max(1),
+                    text,
+                    message: label.message.as_ref(),
+                    primary,
+                }
+            })
+            .collect();
+        labels.sort_by_key(|l| (l.line, l.column));
+
+        let gutter = labels
+            .iter()
+            .map(|l| l.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(gutter);
+        let location = source_map.span_start(self.primary.span);
+
+        let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        out.push_str(&format!(
+            "{}--> {}:{}:{}\n",
+            pad, location.file, location.line, location.column
+        ));
+        out.push_str(&format!("{} |\n", pad));
+
+        let mut last_line = None;
+        for label in labels {
+            if last_line != Some(label.line) {
+                out.push_str(&format!("{:>width$} | {}\n", label.line, label.text, width = gutter));
+                last_line = Some(label.line);
+            }
+            let marker = if label.primary { "^" } else { "-" };
+            let mut underline = format!(
+                "{} | {}{}",
+                pad,
+                " ".repeat(label.column),
+                marker.repeat(label.width)
+            );
+            if let Some(message) = label.message {
+                underline.push(' ');
+                underline.push_str(message);
+            }
+            out.push_str(&underline);
+            out.push('\n');
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{} = note: {}\n", pad, note));
+        }
+        for suggestion in &self.suggestions {
+            match &suggestion.replacement {
+                Some(replacement) => out.push_str(&format!(
+                    "{} = help: {}: `{}`\n",
+                    pad, suggestion.message, replacement
+                )),
+                None => out.push_str(&format!("{} = help: {}\n", pad, suggestion.message)),
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Render a batch of diagnostics followed by a summary line.
+pub fn render_all(diagnostics: &[Diagnostic], source_map: &SourceMap) -> String {
+    let mut out = String::new();
+    for diagnostic in diagnostics {
+        out.push_str(&diagnostic.render(source_map));
+        out.push('\n');
+    }
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    if errors > 0 {
+        out.push_str(&format!(
+            "error: aborting due to {} previous error{}\n",
+            errors,
+            if errors == 1 { "" } else { "s" }
+        ));
+    }
+    out
+}
@@ -1,6 +1,10 @@
+use std::fmt;
 use std::str::Chars;
 use std::iter::Peekable;
 
+use super::diagnostics::{Diagnostic, Label};
+use super::source_map::Span;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     // Keywords
@@ -8,6 +12,10 @@ pub enum TokenType {
     Endpoint,
     Class,
     Interface,
+    Struct,
+    Record,
+    Enum,
+    Match,
     Async,
     Await,
     Cloud,
@@ -15,135 +23,435 @@ pub enum TokenType {
     Export,
     Try,
     Catch,
+    Throw,
     Defer,
     Return,
+    True,
+    False,
+    Null,
+    Let,
+    Var,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Fn,
+    Pub,
+    Const,
+    Module,
+    Extends,
+    Implements,
+    Static,
+    Super,
     
     // Symbols
     LeftBrace,
     RightBrace,
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
     Arrow,
+    FatArrow,
     Colon,
     Semicolon,
+    Comma,
+    Dot,
+    DotDot,
     At,
+
+    // Operators
+    Equal,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    AndAnd,
+    OrOr,
+    Bang,
+    Question,
+    QuestionQuestion,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
     
     // Literals
     Identifier(String),
     StringLiteral(String),
+    /// A string with `\(...)` interpolations is split into the text before
+    /// the first one, the text between two, and the text after the last.
+    /// The embedded expressions are lexed as ordinary tokens in between.
+    StringHead(String),
+    StringMiddle(String),
+    StringTail(String),
     NumberLiteral(f64),
+    /// A number written with a fractional part, such as `3.0`.
+    FloatLiteral(f64),
+
+    // Trivia
+    DocComment(String),
     
     // Special
+    Error(LexError),
     EOF,
 }
 
+/// A malformed piece of input. The lexer reports these as `TokenType::Error`
+/// tokens and keeps going; the parser turns them into diagnostics.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexError {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    UnterminatedBlockComment,
+    MalformedNumber(String),
+}
+
+impl LexError {
+    pub fn to_diagnostic(&self, span: Span) -> Diagnostic {
+        match self {
+            LexError::UnexpectedCharacter(c) => Diagnostic::error(
+                "E0001",
+                format!("unknown character `{}`", c.escape_debug()),
+                Label::new(span),
+            )
+            .with_note("the character was skipped"),
+            LexError::UnterminatedBlockComment => Diagnostic::error(
+                "E0002",
+                "unterminated block comment",
+                Label::new(Span::new(span.file_id, span.start, span.start + 2))
+                    .with_message("comment starts here"),
+            )
+            .with_suggestion("close the comment", Some("*/".to_string())),
+            LexError::UnterminatedString => Diagnostic::error(
+                "E0003",
+                "unterminated string literal",
+                Label::new(span).with_message("string is never closed"),
+            )
+            .with_note("string literals cannot span lines; use `\\n` for a line break")
+            .with_suggestion("close the string", Some("\"".to_string())),
+            LexError::MalformedNumber(text) => Diagnostic::error(
+                "E0004",
+                format!("malformed number literal `{}`", text),
+                Label::new(span).with_message("not a valid number"),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenType::Service => write!(f, "service"),
+            TokenType::Endpoint => write!(f, "endpoint"),
+            TokenType::Class => write!(f, "class"),
+            TokenType::Interface => write!(f, "interface"),
+            TokenType::Struct => write!(f, "struct"),
+            TokenType::Record => write!(f, "record"),
+            TokenType::Enum => write!(f, "enum"),
+            TokenType::Match => write!(f, "match"),
+            TokenType::Async => write!(f, "async"),
+            TokenType::Await => write!(f, "await"),
+            TokenType::Cloud => write!(f, "cloud"),
+            TokenType::Import => write!(f, "import"),
+            TokenType::Export => write!(f, "export"),
+            TokenType::Try => write!(f, "try"),
+            TokenType::Catch => write!(f, "catch"),
+            TokenType::Throw => write!(f, "throw"),
+            TokenType::Defer => write!(f, "defer"),
+            TokenType::Return => write!(f, "return"),
+            TokenType::True => write!(f, "true"),
+            TokenType::False => write!(f, "false"),
+            TokenType::Null => write!(f, "null"),
+            TokenType::Let => write!(f, "let"),
+            TokenType::Var => write!(f, "var"),
+            TokenType::If => write!(f, "if"),
+            TokenType::Else => write!(f, "else"),
+            TokenType::While => write!(f, "while"),
+            TokenType::For => write!(f, "for"),
+            TokenType::In => write!(f, "in"),
+            TokenType::Break => write!(f, "break"),
+            TokenType::Continue => write!(f, "continue"),
+            TokenType::Fn => write!(f, "fn"),
+            TokenType::Pub => write!(f, "pub"),
+            TokenType::Const => write!(f, "const"),
+            TokenType::Module => write!(f, "module"),
+            TokenType::Extends => write!(f, "extends"),
+            TokenType::Implements => write!(f, "implements"),
+            TokenType::Static => write!(f, "static"),
+            TokenType::Super => write!(f, "super"),
+            TokenType::LeftBrace => write!(f, "{{"),
+            TokenType::RightBrace => write!(f, "}}"),
+            TokenType::LeftParen => write!(f, "("),
+            TokenType::RightParen => write!(f, ")"),
+            TokenType::LeftBracket => write!(f, "["),
+            TokenType::RightBracket => write!(f, "]"),
+            TokenType::Arrow => write!(f, "->"),
+            TokenType::FatArrow => write!(f, "=>"),
+            TokenType::Colon => write!(f, ":"),
+            TokenType::Semicolon => write!(f, ";"),
+            TokenType::Comma => write!(f, ","),
+            TokenType::Dot => write!(f, "."),
+            TokenType::DotDot => write!(f, ".."),
+            TokenType::At => write!(f, "@"),
+            TokenType::Equal => write!(f, "="),
+            TokenType::EqualEqual => write!(f, "=="),
+            TokenType::BangEqual => write!(f, "!="),
+            TokenType::Less => write!(f, "<"),
+            TokenType::LessEqual => write!(f, "<="),
+            TokenType::Greater => write!(f, ">"),
+            TokenType::GreaterEqual => write!(f, ">="),
+            TokenType::Plus => write!(f, "+"),
+            TokenType::Minus => write!(f, "-"),
+            TokenType::Star => write!(f, "*"),
+            TokenType::Slash => write!(f, "/"),
+            TokenType::Percent => write!(f, "%"),
+            TokenType::AndAnd => write!(f, "&&"),
+            TokenType::OrOr => write!(f, "||"),
+            TokenType::Bang => write!(f, "!"),
+            TokenType::Question => write!(f, "?"),
+            TokenType::QuestionQuestion => write!(f, "??"),
+            TokenType::PlusEqual => write!(f, "+="),
+            TokenType::MinusEqual => write!(f, "-="),
+            TokenType::StarEqual => write!(f, "*="),
+            TokenType::SlashEqual => write!(f, "/="),
+            TokenType::PercentEqual => write!(f, "%="),
+            TokenType::Identifier(name) => write!(f, "{}", name),
+            TokenType::StringLiteral(value) => write!(f, "\"{}\"", value),
+            TokenType::StringHead(value) => write!(f, "\"{}\\(", value),
+            TokenType::StringMiddle(value) => write!(f, "){}\\(", value),
+            TokenType::StringTail(value) => write!(f, "){}\"", value),
+            TokenType::NumberLiteral(value) => write!(f, "{}", value),
+            TokenType::FloatLiteral(value) => write!(f, "{:?}", value),
+            TokenType::DocComment(text) => write!(f, "/// {}", text),
+            TokenType::Error(LexError::UnexpectedCharacter(c)) => write!(f, "{}", c),
+            TokenType::Error(LexError::MalformedNumber(text)) => write!(f, "{}", text),
+            TokenType::Error(_) => write!(f, "invalid token"),
+            TokenType::EOF => write!(f, "end of file"),
+        }
+    }
+}
+
+impl TokenType {
+    /// How the token should be named in a diagnostic message.
+    pub fn describe(&self) -> String {
+        match self {
+            TokenType::Identifier(name) => format!("identifier `{}`", name),
+            TokenType::StringLiteral(_) => "string literal".to_string(),
+            TokenType::StringHead(_) | TokenType::StringMiddle(_) | TokenType::StringTail(_) => {
+                "interpolated string".to_string()
+            }
+            TokenType::NumberLiteral(_) | TokenType::FloatLiteral(_) => "number literal".to_string(),
+            TokenType::DocComment(_) => "doc comment".to_string(),
+            TokenType::Error(_) => "invalid token".to_string(),
+            TokenType::EOF => "end of file".to_string(),
+            other => format!("`{}`", other),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Token {
     pub token_type: TokenType,
+    pub span: Span,
     pub line: usize,
     pub column: usize,
 }
 
 pub struct Lexer<'a> {
     input: Peekable<Chars<'a>>,
+    file_id: usize,
+    offset: usize,
     line: usize,
     column: usize,
+    /// For each `\(` being lexed, innermost last, how many of the
+    /// parentheses inside it are open. The `)` that closes it resumes the
+    /// string.
+    interpolations: Vec<usize>,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
+        Self::with_file_id(input, 0)
+    }
+
+    /// Create a lexer whose token spans refer to `file_id` in a `SourceMap`.
+    pub fn with_file_id(input: &'a str, file_id: usize) -> Self {
         Lexer {
             input: input.chars().peekable(),
+            file_id,
+            offset: 0,
             line: 1,
             column: 0,
+            interpolations: Vec::new(),
         }
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        if let Some(error) = self.skip_whitespace() {
+            return error;
+        }
 
-        if let Some(&c) = self.input.peek() {
-            match c {
+        let start = (self.offset, self.line, self.column);
+        let token_type = match self.input.peek() {
+            Some(&c) => match c {
                 '{' => self.single_char_token(TokenType::LeftBrace),
                 '}' => self.single_char_token(TokenType::RightBrace),
-                '(' => self.single_char_token(TokenType::LeftParen),
-                ')' => self.single_char_token(TokenType::RightParen),
+                '(' => {
+                    if let Some(open) = self.interpolations.last_mut() {
+                        *open += 1;
+                    }
+                    self.single_char_token(TokenType::LeftParen)
+                }
+                ')' => match self.interpolations.last_mut() {
+                    Some(0) => {
+                        self.interpolations.pop();
+                        self.bump();
+                        self.read_string_part(false)
+                    }
+                    Some(open) => {
+                        *open -= 1;
+                        self.single_char_token(TokenType::RightParen)
+                    }
+                    None => self.single_char_token(TokenType::RightParen),
+                },
+                '[' => self.single_char_token(TokenType::LeftBracket),
+                ']' => self.single_char_token(TokenType::RightBracket),
                 ':' => self.single_char_token(TokenType::Colon),
                 ';' => self.single_char_token(TokenType::Semicolon),
+                ',' => self.single_char_token(TokenType::Comma),
                 '@' => self.single_char_token(TokenType::At),
-                '-' => self.handle_arrow(),
                 '"' => self.read_string(),
-                c if c.is_alphabetic() => self.read_identifier(),
-                c if c.is_digit(10) => self.read_number(),
-                _ => {
-                    self.input.next();
-                    Token {
-                        token_type: TokenType::EOF,
-                        line: self.line,
-                        column: self.column,
-                    }
+                '/' if self.at_doc_comment() => self.read_doc_comment(),
+                '-' | '+' | '*' | '/' | '%' | '=' | '!' | '<' | '>' | '&' | '|' | '?' | '.' => {
+                    self.read_operator(c)
                 }
-            }
-        } else {
-            Token {
-                token_type: TokenType::EOF,
-                line: self.line,
-                column: self.column,
-            }
-        }
-    }
+                c if c.is_alphabetic() || c == '_' => self.read_identifier(),
+                c if c.is_ascii_digit() => self.read_number(),
+                c => {
+                    self.bump();
+                    TokenType::Error(LexError::UnexpectedCharacter(c))
+                }
+            },
+            None => TokenType::EOF,
+        };
 
-    fn single_char_token(&mut self, token_type: TokenType) -> Token {
-        self.input.next();
-        self.column += 1;
         Token {
             token_type,
-            line: self.line,
-            column: self.column - 1,
+            span: Span::new(self.file_id, start.0, self.offset),
+            line: start.1,
+            column: start.2,
         }
     }
 
-    fn handle_arrow(&mut self) -> Token {
-        self.input.next();
-        self.column += 1;
-        
-        if let Some(&'>') = self.input.peek() {
-            self.input.next();
-            self.column += 1;
-            Token {
-                token_type: TokenType::Arrow,
-                line: self.line,
-                column: self.column - 2,
-            }
+    /// Consume one character, keeping the byte offset, line and column in step.
+    fn bump(&mut self) -> Option<char> {
+        let c = self.input.next()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
         } else {
-            // Handle single minus if needed
-            Token {
-                token_type: TokenType::EOF, // Placeholder
-                line: self.line,
-                column: self.column - 1,
-            }
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.input.clone().nth(n)
+    }
+
+    /// `///` starts a doc comment, but `////` and longer are plain comments.
+    fn at_doc_comment(&self) -> bool {
+        let mut chars = self.input.clone();
+        chars.next() == Some('/')
+            && chars.next() == Some('/')
+            && chars.next() == Some('/')
+            && chars.next() != Some('/')
+    }
+
+    fn single_char_token(&mut self, token_type: TokenType) -> TokenType {
+        self.bump();
+        token_type
+    }
+
+    /// Lex an operator that may be one or two characters long, always
+    /// preferring the longest match.
+    fn read_operator(&mut self, first: char) -> TokenType {
+        self.bump();
+
+        let two_char = match (first, self.input.peek()) {
+            ('-', Some('>')) => Some(TokenType::Arrow),
+            ('-', Some('=')) => Some(TokenType::MinusEqual),
+            ('+', Some('=')) => Some(TokenType::PlusEqual),
+            ('*', Some('=')) => Some(TokenType::StarEqual),
+            ('/', Some('=')) => Some(TokenType::SlashEqual),
+            ('%', Some('=')) => Some(TokenType::PercentEqual),
+            ('=', Some('>')) => Some(TokenType::FatArrow),
+            ('=', Some('=')) => Some(TokenType::EqualEqual),
+            ('!', Some('=')) => Some(TokenType::BangEqual),
+            ('<', Some('=')) => Some(TokenType::LessEqual),
+            ('>', Some('=')) => Some(TokenType::GreaterEqual),
+            ('&', Some('&')) => Some(TokenType::AndAnd),
+            ('|', Some('|')) => Some(TokenType::OrOr),
+            ('?', Some('?')) => Some(TokenType::QuestionQuestion),
+            ('.', Some('.')) => Some(TokenType::DotDot),
+            _ => None,
+        };
+        if let Some(token_type) = two_char {
+            self.bump();
+            return token_type;
+        }
+
+        match first {
+            '-' => TokenType::Minus,
+            '+' => TokenType::Plus,
+            '*' => TokenType::Star,
+            '/' => TokenType::Slash,
+            '%' => TokenType::Percent,
+            '=' => TokenType::Equal,
+            '!' => TokenType::Bang,
+            '<' => TokenType::Less,
+            '>' => TokenType::Greater,
+            '?' => TokenType::Question,
+            '.' => TokenType::Dot,
+            // `&` and `|` only exist doubled
+            c => TokenType::Error(LexError::UnexpectedCharacter(c)),
         }
     }
 
-    fn read_identifier(&mut self) -> Token {
+    fn read_identifier(&mut self) -> TokenType {
         let mut identifier = String::new();
-        let start_column = self.column;
 
         while let Some(&c) = self.input.peek() {
             if c.is_alphanumeric() || c == '_' {
                 identifier.push(c);
-                self.input.next();
-                self.column += 1;
+                self.bump();
             } else {
                 break;
             }
         }
 
-        let token_type = match identifier.as_str() {
+        match identifier.as_str() {
             "service" => TokenType::Service,
             "endpoint" => TokenType::Endpoint,
             "class" => TokenType::Class,
             "interface" => TokenType::Interface,
+            "struct" => TokenType::Struct,
+            "record" => TokenType::Record,
+            "enum" => TokenType::Enum,
+            "match" => TokenType::Match,
             "async" => TokenType::Async,
             "await" => TokenType::Await,
             "cloud" => TokenType::Cloud,
@@ -151,96 +459,239 @@ impl<'a> Lexer<'a> {
             "export" => TokenType::Export,
             "try" => TokenType::Try,
             "catch" => TokenType::Catch,
+            "throw" => TokenType::Throw,
             "defer" => TokenType::Defer,
             "return" => TokenType::Return,
+            "true" => TokenType::True,
+            "false" => TokenType::False,
+            "null" => TokenType::Null,
+            "let" => TokenType::Let,
+            "var" => TokenType::Var,
+            "if" => TokenType::If,
+            "else" => TokenType::Else,
+            "while" => TokenType::While,
+            "for" => TokenType::For,
+            "in" => TokenType::In,
+            "break" => TokenType::Break,
+            "continue" => TokenType::Continue,
+            "fn" => TokenType::Fn,
+            "pub" => TokenType::Pub,
+            "const" => TokenType::Const,
+            "module" => TokenType::Module,
+            "extends" => TokenType::Extends,
+            "implements" => TokenType::Implements,
+            "static" => TokenType::Static,
+            "super" => TokenType::Super,
             _ => TokenType::Identifier(identifier),
-        };
-
-        Token {
-            token_type,
-            line: self.line,
-            column: start_column,
         }
     }
 
-    fn read_string(&mut self) -> Token {
-        self.input.next(); // Skip opening quote
-        self.column += 1;
-        let start_column = self.column;
+    fn read_string(&mut self) -> TokenType {
+        self.bump(); // Skip opening quote
+        self.read_string_part(true)
+    }
+
+    /// Read string text up to the closing quote or the next `\(`. `first`
+    /// is whether the text follows the opening quote rather than the end of
+    /// an interpolation. An escaped backslash never starts an interpolation,
+    /// so `"\\(x)"` is the text `\(x)`. A backslash before anything but
+    /// `n`, `t`, `r`, `\\` or `"` is kept as written, so `"\q"` is `\q`.
+    fn read_string_part(&mut self, first: bool) -> TokenType {
         let mut string = String::new();
 
-        while let Some(&c) = self.input.peek() {
-            match c {
-                '"' => {
-                    self.input.next();
-                    self.column += 1;
-                    break;
+        loop {
+            match self.input.peek().copied() {
+                Some('"') => {
+                    self.bump();
+                    return match first {
+                        true => TokenType::StringLiteral(string),
+                        false => TokenType::StringTail(string),
+                    };
                 }
-                '\\' => {
-                    self.input.next();
-                    self.column += 1;
-                    if let Some(next) = self.input.next() {
-                        string.push(match next {
-                            'n' => '\n',
-                            't' => '\t',
-                            'r' => '\r',
-                            '\\' => '\\',
-                            '"' => '"',
-                            _ => next,
-                        });
-                        self.column += 1;
+                Some('\\') if self.peek_nth(1) == Some('(') => {
+                    self.bump();
+                    self.bump();
+                    self.interpolations.push(0);
+                    return match first {
+                        true => TokenType::StringHead(string),
+                        false => TokenType::StringMiddle(string),
+                    };
+                }
+                // A raw line break means the closing quote is missing; stop
+                // here so the following lines still lex normally.
+                None | Some('\n') | Some('\r') => {
+                    return TokenType::Error(LexError::UnterminatedString);
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.input.peek() {
+                        None | Some('\n') | Some('\r') => continue,
+                        Some(_) => {}
+                    }
+                    if let Some(next) = self.bump() {
+                        match next {
+                            'n' => string.push('\n'),
+                            't' => string.push('\t'),
+                            'r' => string.push('\r'),
+                            '\\' | '"' => string.push(next),
+                            _ => {
+                                string.push('\\');
+                                string.push(next);
+                            }
+                        }
                     }
                 }
-                _ => {
+                Some(c) => {
                     string.push(c);
-                    self.input.next();
-                    self.column += 1;
+                    self.bump();
                 }
             }
         }
+    }
 
-        Token {
-            token_type: TokenType::StringLiteral(string),
-            line: self.line,
-            column: start_column,
+    fn read_number(&mut self) -> TokenType {
+        let mut number = self.read_digits();
+        let mut fractional = false;
+
+        // Only take the dot if a fractional part follows, so `1..5` stays a range.
+        if self.peek_nth(0) == Some('.') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            number.push('.');
+            number.push_str(&self.read_digits());
+            fractional = true;
+
+            // A second fractional part (`1.2.3`) or trailing letters (`12px`)
+            // make the whole run malformed. A `.` followed by a name is a
+            // member access instead, as in `1.5.round()`.
+            let mut malformed = false;
+            while self.peek_nth(0) == Some('.') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+                self.bump();
+                number.push('.');
+                number.push_str(&self.read_alphanumeric());
+                malformed = true;
+            }
+            if malformed {
+                return TokenType::Error(LexError::MalformedNumber(number));
+            }
         }
-    }
 
-    fn read_number(&mut self) -> Token {
-        let mut number = String::new();
-        let start_column = self.column;
+        if self.input.peek().is_some_and(|c| c.is_alphabetic() || *c == '_') {
+            number.push_str(&self.read_alphanumeric());
+            return TokenType::Error(LexError::MalformedNumber(number));
+        }
+
+        match number.parse() {
+            Ok(value) if fractional => TokenType::FloatLiteral(value),
+            Ok(value) => TokenType::NumberLiteral(value),
+            Err(_) => TokenType::Error(LexError::MalformedNumber(number)),
+        }
+    }
 
+    fn read_digits(&mut self) -> String {
+        let mut digits = String::new();
         while let Some(&c) = self.input.peek() {
-            if c.is_digit(10) || c == '.' {
-                number.push(c);
-                self.input.next();
-                self.column += 1;
-            } else {
+            if !c.is_ascii_digit() {
                 break;
             }
+            digits.push(c);
+            self.bump();
         }
+        digits
+    }
 
-        Token {
-            token_type: TokenType::NumberLiteral(number.parse().unwrap_or(0.0)),
-            line: self.line,
-            column: start_column,
+    fn read_alphanumeric(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(&c) = self.input.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            text.push(c);
+            self.bump();
         }
+        text
     }
 
-    fn skip_whitespace(&mut self) {
+    fn read_doc_comment(&mut self) -> TokenType {
+        for _ in 0..3 {
+            self.bump();
+        }
+        if let Some(&' ') = self.input.peek() {
+            self.bump();
+        }
+
+        let mut text = String::new();
         while let Some(&c) = self.input.peek() {
-            match c {
-                ' ' | '\t' => {
-                    self.input.next();
-                    self.column += 1;
+            if c == '\n' {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+
+        TokenType::DocComment(text.trim_end().to_string())
+    }
+
+    /// Skip whitespace along with line and (nested) block comments. Doc
+    /// comments are left in place so they can be emitted as tokens. Returns
+    /// an error token if a block comment runs off the end of the input.
+    fn skip_whitespace(&mut self) -> Option<Token> {
+        while let Some(c) = self.peek_nth(0) {
+            match (c, self.peek_nth(1)) {
+                // `\r` is skipped on its own so `\r\n` endings count as one line
+                (' ' | '\t' | '\r' | '\n', _) => {
+                    self.bump();
                 }
-                '\n' => {
-                    self.input.next();
-                    self.line += 1;
-                    self.column = 0;
+                ('/', Some('/')) if !self.at_doc_comment() => self.skip_line_comment(),
+                ('/', Some('*')) => {
+                    let start = (self.offset, self.line, self.column);
+                    if !self.skip_block_comment() {
+                        return Some(Token {
+                            token_type: TokenType::Error(LexError::UnterminatedBlockComment),
+                            span: Span::new(self.file_id, start.0, self.offset),
+                            line: start.1,
+                            column: start.2,
+                        });
+                    }
                 }
                 _ => break,
             }
         }
+        None
+    }
+
+    fn skip_line_comment(&mut self) {
+        while let Some(&c) = self.input.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.bump();
+        }
     }
-} 
\ No newline at end of file
+
+    /// Returns `false` if the input ended before the comment was closed.
+    fn skip_block_comment(&mut self) -> bool {
+        self.bump();
+        self.bump();
+
+        let mut depth = 1;
+        while depth > 0 {
+            match (self.peek_nth(0), self.peek_nth(1)) {
+                (Some('/'), Some('*')) => {
+                    self.bump();
+                    self.bump();
+                    depth += 1;
+                }
+                (Some('*'), Some('/')) => {
+                    self.bump();
+                    self.bump();
+                    depth -= 1;
+                }
+                (Some(_), _) => {
+                    self.bump();
+                }
+                (None, _) => return false,
+            }
+        }
+        true
+    }
+}
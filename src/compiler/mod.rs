@@ -1,47 +1,80 @@
+pub mod checker;
+pub mod diagnostics;
 pub mod lexer;
+pub mod modules;
 pub mod parser;
+pub mod prelude;
+pub mod source_map;
+pub mod types;
+
+#[cfg(test)]
+mod tests;
 
-use std::fs;
 use std::path::Path;
 
+pub use diagnostics::{Diagnostic, Severity};
+pub use modules::ModuleGraph;
+pub use source_map::{SourceMap, Span};
+
 pub struct Compiler {
-    pub source: String,
+    pub source_map: SourceMap,
+    /// The entry file.
+    pub file_id: usize,
+    /// The entry file and every file it imports, parsed.
+    pub modules: ModuleGraph,
 }
 
 impl Compiler {
     pub fn new(source: String) -> Self {
-        Compiler { source }
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file("<input>", source);
+        let modules = ModuleGraph::load(&mut source_map, file_id, None);
+        Compiler {
+            source_map,
+            file_id,
+            modules,
+        }
     }
 
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let source = fs::read_to_string(path)
+        let mut source_map = SourceMap::new();
+        let file_id = source_map
+            .load_file(path.as_ref())
             .map_err(|e| format!("Failed to read source file: {}", e))?;
-        Ok(Compiler { source })
+        let modules = ModuleGraph::load(&mut source_map, file_id, Some(path.as_ref()));
+        Ok(Compiler {
+            source_map,
+            file_id,
+            modules,
+        })
     }
 
-    pub fn compile(&self) -> Result<parser::ASTNode, String> {
-        let mut parser = parser::Parser::new(&self.source);
-        parser.parse_service()
+    pub fn source(&self) -> &str {
+        &self.source_map.file(self.file_id).source
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_hello_world_service() {
-        let source = r#"
-            service HelloService {
-                @get("/hello")
-                endpoint greet(name: String): String {
-                    return "Hello, World!";
-                }
-            }
-        "#;
-
-        let compiler = Compiler::new(source.to_string());
-        let result = compiler.compile();
-        assert!(result.is_ok());
+    /// Link the entry file and its imports into one program and check it,
+    /// returning every diagnostic found if any of them is an error.
+    pub fn compile(&self) -> Result<parser::ASTNode, Vec<Diagnostic>> {
+        let mut diagnostics = self.modules.diagnostics.clone();
+        let (program, link_diagnostics) = self.modules.link();
+        diagnostics.extend(link_diagnostics);
+        // Items that failed to parse are left out of checking, but the rest
+        // are still checked. Unresolved imports leave too much unknown.
+        if !diagnostics.iter().any(|d| d.is_error() && d.code.starts_with("E03")) {
+            let checked = checker::check_program(&program, &self.modules, &diagnostics);
+            diagnostics.extend(checked);
+        }
+
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            Err(diagnostics)
+        } else {
+            Ok(program)
+        }
+    }
+
+    /// Render diagnostics against the compiled sources, rustc-style.
+    pub fn render_diagnostics(&self, diagnostics: &[Diagnostic]) -> String {
+        diagnostics::render_all(diagnostics, &self.source_map)
     }
-} 
\ No newline at end of file
+}
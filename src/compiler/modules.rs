@@ -0,0 +1,619 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use super::diagnostics::{Diagnostic, Label};
+use super::parser::{ASTNode, ImportedName, Parameter, Parser, Pattern, StringPart, TypeExpr, TypeParameter};
+use super::source_map::{SourceMap, Span};
+
+/// One parsed source file of a program.
+#[derive(Debug)]
+pub struct Module {
+    /// The file name, as shown in diagnostics.
+    pub name: String,
+    /// Where the file was read from; `None` for source given as a string.
+    pub path: Option<PathBuf>,
+    pub file_id: usize,
+    pub ast: ASTNode,
+    /// Indices of the modules this one imports.
+    pub imports: Vec<usize>,
+}
+
+/// Every source file reachable from an entry file through `import`s. The
+/// entry is always module 0.
+#[derive(Debug)]
+pub struct ModuleGraph {
+    pub modules: Vec<Module>,
+    /// Problems found parsing the files and resolving their imports.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Module indices ordered so each comes after everything it imports.
+    order: Vec<usize>,
+}
+
+impl ModuleGraph {
+    /// Parse the entry file and, transitively, every file it imports,
+    /// adding them to `source_map`. `path` is where the entry was read
+    /// from; imports of source without a path resolve against the working
+    /// directory.
+    pub fn load(source_map: &mut SourceMap, entry: usize, path: Option<&Path>) -> Self {
+        let base = path
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        let mut loader = Loader {
+            source_map,
+            graph: ModuleGraph {
+                modules: Vec::new(),
+                diagnostics: Vec::new(),
+                order: Vec::new(),
+            },
+            by_path: HashMap::new(),
+            stack: Vec::new(),
+            src_dir: project_src_dir(&base).unwrap_or_else(|| base.clone()),
+            base,
+        };
+        loader.visit(entry, path.map(Path::to_path_buf));
+        loader.graph
+    }
+
+    /// Modules in dependency order: each after every module it imports.
+    pub fn ordered(&self) -> impl Iterator<Item = &Module> {
+        self.order.iter().map(|&index| &self.modules[index])
+    }
+
+    /// Merge every module into one program, dependencies first, named after
+    /// the entry's `module`. The private items of imported files are renamed
+    /// after their file, as in `models/base::helper`, so only exported names
+    /// and those of the entry share a namespace once linked. One of those
+    /// declared by two different files is an error.
+    pub fn link(&self) -> (ASTNode, Vec<Diagnostic>) {
+        let mut items = Vec::new();
+        let mut declared: HashMap<String, &Module> = HashMap::new();
+        let mut diagnostics = Vec::new();
+
+        for module in self.ordered() {
+            let renames = self.private_names(module);
+            for item in top_level_items(&module.ast) {
+                if matches!(item, ASTNode::Import { .. }) {
+                    continue;
+                }
+                let mut item = item.clone();
+                if !renames.is_empty() {
+                    Mangler::new(&renames).item(&mut item);
+                }
+                if let Some((name, _)) = declaration(&item) {
+                    match declared.get(name) {
+                        Some(first) if first.file_id != module.file_id => diagnostics.push(
+                            Diagnostic::error(
+                                "E0304",
+                                format!("`{}` is declared in both `{}` and `{}`", name, first.name, module.name),
+                                Label::new(item.span()).with_message("declared again here"),
+                            )
+                            .with_note("exported names must be unique across all files of a program"),
+                        ),
+                        Some(_) => {}
+                        None => {
+                            declared.insert(name.to_string(), module);
+                        }
+                    }
+                }
+                items.push(item);
+            }
+        }
+
+        let entry = &self.modules[0].ast;
+        let module = match entry {
+            ASTNode::Program { module, .. } => module.clone(),
+            _ => None,
+        };
+        let program = ASTNode::Program {
+            module,
+            items,
+            span: entry.span(),
+        };
+        (program, diagnostics)
+    }
+
+    /// What each private item of `module` is renamed to when linked, unless
+    /// it is the entry, whose names are kept.
+    fn private_names(&self, module: &Module) -> HashMap<String, String> {
+        let entry = &self.modules[0];
+        if module.file_id == entry.file_id {
+            return HashMap::new();
+        }
+        let base = entry
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        let prefix = match &module.path {
+            Some(path) => {
+                let path = path.with_extension("");
+                let relative = path.strip_prefix(&base).unwrap_or(&path);
+                relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+            }
+            None => module.name.clone(),
+        };
+        top_level_items(&module.ast)
+            .iter()
+            .filter_map(declaration)
+            .filter(|(_, public)| !public)
+            .map(|(name, _)| (name.to_string(), format!("{}::{}", prefix, name)))
+            .collect()
+    }
+}
+
+/// Renames top-level items and every reference to them in a module, except
+/// where a local variable or type parameter of the same name hides them.
+struct Mangler<'a> {
+    renames: &'a HashMap<String, String>,
+    /// Names bound in the code being renamed, innermost scope last.
+    locals: Vec<HashSet<String>>,
+    /// Type parameters in scope.
+    generics: Vec<String>,
+}
+
+impl<'a> Mangler<'a> {
+    fn new(renames: &'a HashMap<String, String>) -> Self {
+        Mangler {
+            renames,
+            locals: Vec::new(),
+            generics: Vec::new(),
+        }
+    }
+
+    /// Rename a top-level item: its own name, and the references in it.
+    fn item(&mut self, item: &mut ASTNode) {
+        if let ASTNode::Function { name, .. }
+        | ASTNode::Class { name, .. }
+        | ASTNode::Interface { name, .. }
+        | ASTNode::Struct { name, .. }
+        | ASTNode::Enum { name, .. }
+        | ASTNode::Constant { name, .. } = item
+        {
+            self.rename_type(name);
+        }
+        self.node(item);
+    }
+
+    fn rename_value(&self, name: &mut String) {
+        if !self.locals.iter().any(|scope| scope.contains(name.as_str())) {
+            if let Some(renamed) = self.renames.get(name.as_str()) {
+                *name = renamed.clone();
+            }
+        }
+    }
+
+    fn rename_type(&self, name: &mut String) {
+        if !self.generics.contains(name) {
+            if let Some(renamed) = self.renames.get(name.as_str()) {
+                *name = renamed.clone();
+            }
+        }
+    }
+
+    fn bind(&mut self, name: &str) {
+        if let Some(scope) = self.locals.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn type_expr(&mut self, ty: &mut TypeExpr) {
+        self.rename_type(&mut ty.name);
+        for argument in &mut ty.arguments {
+            self.type_expr(argument);
+        }
+    }
+
+    /// Bring `params` into scope, renaming in their bounds.
+    fn enter_generics(&mut self, params: &mut [TypeParameter]) -> usize {
+        self.generics.extend(params.iter().map(|param| param.name.clone()));
+        for bound in params.iter_mut().flat_map(|param| &mut param.bounds) {
+            self.type_expr(bound);
+        }
+        params.len()
+    }
+
+    fn exit_generics(&mut self, count: usize) {
+        self.generics.truncate(self.generics.len() - count);
+    }
+
+    fn params(&mut self, params: &mut [Parameter]) {
+        for param in params {
+            self.type_expr(&mut param.param_type);
+        }
+    }
+
+    /// Rename in a function or endpoint body, with `params` bound in it.
+    fn body(&mut self, params: &[Parameter], body: &mut ASTNode) {
+        self.locals.push(params.iter().map(|param| param.name.clone()).collect());
+        self.node(body);
+        self.locals.pop();
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Wildcard { .. } | Pattern::Literal { .. } => {}
+            Pattern::Binding { name, .. } => self.bind(name),
+            Pattern::Variant { enum_name, fields, .. } => {
+                self.rename_type(enum_name);
+                for field in fields {
+                    self.pattern(field);
+                }
+            }
+        }
+    }
+
+    fn nodes(&mut self, nodes: &mut [ASTNode]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &mut ASTNode) {
+        match node {
+            ASTNode::Program { items, .. } => self.nodes(items),
+            ASTNode::Import { .. } => {}
+            ASTNode::Service { endpoints, functions, constants, .. } => {
+                self.nodes(endpoints);
+                self.nodes(functions);
+                self.nodes(constants);
+            }
+            ASTNode::Class { type_params, superclass, interfaces, fields, constructor, methods, static_methods, .. } => {
+                let generics = self.enter_generics(type_params);
+                if let Some(superclass) = superclass {
+                    self.rename_type(superclass);
+                }
+                for interface in interfaces {
+                    self.rename_type(interface);
+                }
+                for field in fields {
+                    self.type_expr(&mut field.field_type);
+                    if let Some(default) = &mut field.default {
+                        self.node(default);
+                    }
+                }
+                if let Some(constructor) = constructor {
+                    self.node(constructor);
+                }
+                self.nodes(methods);
+                self.nodes(static_methods);
+                self.exit_generics(generics);
+            }
+            ASTNode::Interface { methods, .. } => {
+                for method in methods {
+                    self.params(&mut method.params);
+                    if let Some(return_type) = &mut method.return_type {
+                        self.type_expr(return_type);
+                    }
+                }
+            }
+            ASTNode::Struct { type_params, fields, .. } => {
+                let generics = self.enter_generics(type_params);
+                for field in fields {
+                    self.type_expr(&mut field.field_type);
+                    if let Some(default) = &mut field.default {
+                        self.node(default);
+                    }
+                }
+                self.exit_generics(generics);
+            }
+            ASTNode::Enum { type_params, variants, .. } => {
+                let generics = self.enter_generics(type_params);
+                for variant in variants {
+                    self.params(&mut variant.fields);
+                }
+                self.exit_generics(generics);
+            }
+            ASTNode::Constant { type_annotation, value, .. } => {
+                if let Some(type_annotation) = type_annotation {
+                    self.type_expr(type_annotation);
+                }
+                self.node(value);
+            }
+            ASTNode::Function { type_params, params, return_type, body, .. } => {
+                let generics = self.enter_generics(type_params);
+                self.params(params);
+                if let Some(return_type) = return_type {
+                    self.type_expr(return_type);
+                }
+                self.body(params, body);
+                self.exit_generics(generics);
+            }
+            ASTNode::Endpoint { params, return_type, body, .. } => {
+                self.params(params);
+                self.type_expr(return_type);
+                self.body(params, body);
+            }
+            ASTNode::Block { statements, .. } => {
+                self.locals.push(HashSet::new());
+                self.nodes(statements);
+                self.locals.pop();
+            }
+            ASTNode::VariableDeclaration { name, type_annotation, initializer, .. } => {
+                if let Some(type_annotation) = type_annotation {
+                    self.type_expr(type_annotation);
+                }
+                if let Some(initializer) = initializer {
+                    self.node(initializer);
+                }
+                self.bind(name);
+            }
+            ASTNode::ForStatement { variable, iterable, body, .. } => {
+                self.node(iterable);
+                self.locals.push(HashSet::from([variable.clone()]));
+                self.node(body);
+                self.locals.pop();
+            }
+            ASTNode::TryStatement { body, catches, .. } => {
+                self.node(body);
+                for catch in catches {
+                    if let Some(error_type) = &mut catch.error_type {
+                        self.type_expr(error_type);
+                    }
+                    self.locals.push(HashSet::from([catch.name.clone()]));
+                    self.node(&mut catch.body);
+                    self.locals.pop();
+                }
+            }
+            ASTNode::Match { subject, arms, .. } => {
+                self.node(subject);
+                for arm in arms {
+                    self.locals.push(HashSet::new());
+                    self.pattern(&mut arm.pattern);
+                    self.node(&mut arm.body);
+                    self.locals.pop();
+                }
+            }
+            ASTNode::StructLiteral { name, fields, .. } => {
+                self.rename_type(name);
+                for (_, value) in fields {
+                    self.node(value);
+                }
+            }
+            ASTNode::Identifier { name, .. } => self.rename_value(name),
+            ASTNode::InterpolatedString { parts, .. } => {
+                for part in parts {
+                    if let StringPart::Expression(expression) = part {
+                        self.node(expression);
+                    }
+                }
+            }
+            ASTNode::ObjectLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.node(value);
+                }
+            }
+            ASTNode::ArrayLiteral { elements: nodes, .. } => self.nodes(nodes),
+            ASTNode::CallExpression { callee, arguments, .. } => {
+                self.node(callee);
+                self.nodes(arguments);
+            }
+            ASTNode::Assignment { target: left, value: right, .. }
+            | ASTNode::BinaryExpression { left, right, .. }
+            | ASTNode::IndexExpression { object: left, index: right, .. }
+            | ASTNode::WhileStatement { condition: left, body: right, .. } => {
+                self.node(left);
+                self.node(right);
+            }
+            ASTNode::IfStatement { condition, then_branch, else_branch, .. } => {
+                self.node(condition);
+                self.node(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.node(else_branch);
+                }
+            }
+            ASTNode::ReturnStatement { expression, .. } => {
+                if let Some(expression) = expression {
+                    self.node(expression);
+                }
+            }
+            ASTNode::ExpressionStatement { expression: inner, .. }
+            | ASTNode::DeferStatement { statement: inner, .. }
+            | ASTNode::UnaryExpression { operand: inner, .. }
+            | ASTNode::MemberExpression { object: inner, .. }
+            | ASTNode::GroupingExpression { expression: inner, .. }
+            | ASTNode::ThrowExpression { error: inner, .. }
+            | ASTNode::AwaitExpression { future: inner, .. }
+            | ASTNode::PropagateExpression { expression: inner, .. } => self.node(inner),
+            ASTNode::BreakStatement { .. }
+            | ASTNode::ContinueStatement { .. }
+            | ASTNode::StringLiteral { .. }
+            | ASTNode::NumberLiteral { .. }
+            | ASTNode::BooleanLiteral { .. }
+            | ASTNode::NullLiteral { .. }
+            | ASTNode::SuperExpression { .. } => {}
+        }
+    }
+}
+
+struct Loader<'a> {
+    source_map: &'a mut SourceMap,
+    graph: ModuleGraph,
+    /// Loaded modules by canonical path, so each file is parsed once.
+    by_path: HashMap<PathBuf, usize>,
+    /// Modules whose imports are being loaded, outermost first.
+    stack: Vec<usize>,
+    /// Directory of the entry file, for imports of source without a path.
+    base: PathBuf,
+    /// Root for imports that are not relative to the importing file.
+    src_dir: PathBuf,
+}
+
+impl Loader<'_> {
+    fn visit(&mut self, file_id: usize, path: Option<PathBuf>) -> usize {
+        let file = self.source_map.file(file_id);
+        let name = file.name.clone();
+        let mut parser = Parser::with_file_id(&file.source, file_id);
+        let ast = parser.parse_program();
+        self.graph.diagnostics.extend(parser.take_diagnostics());
+
+        let imports: Vec<(Vec<ImportedName>, String, Span)> = top_level_items(&ast)
+            .iter()
+            .filter_map(|item| match item {
+                ASTNode::Import { names, path, path_span, .. } => Some((names.clone(), path.clone(), *path_span)),
+                _ => None,
+            })
+            .collect();
+
+        let index = self.graph.modules.len();
+        if let Some(canonical) = path.as_ref().and_then(|path| path.canonicalize().ok()) {
+            self.by_path.insert(canonical, index);
+        }
+        self.graph.modules.push(Module {
+            name,
+            path,
+            file_id,
+            ast,
+            imports: Vec::new(),
+        });
+
+        self.stack.push(index);
+        for (names, target, span) in imports {
+            if let Some(dependency) = self.resolve(index, &target, span) {
+                self.graph.modules[index].imports.push(dependency);
+                self.check_names(dependency, &names, &target);
+            }
+        }
+        self.stack.pop();
+        self.graph.order.push(index);
+        index
+    }
+
+    /// Find, and load if needed, the module `target` names. Paths starting
+    /// with `./` or `../` are relative to the importing file, any other to
+    /// the project's `src/` directory; `.ath` is implied.
+    fn resolve(&mut self, importer: usize, target: &str, span: Span) -> Option<usize> {
+        let dir = match &self.graph.modules[importer].path {
+            Some(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => self.base.clone(),
+        };
+        let mut candidate = if target.starts_with("./") || target.starts_with("../") {
+            dir.join(target)
+        } else {
+            self.src_dir.join(target)
+        };
+        if candidate.extension().is_none() {
+            candidate.set_extension("ath");
+        }
+        let candidate = normalize(&candidate);
+
+        let Ok(path) = candidate.canonicalize() else {
+            self.graph.diagnostics.push(
+                Diagnostic::error(
+                    "E0300",
+                    format!("cannot find module `{}`", target),
+                    Label::new(span).with_message("no such file"),
+                )
+                .with_note(format!("looked for `{}`", candidate.display())),
+            );
+            return None;
+        };
+
+        if let Some(&index) = self.by_path.get(&path) {
+            if let Some(position) = self.stack.iter().position(|&open| open == index) {
+                let cycle = self.stack[position..]
+                    .iter()
+                    .chain(std::iter::once(&index))
+                    .map(|&module| self.graph.modules[module].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                self.graph.diagnostics.push(
+                    Diagnostic::error(
+                        "E0301",
+                        format!("import cycle through module `{}`", target),
+                        Label::new(span).with_message("this import closes the cycle"),
+                    )
+                    .with_note(format!("cycle: {}", cycle))
+                    .with_suggestion("move the shared items into a file that both can import", None),
+                );
+                return None;
+            }
+            return Some(index);
+        }
+
+        match self.source_map.load_file(&candidate) {
+            Ok(file_id) => Some(self.visit(file_id, Some(candidate))),
+            Err(e) => {
+                self.graph.diagnostics.push(Diagnostic::error(
+                    "E0300",
+                    format!("cannot read module `{}`: {}", target, e),
+                    Label::new(span),
+                ));
+                None
+            }
+        }
+    }
+
+    /// Every imported name must be a top-level item the module exports.
+    fn check_names(&mut self, dependency: usize, names: &[ImportedName], target: &str) {
+        let items = top_level_items(&self.graph.modules[dependency].ast);
+        for imported in names {
+            let found = items
+                .iter()
+                .filter_map(declaration)
+                .find(|(name, _)| *name == imported.name);
+            let diagnostic = match found {
+                Some((_, true)) => continue,
+                Some((_, false)) => Diagnostic::error(
+                    "E0303",
+                    format!("`{}` is private to module `{}`", imported.name, target),
+                    Label::new(imported.span).with_message("not exported"),
+                )
+                .with_suggestion(format!("mark `{}` with `export` in its module", imported.name), None),
+                None => Diagnostic::error(
+                    "E0302",
+                    format!("module `{}` has no item named `{}`", target, imported.name),
+                    Label::new(imported.span).with_message("not found in module"),
+                ),
+            };
+            self.graph.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+fn top_level_items(ast: &ASTNode) -> &[ASTNode] {
+    match ast {
+        ASTNode::Program { items, .. } => items,
+        other => std::slice::from_ref(other),
+    }
+}
+
+/// Name and visibility of an importable item.
+fn declaration(item: &ASTNode) -> Option<(&str, bool)> {
+    match item {
+        ASTNode::Function { name, public, .. }
+        | ASTNode::Class { name, public, .. }
+        | ASTNode::Interface { name, public, .. }
+        | ASTNode::Struct { name, public, .. }
+        | ASTNode::Enum { name, public, .. }
+        | ASTNode::Constant { name, public, .. } => Some((name, *public)),
+        _ => None,
+    }
+}
+
+/// `src/` of the nearest directory at or above `dir` holding an
+/// `aether.toml`.
+fn project_src_dir(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join("aether.toml").is_file())
+        .map(|root| root.join("src"))
+}
+
+/// Drop `.` components and fold `..` into their parent, without touching
+/// the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -1,204 +1,2352 @@
+use std::fmt;
+
+use super::diagnostics::{Diagnostic, Label};
 use super::lexer::{Lexer, Token, TokenType};
+use super::source_map::Span;
 
 #[derive(Debug, Clone)]
 pub enum ASTNode {
+    /// The root of a source file and its top-level items. `module` is set
+    /// when the items are wrapped in a `module Name { ... }` block.
+    Program {
+        module: Option<String>,
+        items: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `import { a, b } from "path";`, bringing exported items of another
+    /// source file into scope.
+    Import {
+        names: Vec<ImportedName>,
+        path: String,
+        path_span: Span,
+        span: Span,
+    },
     Service {
         name: String,
+        /// Prefix for every endpoint path, from a `@base("/path")` decorator.
+        base_path: Option<String>,
         endpoints: Vec<ASTNode>,
+        functions: Vec<ASTNode>,
+        constants: Vec<ASTNode>,
+        doc: Option<String>,
+        span: Span,
+    },
+    Class {
+        name: String,
+        type_params: Vec<TypeParameter>,
+        superclass: Option<String>,
+        interfaces: Vec<String>,
+        fields: Vec<Field>,
+        /// The `init` constructor, as a `Function` named `init`.
+        constructor: Option<Box<ASTNode>>,
+        methods: Vec<ASTNode>,
+        static_methods: Vec<ASTNode>,
+        public: bool,
+        doc: Option<String>,
+        span: Span,
+    },
+    Interface {
+        name: String,
+        methods: Vec<MethodSignature>,
+        public: bool,
+        doc: Option<String>,
+        span: Span,
+    },
+    /// A `struct` (or `record`): plain data with named, typed fields.
+    Struct {
+        name: String,
+        type_params: Vec<TypeParameter>,
+        fields: Vec<Field>,
+        public: bool,
+        doc: Option<String>,
+        span: Span,
+    },
+    /// An `enum`: a value that is exactly one of its variants.
+    Enum {
+        name: String,
+        type_params: Vec<TypeParameter>,
+        variants: Vec<EnumVariant>,
+        public: bool,
+        doc: Option<String>,
+        span: Span,
+    },
+    Constant {
+        name: String,
+        type_annotation: Option<TypeExpr>,
+        value: Box<ASTNode>,
+        public: bool,
+        doc: Option<String>,
+        span: Span,
+    },
+    Function {
+        name: String,
+        type_params: Vec<TypeParameter>,
+        params: Vec<Parameter>,
+        /// `None` when the function returns nothing.
+        return_type: Option<TypeExpr>,
+        body: Box<ASTNode>,
+        /// Whether the function is `async`: calling it runs the body
+        /// concurrently and gives a `Future` of what it returns.
+        is_async: bool,
+        public: bool,
+        doc: Option<String>,
+        span: Span,
     },
     Endpoint {
         path: String,
         method: String,
         params: Vec<Parameter>,
-        return_type: String,
+        return_type: TypeExpr,
         body: Box<ASTNode>,
+        doc: Option<String>,
+        span: Span,
     },
     Block {
         statements: Vec<ASTNode>,
+        span: Span,
     },
     ReturnStatement {
+        /// `None` for a bare `return;`, which returns `null`.
+        expression: Option<Box<ASTNode>>,
+        span: Span,
+    },
+    VariableDeclaration {
+        name: String,
+        mutable: bool,
+        type_annotation: Option<TypeExpr>,
+        initializer: Option<Box<ASTNode>>,
+        span: Span,
+    },
+    Assignment {
+        target: Box<ASTNode>,
+        /// The arithmetic operator of a compound assignment such as `+=`.
+        operator: Option<BinaryOperator>,
+        value: Box<ASTNode>,
+        span: Span,
+    },
+    ExpressionStatement {
         expression: Box<ASTNode>,
+        span: Span,
+    },
+    IfStatement {
+        condition: Box<ASTNode>,
+        then_branch: Box<ASTNode>,
+        /// Either a `Block` or, for `else if`, another `IfStatement`.
+        else_branch: Option<Box<ASTNode>>,
+        span: Span,
+    },
+    WhileStatement {
+        condition: Box<ASTNode>,
+        body: Box<ASTNode>,
+        span: Span,
+    },
+    ForStatement {
+        variable: String,
+        iterable: Box<ASTNode>,
+        body: Box<ASTNode>,
+        span: Span,
+    },
+    BreakStatement {
+        span: Span,
+    },
+    ContinueStatement {
+        span: Span,
+    },
+    /// `try { ... } catch (e: Type) { ... }`: run `body`, handing an error
+    /// thrown out of it to the first clause accepting it.
+    TryStatement {
+        body: Box<ASTNode>,
+        catches: Vec<CatchClause>,
+        span: Span,
+    },
+    /// `defer statement`: run `statement` as the enclosing block exits,
+    /// however it exits, before any statement deferred earlier in it.
+    DeferStatement {
+        statement: Box<ASTNode>,
+        span: Span,
     },
     StringLiteral {
         value: String,
+        span: Span,
+    },
+    /// `"text \(expression) text"`
+    InterpolatedString {
+        parts: Vec<StringPart>,
+        span: Span,
+    },
+    NumberLiteral {
+        value: f64,
+        /// Whether it was written with a fractional part, as in `3.0`,
+        /// which makes it a `Float` even if the value is whole.
+        is_float: bool,
+        span: Span,
+    },
+    BooleanLiteral {
+        value: bool,
+        span: Span,
+    },
+    NullLiteral {
+        span: Span,
+    },
+    ArrayLiteral {
+        elements: Vec<ASTNode>,
+        span: Span,
+    },
+    ObjectLiteral {
+        fields: Vec<(String, ASTNode)>,
+        span: Span,
+    },
+    /// `Name { field: value, ... }`
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, ASTNode)>,
+        span: Span,
+    },
+    /// `match subject { pattern => arm, ... }`. Used as a statement, an arm
+    /// may be a block; used as an expression, each arm is an expression.
+    Match {
+        subject: Box<ASTNode>,
+        arms: Vec<MatchArm>,
+        span: Span,
     },
     Identifier {
         name: String,
+        span: Span,
+    },
+    BinaryExpression {
+        operator: BinaryOperator,
+        left: Box<ASTNode>,
+        right: Box<ASTNode>,
+        span: Span,
+    },
+    UnaryExpression {
+        operator: UnaryOperator,
+        operand: Box<ASTNode>,
+        span: Span,
+    },
+    CallExpression {
+        callee: Box<ASTNode>,
+        arguments: Vec<ASTNode>,
+        span: Span,
+    },
+    MemberExpression {
+        object: Box<ASTNode>,
+        member: String,
+        span: Span,
+    },
+    IndexExpression {
+        object: Box<ASTNode>,
+        index: Box<ASTNode>,
+        span: Span,
+    },
+    GroupingExpression {
+        expression: Box<ASTNode>,
+        span: Span,
+    },
+    /// `super`, only valid as the object of a method call inside a class.
+    SuperExpression {
+        span: Span,
+    },
+    /// `throw error`: unwind to the nearest `catch` accepting `error`.
+    ThrowExpression {
+        error: Box<ASTNode>,
+        span: Span,
+    },
+    /// `await future`: wait for a `Future` and take its value.
+    AwaitExpression {
+        future: Box<ASTNode>,
+        span: Span,
+    },
+    /// `expression?`: what a `Result.Ok` or `Option.Some` holds, or else
+    /// return the `Err` or `None` from the enclosing function.
+    PropagateExpression {
+        expression: Box<ASTNode>,
+        span: Span,
+    },
+}
+
+impl ASTNode {
+    pub fn span(&self) -> Span {
+        match self {
+            ASTNode::Program { span, .. }
+            | ASTNode::Import { span, .. }
+            | ASTNode::Service { span, .. }
+            | ASTNode::Function { span, .. }
+            | ASTNode::Class { span, .. }
+            | ASTNode::Interface { span, .. }
+            | ASTNode::Struct { span, .. }
+            | ASTNode::Enum { span, .. }
+            | ASTNode::Constant { span, .. }
+            | ASTNode::Endpoint { span, .. }
+            | ASTNode::Block { span, .. }
+            | ASTNode::ReturnStatement { span, .. }
+            | ASTNode::VariableDeclaration { span, .. }
+            | ASTNode::Assignment { span, .. }
+            | ASTNode::ExpressionStatement { span, .. }
+            | ASTNode::IfStatement { span, .. }
+            | ASTNode::WhileStatement { span, .. }
+            | ASTNode::ForStatement { span, .. }
+            | ASTNode::BreakStatement { span }
+            | ASTNode::ContinueStatement { span }
+            | ASTNode::TryStatement { span, .. }
+            | ASTNode::DeferStatement { span, .. }
+            | ASTNode::StringLiteral { span, .. }
+            | ASTNode::InterpolatedString { span, .. }
+            | ASTNode::NumberLiteral { span, .. }
+            | ASTNode::BooleanLiteral { span, .. }
+            | ASTNode::NullLiteral { span }
+            | ASTNode::ArrayLiteral { span, .. }
+            | ASTNode::ObjectLiteral { span, .. }
+            | ASTNode::StructLiteral { span, .. }
+            | ASTNode::Match { span, .. }
+            | ASTNode::Identifier { span, .. }
+            | ASTNode::BinaryExpression { span, .. }
+            | ASTNode::UnaryExpression { span, .. }
+            | ASTNode::CallExpression { span, .. }
+            | ASTNode::MemberExpression { span, .. }
+            | ASTNode::IndexExpression { span, .. }
+            | ASTNode::GroupingExpression { span, .. }
+            | ASTNode::SuperExpression { span }
+            | ASTNode::ThrowExpression { span, .. }
+            | ASTNode::AwaitExpression { span, .. }
+            | ASTNode::PropagateExpression { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    NullCoalesce,
+    Range,
+}
+
+impl BinaryOperator {
+    /// Binding power and right-associativity of a binary operator token,
+    /// loosest first: `||`, `&&`, equality, comparison, `??`, `..`,
+    /// additive, multiplicative.
+    fn from_token(token_type: &TokenType) -> Option<(BinaryOperator, u8, bool)> {
+        let entry = match token_type {
+            TokenType::OrOr => (BinaryOperator::Or, 1, false),
+            TokenType::AndAnd => (BinaryOperator::And, 2, false),
+            TokenType::EqualEqual => (BinaryOperator::Equal, 3, false),
+            TokenType::BangEqual => (BinaryOperator::NotEqual, 3, false),
+            TokenType::Less => (BinaryOperator::Less, 4, false),
+            TokenType::LessEqual => (BinaryOperator::LessEqual, 4, false),
+            TokenType::Greater => (BinaryOperator::Greater, 4, false),
+            TokenType::GreaterEqual => (BinaryOperator::GreaterEqual, 4, false),
+            TokenType::QuestionQuestion => (BinaryOperator::NullCoalesce, 5, true),
+            TokenType::DotDot => (BinaryOperator::Range, 6, false),
+            TokenType::Plus => (BinaryOperator::Add, 7, false),
+            TokenType::Minus => (BinaryOperator::Subtract, 7, false),
+            TokenType::Star => (BinaryOperator::Multiply, 8, false),
+            TokenType::Slash => (BinaryOperator::Divide, 8, false),
+            TokenType::Percent => (BinaryOperator::Modulo, 8, false),
+            _ => return None,
+        };
+        Some(entry)
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+            BinaryOperator::NullCoalesce => "??",
+            BinaryOperator::Range => "..",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Not,
+    Negate,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOperator::Not => write!(f, "!"),
+            UnaryOperator::Negate => write!(f, "-"),
+        }
+    }
+}
+
+/// A type as written in the source: a name, any generic arguments in
+/// angle brackets, and a trailing `?` if the value may be `null`.
+#[derive(Debug, Clone)]
+pub struct TypeExpr {
+    pub name: String,
+    pub arguments: Vec<TypeExpr>,
+    pub optional: bool,
+    pub span: Span,
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.arguments.is_empty() {
+            let arguments: Vec<String> = self.arguments.iter().map(ToString::to_string).collect();
+            write!(f, "<{}>", arguments.join(", "))?;
+        }
+        if self.optional {
+            write!(f, "?")?;
+        }
+        Ok(())
+    }
+}
+
+impl TypeExpr {
+    /// This type with each of `params` replaced by the argument in the same
+    /// position, or by `dynamic` where there is none. Passing no arguments
+    /// erases the parameters, which is all the run time can check.
+    pub fn substitute(&self, params: &[TypeParameter], arguments: &[TypeExpr]) -> TypeExpr {
+        if params.is_empty() {
+            return self.clone();
+        }
+        if self.arguments.is_empty() {
+            if let Some(index) = params.iter().position(|param| param.name == self.name) {
+                let mut argument = arguments.get(index).cloned().unwrap_or_else(|| TypeExpr {
+                    name: "dynamic".to_string(),
+                    arguments: Vec::new(),
+                    optional: false,
+                    span: self.span,
+                });
+                argument.optional |= self.optional;
+                return argument;
+            }
+        }
+        TypeExpr {
+            name: self.name.clone(),
+            arguments: self.arguments.iter().map(|argument| argument.substitute(params, arguments)).collect(),
+            optional: self.optional,
+            span: self.span,
+        }
+    }
+}
+
+/// A type parameter of a generic declaration, as in `fn first<T>` or
+/// `class Sorted<T: Comparable>`, with the interfaces bounding it.
+#[derive(Debug, Clone)]
+pub struct TypeParameter {
+    pub name: String,
+    pub bounds: Vec<TypeExpr>,
+    pub span: Span,
+}
+
+/// A piece of an interpolated string.
+#[derive(Debug, Clone)]
+pub enum StringPart {
+    Text(String),
+    Expression(Box<ASTNode>),
+}
+
+/// One variant of an enum: `Name` or `Name(field: Type, ...)`.
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<Parameter>,
+    pub span: Span,
+}
+
+/// `catch (name[: Type]) { ... }`. Without a type, the clause catches
+/// any `Error`.
+#[derive(Debug, Clone)]
+pub struct CatchClause {
+    pub name: String,
+    pub error_type: Option<TypeExpr>,
+    pub body: ASTNode,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: ASTNode,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`: matches anything.
+    Wildcard { span: Span },
+    /// A name, bound to whatever value is matched.
+    Binding { name: String, span: Span },
+    /// A number, string, boolean or `null`, matching an equal value.
+    Literal { value: ASTNode, span: Span },
+    /// `Enum.Variant` or `Enum.Variant(pattern, ...)`, with one pattern per
+    /// payload field.
+    Variant {
+        enum_name: String,
+        variant: String,
+        fields: Vec<Pattern>,
+        span: Span,
     },
 }
 
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard { span }
+            | Pattern::Binding { span, .. }
+            | Pattern::Literal { span, .. }
+            | Pattern::Variant { span, .. } => *span,
+        }
+    }
+
+    /// Whether the pattern matches every value.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Pattern::Wildcard { .. } | Pattern::Binding { .. })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
-    pub param_type: String,
+    pub param_type: TypeExpr,
+    pub span: Span,
+}
+
+/// One name in the braces of an `import`.
+#[derive(Debug, Clone)]
+pub struct ImportedName {
+    pub name: String,
+    pub span: Span,
+}
+
+/// A method an interface requires implementing classes to provide.
+#[derive(Debug, Clone)]
+pub struct MethodSignature {
+    pub name: String,
+    pub params: Vec<Parameter>,
+    pub return_type: Option<TypeExpr>,
+    pub span: Span,
+}
+
+/// A typed field of a class, with an optional default value.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub field_type: TypeExpr,
+    pub default: Option<ASTNode>,
+    pub span: Span,
+}
+
+type ParseResult<T> = Result<T, Box<Diagnostic>>;
+
+enum ClassMember {
+    Field(Field),
+    Constructor(ASTNode),
 }
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Token,
+    previous_span: Span,
+    /// Line of the most recently consumed token.
+    previous_line: usize,
+    /// Whether a lexer error was dropped just before the current token. A
+    /// parse error there is only a consequence of it, so is not reported.
+    after_lexer_error: bool,
+    pending_docs: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    /// Number of loops enclosing the statement being parsed.
+    loop_depth: usize,
+    /// Whether the statement being parsed is deferred, and so may not
+    /// leave its block.
+    deferring: bool,
+    /// Whether `Name {` starts a struct literal. Off in the head of an
+    /// `if`, `while` or `for`, where the brace opens the body instead.
+    struct_literals: bool,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
-        let mut lexer = Lexer::new(input);
+        Self::with_file_id(input, 0)
+    }
+
+    /// Create a parser whose spans refer to `file_id` in a `SourceMap`.
+    pub fn with_file_id(input: &'a str, file_id: usize) -> Self {
+        let mut lexer = Lexer::with_file_id(input, file_id);
         let current_token = lexer.next_token();
-        Parser {
+        let mut parser = Parser {
             lexer,
+            previous_span: Span::new(file_id, 0, 0),
+            previous_line: 1,
+            after_lexer_error: false,
             current_token,
-        }
+            pending_docs: Vec::new(),
+            diagnostics: Vec::new(),
+            loop_depth: 0,
+            deferring: false,
+            struct_literals: true,
+        };
+        parser.skip_trivia();
+        parser
+    }
+
+    /// All diagnostics reported so far by the lexer and parser, in source order.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        diagnostics.sort_by_key(|d| (d.primary.span.file_id, d.primary.span.start));
+        diagnostics
     }
 
     fn advance(&mut self) {
+        self.previous_span = self.current_token.span;
+        self.previous_line = self.current_token.line;
+        self.after_lexer_error = false;
         self.current_token = self.lexer.next_token();
+        self.skip_trivia();
     }
 
-    fn expect(&mut self, token_type: TokenType) -> Result<(), String> {
-        if std::mem::discriminant(&self.current_token.token_type) == std::mem::discriminant(&token_type) {
-            self.advance();
-            Ok(())
-        } else {
-            Err(format!(
-                "Expected {:?}, got {:?} at line {} column {}",
-                token_type,
-                self.current_token.token_type,
-                self.current_token.line,
-                self.current_token.column
-            ))
+    /// Doc comments and lexer errors never reach the grammar. Doc comments
+    /// are buffered until the next documentable item claims them; lexer
+    /// errors are reported and the offending token dropped.
+    fn skip_trivia(&mut self) {
+        loop {
+            match &self.current_token.token_type {
+                TokenType::DocComment(text) => self.pending_docs.push(text.clone()),
+                TokenType::Error(error) => {
+                    let diagnostic = error.to_diagnostic(self.current_token.span);
+                    self.diagnostics.push(diagnostic);
+                    self.after_lexer_error = true;
+                }
+                _ => return,
+            }
+            self.current_token = self.lexer.next_token();
         }
     }
 
-    pub fn parse_service(&mut self) -> Result<ASTNode, String> {
-        self.expect(TokenType::Service)?;
-        
-        let name = match &self.current_token.token_type {
-            TokenType::Identifier(name) => name.clone(),
-            _ => return Err("Expected service name".to_string()),
-        };
-        self.advance();
+    /// Record a parse error, unless it is a consequence of a lexer error
+    /// already reported for the preceding source.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if !self.after_lexer_error {
+            self.diagnostics.push(diagnostic);
+        }
+    }
 
-        self.expect(TokenType::LeftBrace)?;
-        
-        let mut endpoints = Vec::new();
-        while let TokenType::At = self.current_token.token_type {
-            endpoints.push(self.parse_endpoint()?);
+    /// Whether the current token can begin a statement, so that a `;`
+    /// missing before it was most likely just left out. Tokens that may
+    /// also continue a line only count when they start a new one.
+    fn at_statement_start(&self) -> bool {
+        match self.current_token.token_type {
+            TokenType::Let
+            | TokenType::Var
+            | TokenType::Return
+            | TokenType::If
+            | TokenType::While
+            | TokenType::For
+            | TokenType::Match
+            | TokenType::Try
+            | TokenType::Defer
+            | TokenType::Throw
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::RightBrace => true,
+            TokenType::Identifier(_)
+            | TokenType::StringLiteral(_)
+            | TokenType::StringHead(_)
+            | TokenType::NumberLiteral(_)
+            | TokenType::FloatLiteral(_)
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Null
+            | TokenType::Super
+            | TokenType::Bang
+            | TokenType::Await => self.current_token.line > self.previous_line,
+            _ => false,
         }
+    }
 
-        self.expect(TokenType::RightBrace)?;
+    fn take_docs(&mut self) -> Option<String> {
+        if self.pending_docs.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.pending_docs).join("\n"))
+        }
+    }
 
-        Ok(ASTNode::Service {
-            name,
-            endpoints,
-        })
+    /// Span from `start` up to the end of the most recently consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span)
     }
 
-    fn parse_endpoint(&mut self) -> Result<ASTNode, String> {
-        // Parse @get decorator
-        self.expect(TokenType::At)?;
-        let method = match &self.current_token.token_type {
-            TokenType::Identifier(method) => method.clone().to_lowercase(),
-            _ => return Err("Expected HTTP method".to_string()),
-        };
-        self.advance();
+    fn check(&self, token_type: &TokenType) -> bool {
+        std::mem::discriminant(&self.current_token.token_type) == std::mem::discriminant(token_type)
+    }
 
-        // Parse path
-        self.expect(TokenType::LeftParen)?;
-        let path = match &self.current_token.token_type {
-            TokenType::StringLiteral(path) => path.clone(),
-            _ => return Err("Expected path string".to_string()),
-        };
-        self.advance();
-        self.expect(TokenType::RightParen)?;
+    fn current_label(&self) -> Label {
+        Label::new(self.current_token.span)
+    }
 
-        // Parse endpoint keyword and name
-        self.expect(TokenType::Endpoint)?;
-        let _name = match &self.current_token.token_type {
-            TokenType::Identifier(name) => name.clone(),
-            _ => return Err("Expected endpoint name".to_string()),
-        };
-        self.advance();
+    fn error_here(&self, code: &'static str, expected: &str) -> Box<Diagnostic> {
+        Box::new(Diagnostic::error(
+            code,
+            format!("expected {}, found {}", expected, self.current_token.token_type.describe()),
+            self.current_label().with_message(format!("expected {}", expected)),
+        ))
+    }
 
-        // Parse parameters
-        self.expect(TokenType::LeftParen)?;
-        let mut params = Vec::new();
-        while let TokenType::Identifier(_) = &self.current_token.token_type {
-            let param_name = match &self.current_token.token_type {
-                TokenType::Identifier(name) => name.clone(),
-                _ => break,
-            };
+    fn expect(&mut self, token_type: TokenType) -> ParseResult<()> {
+        if self.check(&token_type) {
             self.advance();
+            Ok(())
+        } else {
+            let expected = format!("`{}`", token_type);
+            if token_type == TokenType::Semicolon {
+                // A missing terminator is best reported where it should have been.
+                let end = self.previous_span.end;
+                let diagnostic = Diagnostic::error(
+                    "E0100",
+                    format!("expected `;`, found {}", self.current_token.token_type.describe()),
+                    Label::new(Span::new(self.previous_span.file_id, end, end))
+                        .with_message("expected `;` here"),
+                )
+                .with_secondary(self.current_label().with_message("unexpected token"))
+                .with_suggestion("add a semicolon", Some(";".to_string()));
+                if self.at_statement_start() {
+                    // Carry on as if it were there, so the next statement is still parsed
+                    self.report(diagnostic);
+                    return Ok(());
+                }
+                return Err(Box::new(diagnostic));
+            }
+            let mut diagnostic = self.error_here("E0100", &expected);
+            if matches!(token_type, TokenType::RightParen | TokenType::RightBrace) {
+                diagnostic = Box::new(diagnostic.with_suggestion(
+                    format!("insert {} here", expected),
+                    Some(token_type.to_string()),
+                ));
+            }
+            Err(diagnostic)
+        }
+    }
 
-            self.expect(TokenType::Colon)?;
+    fn expect_identifier(&mut self, what: &str) -> ParseResult<String> {
+        match &self.current_token.token_type {
+            TokenType::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.error_here("E0101", what)),
+        }
+    }
 
-            let param_type = match &self.current_token.token_type {
-                TokenType::Identifier(type_name) => type_name.clone(),
-                _ => return Err("Expected parameter type".to_string()),
-            };
+    /// Skip tokens until the start of the next endpoint, function or
+    /// service, or the end of the enclosing service, stepping over any nested
+    /// braces on the way.
+    fn synchronize_item(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current_token.token_type {
+                TokenType::EOF => return,
+                TokenType::At
+                | TokenType::Fn
+                | TokenType::Async
+                | TokenType::Const
+                | TokenType::Class
+                | TokenType::Interface
+                | TokenType::Struct
+                | TokenType::Record
+                | TokenType::Enum
+                | TokenType::Static
+                | TokenType::Pub
+                | TokenType::Export
+                | TokenType::Import
+                | TokenType::Service
+                    if depth == 0 =>
+                {
+                    return
+                }
+                TokenType::RightBrace if depth == 0 => return,
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
+            }
             self.advance();
+        }
+    }
 
-            params.push(Parameter {
-                name: param_name,
-                param_type,
-            });
+    /// Skip tokens until the start of the next top-level item, or a `}`
+    /// that may close the enclosing module, stepping over nested braces.
+    fn synchronize_top_level(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current_token.token_type {
+                TokenType::EOF => return,
+                TokenType::Service
+                | TokenType::At
+                | TokenType::Fn
+                | TokenType::Async
+                | TokenType::Const
+                | TokenType::Class
+                | TokenType::Interface
+                | TokenType::Struct
+                | TokenType::Record
+                | TokenType::Enum
+                | TokenType::Pub
+                | TokenType::Export
+                | TokenType::Import
+                | TokenType::RightBrace
+                    if depth == 0 =>
+                {
+                    return
+                }
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
 
-            if let TokenType::RightParen = self.current_token.token_type {
-                break;
+    /// Skip tokens until just past the next `;`, or up to the `}` closing
+    /// the current block.
+    fn synchronize_statement(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current_token.token_type {
+                TokenType::EOF => return,
+                TokenType::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::RightBrace if depth == 0 => return,
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
             }
+            self.advance();
         }
-        self.expect(TokenType::RightParen)?;
+    }
 
-        // Parse return type
-        self.expect(TokenType::Colon)?;
-        let return_type = match &self.current_token.token_type {
-            TokenType::Identifier(type_name) => type_name.clone(),
-            _ => return Err("Expected return type".to_string()),
+    /// Skip a malformed `match` arm, up to and including the comma ending
+    /// it, or to the `}` closing the `match`.
+    fn synchronize_arm(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current_token.token_type {
+                TokenType::EOF => return,
+                TokenType::Comma if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::RightBrace if depth == 0 => return,
+                TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skip a malformed interface member, including any body it was
+    /// wrongly given, stopping before the next `fn` or the closing `}`.
+    fn synchronize_signature(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current_token.token_type {
+                TokenType::EOF => return,
+                TokenType::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::Fn | TokenType::RightBrace if depth == 0 => return,
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Parse a whole source file, recovering from errors in individual
+    /// items and statements. All problems are reported through
+    /// `take_diagnostics`.
+    pub fn parse_program(&mut self) -> ASTNode {
+        let start = self.current_token.span;
+        let module = if self.check(&TokenType::Module) {
+            match self.parse_module_header() {
+                Ok(name) => Some(name),
+                Err(diagnostic) => {
+                    self.report(*diagnostic);
+                    Some(String::new())
+                }
+            }
+        } else {
+            None
         };
-        self.advance();
 
-        // Parse body
-        self.expect(TokenType::LeftBrace)?;
-        let body = self.parse_block()?;
-        self.expect(TokenType::RightBrace)?;
+        let mut items = Vec::new();
+        loop {
+            match self.current_token.token_type {
+                TokenType::EOF => break,
+                TokenType::RightBrace if module.is_some() => break,
+                TokenType::Service | TokenType::At => match self.parse_service() {
+                    Some(service) => items.push(service),
+                    None => self.synchronize_top_level(),
+                },
+                TokenType::Import => match self.parse_import() {
+                    Ok(import) => items.push(import),
+                    Err(diagnostic) => {
+                        self.report(*diagnostic);
+                        self.synchronize_top_level();
+                    }
+                },
+                TokenType::Fn
+                | TokenType::Async
+                | TokenType::Const
+                | TokenType::Class
+                | TokenType::Interface
+                | TokenType::Struct
+                | TokenType::Record
+                | TokenType::Enum
+                | TokenType::Pub
+                | TokenType::Export => {
+                    match self.parse_declaration() {
+                        Ok(declaration) => items.push(declaration),
+                        Err(diagnostic) => {
+                            self.report(*diagnostic);
+                            self.synchronize_top_level();
+                        }
+                    }
+                }
+                _ => {
+                    let diagnostic = self
+                        .error_here("E0102", "an item")
+                        .with_note("a source file contains `import`s and `service`, `class`, `interface`, `struct`, `enum`, `fn` and `const` declarations");
+                    self.report(diagnostic);
+                    if self.check(&TokenType::RightBrace) {
+                        self.advance();
+                    } else {
+                        self.synchronize_top_level();
+                    }
+                }
+            }
+        }
 
-        Ok(ASTNode::Endpoint {
-            path,
-            method,
-            params,
-            return_type,
-            body: Box::new(body),
-        })
-    }
+        if let Some(name) = &module {
+            if self.check(&TokenType::EOF) {
+                let diagnostic = self
+                    .error_here("E0100", "`}`")
+                    .with_note(format!("module `{}` is never closed", name));
+                self.report(diagnostic);
+            } else {
+                self.advance();
+                if !self.check(&TokenType::EOF) {
+                    let diagnostic = self
+                        .error_here("E0100", "end of file")
+                        .with_note("a module must contain every item in its file");
+                    self.report(diagnostic);
+                }
+            }
+        }
 
-    fn parse_block(&mut self) -> Result<ASTNode, String> {
-        let mut statements = Vec::new();
-        
-        while let TokenType::Return = self.current_token.token_type {
-            statements.push(self.parse_return_statement()?);
+        ASTNode::Program {
+            module: module.filter(|name| !name.is_empty()),
+            items,
+            span: self.span_from(start),
         }
+    }
 
-        Ok(ASTNode::Block { statements })
+    fn parse_module_header(&mut self) -> ParseResult<String> {
+        self.expect(TokenType::Module)?;
+        let name = self.expect_identifier("module name")?;
+        self.expect(TokenType::LeftBrace)?;
+        Ok(name)
     }
 
-    fn parse_return_statement(&mut self) -> Result<ASTNode, String> {
-        self.expect(TokenType::Return)?;
-        
-        let expr = match &self.current_token.token_type.clone() {
-            TokenType::StringLiteral(s) => {
-                let value = s.clone();
-                self.advance();
-                ASTNode::StringLiteral { value }
+    /// Parse a service, recovering from errors in individual endpoints and
+    /// statements. Returns `None` if the service header itself is unusable;
+    /// all problems are reported through `take_diagnostics`.
+    pub fn parse_service(&mut self) -> Option<ASTNode> {
+        let start = self.current_token.span;
+        let doc = self.take_docs();
+        let (base_path, name) = match self.parse_service_header() {
+            Ok(header) => header,
+            Err(diagnostic) => {
+                self.report(*diagnostic);
+                return None;
             }
-            TokenType::Identifier(name) => {
-                let name = name.clone();
-                self.advance();
-                ASTNode::Identifier { name }
-            }
-            _ => return Err("Expected expression after return".to_string()),
         };
 
-        self.expect(TokenType::Semicolon)?;
+        let mut endpoints = Vec::new();
+        let mut functions = Vec::new();
+        let mut constants = Vec::new();
+        loop {
+            match self.current_token.token_type {
+                TokenType::RightBrace => {
+                    self.advance();
+                    break;
+                }
+                TokenType::EOF => {
+                    let diagnostic = self
+                        .error_here("E0100", "`}`")
+                        .with_note(format!("service `{}` is never closed", name));
+                    self.report(diagnostic);
+                    break;
+                }
+                TokenType::At => match self.parse_endpoint() {
+                    Ok(endpoint) => endpoints.push(endpoint),
+                    Err(diagnostic) => {
+                        self.report(*diagnostic);
+                        self.synchronize_item();
+                    }
+                },
+                TokenType::Fn
+                | TokenType::Async
+                | TokenType::Const
+                | TokenType::Class
+                | TokenType::Interface
+                | TokenType::Struct
+                | TokenType::Record
+                | TokenType::Enum
+                | TokenType::Pub => match self.parse_declaration() {
+                    Ok(function @ ASTNode::Function { .. }) => functions.push(function),
+                    Ok(constant @ ASTNode::Constant { .. }) => constants.push(constant),
+                    Ok(other) => {
+                        let kind = match other {
+                            ASTNode::Interface { .. } => "interface",
+                            ASTNode::Struct { .. } => "struct",
+                            ASTNode::Enum { .. } => "enum",
+                            _ => "class",
+                        };
+                        self.report(
+                            Diagnostic::error(
+                                "E0109",
+                                format!("{}s must be declared at the top level", kind),
+                                Label::new(other.span()).with_message("declared inside a service"),
+                            )
+                            .with_suggestion(format!("move the {} out of the service", kind), None),
+                        )
+                    }
+                    Err(diagnostic) => {
+                        self.report(*diagnostic);
+                        self.synchronize_item();
+                    }
+                },
+                _ => {
+                    let diagnostic = self
+                        .error_here("E0102", "an endpoint or function")
+                        .with_note("endpoints start with an HTTP method decorator such as `@get(\"/path\")`");
+                    self.report(diagnostic);
+                    self.synchronize_item();
+                }
+            }
+        }
+
+        Some(ASTNode::Service {
+            name,
+            base_path,
+            endpoints,
+            functions,
+            constants,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `[@base("/path")] service Name {`
+    fn parse_service_header(&mut self) -> ParseResult<(Option<String>, String)> {
+        let base_path = if self.check(&TokenType::At) {
+            let decorator_start = self.current_token.span;
+            self.advance();
+            let decorator = self.expect_identifier("decorator name")?;
+            if decorator != "base" {
+                return Err(Box::new(
+                    Diagnostic::error(
+                        "E0108",
+                        format!("unknown service decorator `@{}`", decorator),
+                        Label::new(self.span_from(decorator_start)).with_message("not valid on a service"),
+                    )
+                    .with_note("endpoints must be declared inside a service")
+                    .with_suggestion("set a base path for the service's endpoints", Some("@base(\"/path\")".to_string())),
+                ));
+            }
+            Some(self.parse_path_argument()?)
+        } else {
+            None
+        };
+
+        self.expect(TokenType::Service)?;
+        let name = self.expect_identifier("service name")?;
+        self.expect(TokenType::LeftBrace)?;
+        Ok((base_path, name))
+    }
+
+    /// The `("/path")` argument of a route decorator.
+    fn parse_path_argument(&mut self) -> ParseResult<String> {
+        self.expect(TokenType::LeftParen)?;
+        let path = match &self.current_token.token_type {
+            TokenType::StringLiteral(path) => path.clone(),
+            _ => return Err(self.error_here("E0101", "path string")),
+        };
+        self.advance();
+        self.expect(TokenType::RightParen)?;
+        Ok(path)
+    }
+
+    fn parse_endpoint(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let doc = self.take_docs();
+
+        // Parse @get decorator
+        self.expect(TokenType::At)?;
+        let method = self.expect_identifier("HTTP method")?.to_lowercase();
+
+        // Parse path
+        let path = self.parse_path_argument()?;
+
+        // Parse endpoint keyword and name
+        self.expect(TokenType::Endpoint)?;
+        let _name = self.expect_identifier("endpoint name")?;
+
+        let params = self.parse_parameters()?;
+
+        // Parse return type
+        self.expect(TokenType::Colon)?;
+        let return_type = self.parse_type()?;
+
+        // Parse body
+        let body = self.parse_block()?;
+
+        Ok(ASTNode::Endpoint {
+            path,
+            method,
+            params,
+            return_type,
+            body: Box::new(body),
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    /// A function, constant, class, interface, struct or enum, optionally
+    /// marked `pub`.
+    fn parse_declaration(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let doc = self.take_docs();
+        // `export` is an alias of `pub`: either makes the item importable
+        let public = self.check(&TokenType::Pub) || self.check(&TokenType::Export);
+        if public {
+            self.advance();
+        }
+        match self.current_token.token_type {
+            TokenType::Fn | TokenType::Async => self.parse_function(start, doc, public),
+            TokenType::Const => self.parse_constant(start, doc, public),
+            TokenType::Class => self.parse_class(start, doc, public),
+            TokenType::Interface => self.parse_interface(start, doc, public),
+            TokenType::Struct | TokenType::Record => self.parse_struct(start, doc, public),
+            TokenType::Enum => self.parse_enum(start, doc, public),
+            _ => Err(self.error_here("E0100", "`fn`, `const`, `class`, `interface`, `struct` or `enum`")),
+        }
+    }
+
+    /// `import { a, b } from "path";`
+    fn parse_import(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.pending_docs.clear();
+        self.expect(TokenType::Import)?;
+        self.expect(TokenType::LeftBrace)?;
+
+        // At least one name, with an optional trailing comma
+        let mut names = Vec::new();
+        loop {
+            let span = self.current_token.span;
+            let name = self.expect_identifier("imported name")?;
+            names.push(ImportedName { name, span });
+            if !self.check(&TokenType::Comma) {
+                break;
+            }
+            self.advance();
+            if self.check(&TokenType::RightBrace) {
+                break;
+            }
+        }
+        self.expect(TokenType::RightBrace)?;
+
+        if !matches!(&self.current_token.token_type, TokenType::Identifier(word) if word == "from") {
+            return Err(self.error_here("E0100", "`from`"));
+        }
+        self.advance();
+        let path_span = self.current_token.span;
+        let path = match &self.current_token.token_type {
+            TokenType::StringLiteral(path) => path.clone(),
+            _ => return Err(self.error_here("E0101", "module path string")),
+        };
+        self.advance();
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(ASTNode::Import {
+            names,
+            path,
+            path_span,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `interface Name { fn method(params) [: ReturnType]; ... }`
+    fn parse_interface(&mut self, start: Span, doc: Option<String>, public: bool) -> ParseResult<ASTNode> {
+        self.expect(TokenType::Interface)?;
+        let name = self.expect_identifier("interface name")?;
+        self.expect(TokenType::LeftBrace)?;
+
+        let mut methods = Vec::new();
+        loop {
+            match self.current_token.token_type {
+                TokenType::RightBrace => {
+                    self.advance();
+                    break;
+                }
+                TokenType::EOF => {
+                    let diagnostic = self
+                        .error_here("E0100", "`}`")
+                        .with_note(format!("interface `{}` is never closed", name));
+                    self.report(diagnostic);
+                    break;
+                }
+                _ => match self.parse_method_signature() {
+                    Ok(signature) => methods.push(signature),
+                    Err(diagnostic) => {
+                        self.report(*diagnostic);
+                        self.synchronize_signature();
+                    }
+                },
+            }
+        }
+
+        Ok(ASTNode::Interface {
+            name,
+            methods,
+            public,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_method_signature(&mut self) -> ParseResult<MethodSignature> {
+        let start = self.current_token.span;
+        self.pending_docs.clear();
+        if !self.check(&TokenType::Fn) {
+            return Err(self
+                .error_here("E0102", "a method signature")
+                .with_note("interfaces list methods as `fn name(params): Type;` without a body")
+                .into());
+        }
+        self.advance();
+        let name = self.expect_identifier("method name")?;
+        let params = self.parse_parameters()?;
+        let return_type = if self.check(&TokenType::Colon) {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(MethodSignature {
+            name,
+            params,
+            return_type,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `struct Name[<T, ...>] { name: Type [= default]; ... }`, with `record` as an
+    /// alias of `struct`.
+    fn parse_struct(&mut self, start: Span, doc: Option<String>, public: bool) -> ParseResult<ASTNode> {
+        self.advance();
+        let name = self.expect_identifier("struct name")?;
+        let type_params = self.parse_type_parameters()?;
+        self.expect(TokenType::LeftBrace)?;
+
+        let mut fields = Vec::new();
+        loop {
+            match self.current_token.token_type {
+                TokenType::RightBrace => {
+                    self.advance();
+                    break;
+                }
+                TokenType::EOF => {
+                    let diagnostic = self
+                        .error_here("E0100", "`}`")
+                        .with_note(format!("struct `{}` is never closed", name));
+                    self.report(diagnostic);
+                    break;
+                }
+                _ => {
+                    let field_start = self.current_token.span;
+                    self.pending_docs.clear();
+                    let field = self
+                        .expect_identifier("field name")
+                        .and_then(|field_name| self.parse_field(field_start, field_name));
+                    match field {
+                        Ok(field) => fields.push(field),
+                        Err(diagnostic) => {
+                            self.report(*diagnostic);
+                            self.synchronize_statement();
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ASTNode::Struct {
+            name,
+            type_params,
+            fields,
+            public,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `enum Name[<T, ...>] { Variant, Variant(field: Type, ...), ... }`
+    fn parse_enum(&mut self, start: Span, doc: Option<String>, public: bool) -> ParseResult<ASTNode> {
+        self.expect(TokenType::Enum)?;
+        let name = self.expect_identifier("enum name")?;
+        let type_params = self.parse_type_parameters()?;
+        self.expect(TokenType::LeftBrace)?;
+        let variants = self.parse_comma_separated(TokenType::RightBrace, |parser| {
+            let start = parser.current_token.span;
+            parser.pending_docs.clear();
+            let name = parser.expect_identifier("variant name")?;
+            let fields = if parser.check(&TokenType::LeftParen) {
+                parser.parse_parameters()?
+            } else {
+                Vec::new()
+            };
+            Ok(EnumVariant {
+                name,
+                fields,
+                span: parser.span_from(start),
+            })
+        })?;
+
+        Ok(ASTNode::Enum {
+            name,
+            type_params,
+            variants,
+            public,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `class Name[<T, ...>] [extends Base] { fields, init, methods }`, recovering
+    /// from errors in individual members.
+    fn parse_class(&mut self, start: Span, doc: Option<String>, public: bool) -> ParseResult<ASTNode> {
+        self.expect(TokenType::Class)?;
+        let name = self.expect_identifier("class name")?;
+        let type_params = self.parse_type_parameters()?;
+        let superclass = if self.check(&TokenType::Extends) {
+            self.advance();
+            Some(self.expect_identifier("superclass name")?)
+        } else {
+            None
+        };
+        let mut interfaces = Vec::new();
+        if self.check(&TokenType::Implements) {
+            self.advance();
+            loop {
+                interfaces.push(self.expect_identifier("interface name")?);
+                if !self.check(&TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        self.expect(TokenType::LeftBrace)?;
+
+        let mut fields = Vec::new();
+        let mut constructor: Option<Box<ASTNode>> = None;
+        let mut methods = Vec::new();
+        let mut static_methods = Vec::new();
+        loop {
+            let member_start = self.current_token.span;
+            let member = match self.current_token.token_type {
+                TokenType::RightBrace => {
+                    self.advance();
+                    break;
+                }
+                TokenType::EOF => {
+                    let diagnostic = self
+                        .error_here("E0100", "`}`")
+                        .with_note(format!("class `{}` is never closed", name));
+                    self.report(diagnostic);
+                    break;
+                }
+                TokenType::Fn | TokenType::Async => {
+                    let doc = self.take_docs();
+                    self.parse_function(member_start, doc, true).map(|method| methods.push(method))
+                }
+                TokenType::Static => {
+                    let doc = self.take_docs();
+                    self.advance();
+                    self.parse_function(member_start, doc, true).map(|method| static_methods.push(method))
+                }
+                TokenType::Identifier(_) => match self.parse_field_or_constructor() {
+                    Ok(ClassMember::Field(field)) => {
+                        fields.push(field);
+                        Ok(())
+                    }
+                    Ok(ClassMember::Constructor(init)) => {
+                        // The duplicate parsed fine, so report it without skipping ahead
+                        match &constructor {
+                            Some(first) => self.report(
+                                Diagnostic::error(
+                                    "E0110",
+                                    format!("class `{}` declares more than one constructor", name),
+                                    Label::new(init.span()).with_message("second constructor"),
+                                )
+                                .with_secondary(Label::new(first.span()).with_message("first constructor declared here")),
+                            ),
+                            None => constructor = Some(Box::new(init)),
+                        }
+                        Ok(())
+                    }
+                    Err(diagnostic) => Err(diagnostic),
+                },
+                _ => {
+                    let diagnostic = self
+                        .error_here("E0102", "a field, constructor or method")
+                        .with_note("class members are `name: Type;` fields, `init(...)` and `fn` or `static fn` methods");
+                    self.report(diagnostic);
+                    // Resume at the next token that can start a member
+                    while !matches!(
+                        self.current_token.token_type,
+                        TokenType::Identifier(_)
+                            | TokenType::Fn
+                            | TokenType::Async
+                            | TokenType::Static
+                            | TokenType::RightBrace
+                            | TokenType::EOF
+                    ) {
+                        self.advance();
+                    }
+                    Ok(())
+                }
+            };
+            if let Err(diagnostic) = member {
+                self.report(*diagnostic);
+                self.synchronize_item();
+            }
+        }
+
+        Ok(ASTNode::Class {
+            name,
+            type_params,
+            superclass,
+            interfaces,
+            fields,
+            constructor,
+            methods,
+            static_methods,
+            public,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `name: Type [= default];` or `init(params) { ... }`.
+    fn parse_field_or_constructor(&mut self) -> ParseResult<ClassMember> {
+        let start = self.current_token.span;
+        let doc = self.take_docs();
+        let name = self.expect_identifier("field name")?;
+
+        if name == "init" && self.check(&TokenType::LeftParen) {
+            let params = self.parse_parameters()?;
+            let body = self.parse_function_body()?;
+            return Ok(ClassMember::Constructor(ASTNode::Function {
+                name,
+                type_params: Vec::new(),
+                params,
+                return_type: None,
+                body: Box::new(body),
+                is_async: false,
+                public: true,
+                doc,
+                span: self.span_from(start),
+            }));
+        }
+
+        self.parse_field(start, name).map(ClassMember::Field)
+    }
+
+    /// The `: Type [= default];` following a field's name.
+    fn parse_field(&mut self, start: Span, name: String) -> ParseResult<Field> {
+        self.expect(TokenType::Colon)?;
+        let field_type = self.parse_type()?;
+        let default = if self.check(&TokenType::Equal) {
+            self.advance();
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(Field {
+            name,
+            field_type,
+            default,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `const NAME[: Type] = value;`
+    fn parse_constant(&mut self, start: Span, doc: Option<String>, public: bool) -> ParseResult<ASTNode> {
+        self.expect(TokenType::Const)?;
+        let name = self.expect_identifier("constant name")?;
+        let type_annotation = if self.check(&TokenType::Colon) {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        self.expect(TokenType::Equal)?;
+        let value = self.parse_expression()?;
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(ASTNode::Constant {
+            name,
+            type_annotation,
+            value: Box::new(value),
+            public,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `[async] fn name[<T, ...>](params) [: ReturnType] { ... }`
+    fn parse_function(&mut self, start: Span, doc: Option<String>, public: bool) -> ParseResult<ASTNode> {
+        let is_async = self.check(&TokenType::Async);
+        if is_async {
+            self.advance();
+        }
+        self.expect(TokenType::Fn)?;
+        let name = self.expect_identifier("function name")?;
+        let type_params = self.parse_type_parameters()?;
+        let params = self.parse_parameters()?;
+
+        let return_type = if self.check(&TokenType::Colon) {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        let body = self.parse_function_body()?;
+
+        Ok(ASTNode::Function {
+            name,
+            type_params,
+            params,
+            return_type,
+            body: Box::new(body),
+            is_async,
+            public,
+            doc,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_function_body(&mut self) -> ParseResult<ASTNode> {
+        // `break` and `continue` never reach through a function boundary
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block();
+        self.loop_depth = loop_depth;
+        body
+    }
+
+    /// A parenthesised, comma-separated list of `name: Type` parameters.
+    fn parse_parameters(&mut self) -> ParseResult<Vec<Parameter>> {
+        self.expect(TokenType::LeftParen)?;
+        let mut params = Vec::new();
+        while let TokenType::Identifier(_) = &self.current_token.token_type {
+            let param_start = self.current_token.span;
+            let param_name = self.expect_identifier("parameter name")?;
+            self.expect(TokenType::Colon)?;
+            let param_type = self.parse_type()?;
+
+            params.push(Parameter {
+                name: param_name,
+                param_type,
+                span: self.span_from(param_start),
+            });
+
+            if self.check(&TokenType::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenType::RightParen)?;
+        Ok(params)
+    }
+
+    pub fn parse_block(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::LeftBrace)?;
+        let mut statements = Vec::new();
+
+        loop {
+            match self.current_token.token_type {
+                TokenType::RightBrace | TokenType::EOF => break,
+                _ => match self.parse_statement() {
+                    Ok(statement) => statements.push(statement),
+                    Err(diagnostic) => {
+                        self.report(*diagnostic);
+                        self.synchronize_statement();
+                    }
+                },
+            }
+        }
+
+        self.expect(TokenType::RightBrace)?;
+        Ok(ASTNode::Block {
+            statements,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_statement(&mut self) -> ParseResult<ASTNode> {
+        // Statements are not documentable
+        self.pending_docs.clear();
+        match self.current_token.token_type {
+            TokenType::Return => self.parse_return_statement(),
+            TokenType::Let | TokenType::Var => self.parse_variable_declaration(),
+            TokenType::If => self.parse_if_statement(),
+            TokenType::While => self.parse_while_statement(),
+            TokenType::For => self.parse_for_statement(),
+            TokenType::Match => self.parse_match(true),
+            TokenType::Try => self.parse_try_statement(),
+            TokenType::Defer => self.parse_defer_statement(),
+            TokenType::Break | TokenType::Continue => self.parse_loop_control(),
+            // A `{` at the start of a statement opens a nested block, not an object literal
+            TokenType::LeftBrace => self.parse_block(),
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    /// `Name`, or `Name<Argument, ...>`, either followed by an optional `?`.
+    pub fn parse_type(&mut self) -> ParseResult<TypeExpr> {
+        let start = self.current_token.span;
+        let name = self.expect_identifier("a type")?;
+        let mut arguments = Vec::new();
+        if self.check(&TokenType::Less) {
+            self.advance();
+            loop {
+                arguments.push(self.parse_type()?);
+                if !self.check(&TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+            self.expect(TokenType::Greater)?;
+        }
+        let optional = self.check(&TokenType::Question);
+        if optional {
+            self.advance();
+        }
+        Ok(TypeExpr {
+            name,
+            arguments,
+            optional,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `<T, U: Bound + Bound>` after the name of a generic declaration, if
+    /// present.
+    fn parse_type_parameters(&mut self) -> ParseResult<Vec<TypeParameter>> {
+        if !self.check(&TokenType::Less) {
+            return Ok(Vec::new());
+        }
+        self.advance();
+        self.parse_comma_separated(TokenType::Greater, |parser| {
+            let start = parser.current_token.span;
+            let name = parser.expect_identifier("type parameter name")?;
+            let mut bounds = Vec::new();
+            if parser.check(&TokenType::Colon) {
+                parser.advance();
+                loop {
+                    bounds.push(parser.parse_type()?);
+                    if !parser.check(&TokenType::Plus) {
+                        break;
+                    }
+                    parser.advance();
+                }
+            }
+            Ok(TypeParameter {
+                name,
+                bounds,
+                span: parser.span_from(start),
+            })
+        })
+    }
+
+    fn parse_variable_declaration(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let mutable = self.check(&TokenType::Var);
+        self.advance();
+
+        let name = self.expect_identifier("variable name")?;
+        let type_annotation = if self.check(&TokenType::Colon) {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        let initializer = if self.check(&TokenType::Equal) {
+            self.advance();
+            Some(Box::new(self.parse_expression()?))
+        } else if !mutable {
+            return Err(Box::new(
+                self.error_here("E0100", "`=`")
+                    .with_note(format!("`let` bindings must be initialized; use `var {}` to assign later", name)),
+            ));
+        } else {
+            None
+        };
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(ASTNode::VariableDeclaration {
+            name,
+            mutable,
+            type_annotation,
+            initializer,
+            span: self.span_from(start),
+        })
+    }
+
+    /// An expression followed by `;`, or an assignment when the expression is
+    /// followed by `=` or a compound operator such as `+=`.
+    fn parse_expression_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let expression = self.parse_expression()?;
+
+        let operator = match self.current_token.token_type {
+            TokenType::Equal => Some(None),
+            TokenType::PlusEqual => Some(Some(BinaryOperator::Add)),
+            TokenType::MinusEqual => Some(Some(BinaryOperator::Subtract)),
+            TokenType::StarEqual => Some(Some(BinaryOperator::Multiply)),
+            TokenType::SlashEqual => Some(Some(BinaryOperator::Divide)),
+            TokenType::PercentEqual => Some(Some(BinaryOperator::Modulo)),
+            _ => None,
+        };
+
+        let Some(operator) = operator else {
+            self.expect(TokenType::Semicolon)?;
+            return Ok(ASTNode::ExpressionStatement {
+                expression: Box::new(expression),
+                span: self.span_from(start),
+            });
+        };
+
+        if !matches!(
+            expression,
+            ASTNode::Identifier { .. } | ASTNode::MemberExpression { .. } | ASTNode::IndexExpression { .. }
+        ) {
+            return Err(Box::new(
+                Diagnostic::error(
+                    "E0105",
+                    "invalid assignment target",
+                    Label::new(expression.span()).with_message("cannot assign to this expression"),
+                )
+                .with_note("only variables, fields and indexed elements can be assigned"),
+            ));
+        }
+        self.advance();
+
+        let value = self.parse_expression()?;
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(ASTNode::Assignment {
+            target: Box::new(expression),
+            operator,
+            value: Box::new(value),
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_if_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::If)?;
+        let condition = self.parse_expression_before_block()?;
+        let then_branch = self.parse_block()?;
+
+        let else_branch = if self.check(&TokenType::Else) {
+            self.advance();
+            if self.check(&TokenType::If) {
+                Some(Box::new(self.parse_if_statement()?))
+            } else {
+                Some(Box::new(self.parse_block()?))
+            }
+        } else {
+            None
+        };
+
+        Ok(ASTNode::IfStatement {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_while_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::While)?;
+        let condition = self.parse_expression_before_block()?;
+        let body = self.parse_loop_body()?;
+
+        Ok(ASTNode::WhileStatement {
+            condition: Box::new(condition),
+            body: Box::new(body),
+            span: self.span_from(start),
+        })
+    }
+
+    /// `try { ... }` followed by one or more `catch (name[: Type]) { ... }`.
+    fn parse_try_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::Try)?;
+        let body = self.parse_block()?;
+        let mut catches = Vec::new();
+        while self.check(&TokenType::Catch) {
+            let catch_start = self.current_token.span;
+            self.advance();
+            self.expect(TokenType::LeftParen)?;
+            let name = self.expect_identifier("error name")?;
+            let error_type = if self.check(&TokenType::Colon) {
+                self.advance();
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            self.expect(TokenType::RightParen)?;
+            let body = self.parse_block()?;
+            catches.push(CatchClause {
+                name,
+                error_type,
+                body,
+                span: self.span_from(catch_start),
+            });
+        }
+        if catches.is_empty() {
+            return Err(self
+                .error_here("E0100", "`catch`")
+                .with_note("a `try` block needs at least one `catch` clause")
+                .into());
+        }
+
+        Ok(ASTNode::TryStatement {
+            body: Box::new(body),
+            catches,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `defer statement`. The statement cannot `return`, nor `break` or
+    /// `continue` a loop outside it.
+    fn parse_defer_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::Defer)?;
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let deferring = std::mem::replace(&mut self.deferring, true);
+        let statement = self.parse_statement();
+        self.loop_depth = loop_depth;
+        self.deferring = deferring;
+
+        Ok(ASTNode::DeferStatement {
+            statement: Box::new(statement?),
+            span: self.span_from(start),
+        })
+    }
+
+    fn report_deferred_exit(&mut self, keyword: &TokenType, span: Span) {
+        self.report(
+            Diagnostic::error(
+                "E0112",
+                format!("`{}` in a deferred statement", keyword),
+                Label::new(span).with_message(format!("cannot `{}` out of a deferred statement", keyword)),
+            )
+            .with_note("a deferred statement runs as its block exits, so it cannot leave the block itself"),
+        );
+    }
+
+    fn parse_for_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::For)?;
+        let variable = self.expect_identifier("loop variable")?;
+        self.expect(TokenType::In)?;
+        let iterable = self.parse_expression_before_block()?;
+        let body = self.parse_loop_body()?;
+
+        Ok(ASTNode::ForStatement {
+            variable,
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_loop_body(&mut self) -> ParseResult<ASTNode> {
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// `break;` or `continue;`. Outside a loop the statement is still parsed,
+    /// but reported so it never reaches the runtime.
+    fn parse_loop_control(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let keyword = self.current_token.token_type.clone();
+        self.advance();
+        self.expect(TokenType::Semicolon)?;
+
+        if self.loop_depth == 0 && self.deferring {
+            self.report_deferred_exit(&keyword, start);
+        } else if self.loop_depth == 0 {
+            self.report(
+                Diagnostic::error(
+                    "E0106",
+                    format!("`{}` outside of a loop", keyword),
+                    Label::new(start).with_message(format!("cannot `{}` outside of a loop", keyword)),
+                )
+                .with_note("`break` and `continue` may only be used inside `while` and `for` loops"),
+            );
+        }
+
+        let span = self.span_from(start);
+        Ok(match keyword {
+            TokenType::Break => ASTNode::BreakStatement { span },
+            _ => ASTNode::ContinueStatement { span },
+        })
+    }
+
+    fn parse_return_statement(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::Return)?;
+        let expression = match self.current_token.token_type {
+            TokenType::Semicolon => None,
+            _ => Some(Box::new(self.parse_expression()?)),
+        };
+        self.expect(TokenType::Semicolon)?;
+        if self.deferring {
+            self.report_deferred_exit(&TokenType::Return, start);
+        }
 
         Ok(ASTNode::ReturnStatement {
-            expression: Box::new(expr),
+            expression,
+            span: self.span_from(start),
         })
     }
-} 
\ No newline at end of file
+
+    /// Parse an expression by precedence climbing over the binary operator
+    /// table in `BinaryOperator::from_token`.
+    pub fn parse_expression(&mut self) -> ParseResult<ASTNode> {
+        self.parse_binary(0)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<ASTNode> {
+        let mut left = self.parse_unary()?;
+
+        while let Some((operator, precedence, right_associative)) =
+            BinaryOperator::from_token(&self.current_token.token_type)
+        {
+            if precedence < min_precedence {
+                break;
+            }
+            self.advance();
+
+            let next_min = if right_associative { precedence } else { precedence + 1 };
+            let right = self.parse_binary(next_min)?;
+            let span = left.span().to(right.span());
+            left = ASTNode::BinaryExpression {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+                span,
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> ParseResult<ASTNode> {
+        let operator = match self.current_token.token_type {
+            TokenType::Bang => UnaryOperator::Not,
+            TokenType::Minus => UnaryOperator::Negate,
+            // Like `return`, `throw` takes everything after it
+            TokenType::Throw => {
+                let start = self.current_token.span;
+                self.advance();
+                let error = self.parse_expression()?;
+                return Ok(ASTNode::ThrowExpression {
+                    error: Box::new(error),
+                    span: self.span_from(start),
+                });
+            }
+            // `await` binds like a prefix operator: `await a() + await b()`
+            TokenType::Await => {
+                let start = self.current_token.span;
+                self.advance();
+                let future = self.parse_unary()?;
+                return Ok(ASTNode::AwaitExpression {
+                    future: Box::new(future),
+                    span: self.span_from(start),
+                });
+            }
+            _ => return self.parse_postfix(),
+        };
+        let start = self.current_token.span;
+        self.advance();
+
+        let operand = self.parse_unary()?;
+        Ok(ASTNode::UnaryExpression {
+            operator,
+            operand: Box::new(operand),
+            span: self.span_from(start),
+        })
+    }
+
+    /// Calls, member accesses, indexing and `?`, applied left to right.
+    fn parse_postfix(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let mut expression = self.parse_primary()?;
+
+        loop {
+            match self.current_token.token_type {
+                TokenType::LeftParen => {
+                    self.advance();
+                    let arguments = self.parse_comma_separated(TokenType::RightParen, Self::parse_expression)?;
+                    expression = ASTNode::CallExpression {
+                        callee: Box::new(expression),
+                        arguments,
+                        span: self.span_from(start),
+                    };
+                }
+                TokenType::Dot => {
+                    self.advance();
+                    let member = self.expect_identifier("member name after `.`")?;
+                    expression = ASTNode::MemberExpression {
+                        object: Box::new(expression),
+                        member,
+                        span: self.span_from(start),
+                    };
+                }
+                TokenType::LeftBracket => {
+                    self.advance();
+                    let index = self.parse_expression()?;
+                    self.expect(TokenType::RightBracket)?;
+                    expression = ASTNode::IndexExpression {
+                        object: Box::new(expression),
+                        index: Box::new(index),
+                        span: self.span_from(start),
+                    };
+                }
+                TokenType::Question => {
+                    self.advance();
+                    expression = ASTNode::PropagateExpression {
+                        expression: Box::new(expression),
+                        span: self.span_from(start),
+                    };
+                }
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// `match subject { pattern => arm, ... }`. Arms are separated by commas,
+    /// which may be left out after a block. Only a `match` used as a
+    /// statement may have blocks as arms.
+    fn parse_match(&mut self, statement: bool) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        self.expect(TokenType::Match)?;
+        let subject = self.parse_expression_before_block()?;
+        self.expect(TokenType::LeftBrace)?;
+
+        let mut arms = Vec::new();
+        while !matches!(self.current_token.token_type, TokenType::RightBrace | TokenType::EOF) {
+            let arm = match self.parse_match_arm(statement) {
+                Ok(arm) => arm,
+                Err(diagnostic) => {
+                    self.report(*diagnostic);
+                    self.synchronize_arm();
+                    continue;
+                }
+            };
+            let is_block = matches!(arm.body, ASTNode::Block { .. });
+            arms.push(arm);
+            if self.check(&TokenType::Comma) {
+                self.advance();
+            } else if !is_block {
+                break;
+            }
+        }
+        self.expect(TokenType::RightBrace)?;
+
+        Ok(ASTNode::Match {
+            subject: Box::new(subject),
+            arms,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_match_arm(&mut self, statement: bool) -> ParseResult<MatchArm> {
+        let start = self.current_token.span;
+        let pattern = self.parse_pattern()?;
+        self.expect(TokenType::FatArrow)?;
+        let body = if statement && self.check(&TokenType::LeftBrace) {
+            self.parse_block()?
+        } else {
+            self.parse_expression()?
+        };
+        Ok(MatchArm {
+            pattern,
+            body,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let start = self.current_token.span;
+        let literal = match &self.current_token.token_type {
+            TokenType::NumberLiteral(value) => Some(ASTNode::NumberLiteral { value: *value, is_float: false, span: start }),
+            TokenType::FloatLiteral(value) => Some(ASTNode::NumberLiteral { value: *value, is_float: true, span: start }),
+            TokenType::StringLiteral(value) => Some(ASTNode::StringLiteral { value: value.clone(), span: start }),
+            TokenType::True => Some(ASTNode::BooleanLiteral { value: true, span: start }),
+            TokenType::False => Some(ASTNode::BooleanLiteral { value: false, span: start }),
+            TokenType::Null => Some(ASTNode::NullLiteral { span: start }),
+            TokenType::Minus => {
+                self.advance();
+                match self.current_token.token_type {
+                    TokenType::NumberLiteral(value) | TokenType::FloatLiteral(value) => Some(ASTNode::NumberLiteral {
+                        value: -value,
+                        is_float: matches!(self.current_token.token_type, TokenType::FloatLiteral(_)),
+                        span: self.span_from(start),
+                    }),
+                    _ => return Err(self.error_here("E0111", "a number")),
+                }
+            }
+            _ => None,
+        };
+        if let Some(value) = literal {
+            self.advance();
+            return Ok(Pattern::Literal {
+                value,
+                span: self.span_from(start),
+            });
+        }
+
+        let name = match &self.current_token.token_type {
+            TokenType::Identifier(name) => name.clone(),
+            _ => {
+                return Err(self
+                    .error_here("E0111", "a pattern")
+                    .with_note("patterns are literals, `_`, names to bind, or `Enum.Variant(...)`")
+                    .into())
+            }
+        };
+        self.advance();
+        if !self.check(&TokenType::Dot) {
+            return Ok(if name == "_" {
+                Pattern::Wildcard { span: start }
+            } else {
+                Pattern::Binding { name, span: start }
+            });
+        }
+        self.advance();
+        let variant = self.expect_identifier("variant name")?;
+        let fields = if self.check(&TokenType::LeftParen) {
+            self.advance();
+            self.parse_comma_separated(TokenType::RightParen, Self::parse_pattern)?
+        } else {
+            Vec::new()
+        };
+        Ok(Pattern::Variant {
+            enum_name: name,
+            variant,
+            fields,
+            span: self.span_from(start),
+        })
+    }
+
+    /// An expression followed by a block, in which `Name {` is not taken
+    /// as the start of a struct literal.
+    fn parse_expression_before_block(&mut self) -> ParseResult<ASTNode> {
+        self.with_struct_literals(false, Self::parse_expression)
+    }
+
+    fn with_struct_literals<T>(&mut self, allowed: bool, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        let outer = std::mem::replace(&mut self.struct_literals, allowed);
+        let result = parse(self);
+        self.struct_literals = outer;
+        result
+    }
+
+    fn parse_primary(&mut self) -> ParseResult<ASTNode> {
+        let span = self.current_token.span;
+        let literal = match &self.current_token.token_type {
+            TokenType::StringLiteral(value) => Some(ASTNode::StringLiteral { value: value.clone(), span }),
+            TokenType::NumberLiteral(value) => Some(ASTNode::NumberLiteral { value: *value, is_float: false, span }),
+            TokenType::FloatLiteral(value) => Some(ASTNode::NumberLiteral { value: *value, is_float: true, span }),
+            TokenType::True => Some(ASTNode::BooleanLiteral { value: true, span }),
+            TokenType::False => Some(ASTNode::BooleanLiteral { value: false, span }),
+            TokenType::Null => Some(ASTNode::NullLiteral { span }),
+            TokenType::Identifier(name) => Some(ASTNode::Identifier { name: name.clone(), span }),
+            TokenType::Super => Some(ASTNode::SuperExpression { span }),
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            self.advance();
+            return match literal {
+                ASTNode::Identifier { name, .. } if self.struct_literals && self.check(&TokenType::LeftBrace) => {
+                    self.advance();
+                    let fields = self.parse_comma_separated(TokenType::RightBrace, Self::parse_object_field)?;
+                    Ok(ASTNode::StructLiteral {
+                        name,
+                        fields,
+                        span: self.span_from(span),
+                    })
+                }
+                literal => Ok(literal),
+            };
+        }
+
+        match self.current_token.token_type {
+            TokenType::LeftParen => {
+                self.advance();
+                let expression = self.with_struct_literals(true, Self::parse_expression)?;
+                self.expect(TokenType::RightParen)?;
+                Ok(ASTNode::GroupingExpression {
+                    expression: Box::new(expression),
+                    span: self.span_from(span),
+                })
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+                let elements = self.parse_comma_separated(TokenType::RightBracket, Self::parse_expression)?;
+                Ok(ASTNode::ArrayLiteral {
+                    elements,
+                    span: self.span_from(span),
+                })
+            }
+            TokenType::Match => self.parse_match(false),
+            TokenType::LeftBrace => {
+                self.advance();
+                let fields = self.parse_comma_separated(TokenType::RightBrace, Self::parse_object_field)?;
+                Ok(ASTNode::ObjectLiteral {
+                    fields,
+                    span: self.span_from(span),
+                })
+            }
+            TokenType::StringHead(_) => self.parse_interpolated_string(),
+            _ => Err(self.error_here("E0104", "an expression")),
+        }
+    }
+
+    /// A string literal with `\(...)` interpolations, which the lexer hands
+    /// over as pieces of text around the tokens of each expression.
+    fn parse_interpolated_string(&mut self) -> ParseResult<ASTNode> {
+        let start = self.current_token.span;
+        let mut parts = Vec::new();
+        let mut text = match &self.current_token.token_type {
+            TokenType::StringHead(text) => text.clone(),
+            _ => return Err(self.error_here("E0104", "a string")),
+        };
+        loop {
+            self.advance();
+            if !text.is_empty() {
+                parts.push(StringPart::Text(text));
+            }
+            let expression = self.with_struct_literals(true, Self::parse_expression)?;
+            parts.push(StringPart::Expression(Box::new(expression)));
+            text = match &self.current_token.token_type {
+                TokenType::StringMiddle(text) => text.clone(),
+                TokenType::StringTail(text) => {
+                    let text = text.clone();
+                    self.advance();
+                    if !text.is_empty() {
+                        parts.push(StringPart::Text(text));
+                    }
+                    return Ok(ASTNode::InterpolatedString {
+                        parts,
+                        span: self.span_from(start),
+                    });
+                }
+                _ => {
+                    return Err(self
+                        .error_here("E0100", "`)`")
+                        .with_note("an interpolation holds a single expression")
+                        .into())
+                }
+            };
+        }
+    }
+
+    fn parse_object_field(&mut self) -> ParseResult<(String, ASTNode)> {
+        let key = match &self.current_token.token_type {
+            TokenType::Identifier(key) | TokenType::StringLiteral(key) => key.clone(),
+            _ => return Err(self.error_here("E0101", "a field name")),
+        };
+        self.advance();
+        self.expect(TokenType::Colon)?;
+        let value = self.parse_expression()?;
+        Ok((key, value))
+    }
+
+    /// Parse `item, item, ...` up to and including `close`, allowing a
+    /// trailing comma. The opening delimiter must already be consumed.
+    /// Struct literals are allowed again inside the delimiters.
+    fn parse_comma_separated<T>(
+        &mut self,
+        close: TokenType,
+        mut parse_item: impl FnMut(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        self.with_struct_literals(true, |parser| {
+            let mut items = Vec::new();
+            while !parser.check(&close) {
+                items.push(parse_item(parser)?);
+                if parser.check(&TokenType::Comma) {
+                    parser.advance();
+                } else {
+                    break;
+                }
+            }
+            parser.expect(close)?;
+            Ok(items)
+        })
+    }
+}
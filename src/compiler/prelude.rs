@@ -0,0 +1,42 @@
+use std::sync::OnceLock;
+
+use super::parser::{ASTNode, Parser};
+
+/// The file id of spans in the prelude. Every `SourceMap` resolves it to
+/// `SOURCE`, so diagnostics may point into the prelude like any file.
+pub const FILE_ID: usize = usize::MAX;
+
+/// The name the prelude is reported under.
+pub const FILE_NAME: &str = "<prelude>";
+
+/// Declarations every file can use without importing them.
+pub const SOURCE: &str = r#"
+pub class Error {
+    message: String;
+    stack: List<String> = [];
+
+    init(message: String) {
+        self.message = message;
+    }
+}
+
+pub enum Result<T, E> {
+    Ok(value: T),
+    Err(error: E),
+}
+
+pub enum Option<T> {
+    Some(value: T),
+    None,
+}
+"#;
+
+/// The top-level declarations of the prelude, parsed on first use.
+pub fn items() -> &'static [ASTNode] {
+    static ITEMS: OnceLock<Vec<ASTNode>> = OnceLock::new();
+    ITEMS.get_or_init(|| match Parser::with_file_id(SOURCE, FILE_ID).parse_program() {
+        ASTNode::Program { items, .. } => items,
+        other => vec![other],
+    })
+}
+
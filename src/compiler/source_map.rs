@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use super::prelude;
+
+/// A half-open byte range `start..end` within one file of a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file_id: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file_id: usize, start: usize, end: usize) -> Self {
+        Span { file_id, start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file_id: self.file_id,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// Whether `other` lies within `self`.
+    pub fn contains(&self, other: Span) -> bool {
+        self.file_id == other.file_id && self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// A resolved position. `line` and `column` are both 1-based; `column`
+/// counts characters, not bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    /// 0-based line index containing the byte `offset`.
+    fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    /// Text of the 1-based `line`, without its line terminator.
+    pub fn line_text(&self, line: usize) -> &str {
+        let Some(&start) = self.line_starts.get(line.wrapping_sub(1)) else {
+            return "";
+        };
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.source.len());
+        self.source[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// Owns the text of every file taking part in a compilation so spans can be
+/// mapped back to file names, lines and columns.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) -> usize {
+        self.files.push(SourceFile::new(name.into(), source.into()));
+        self.files.len() - 1
+    }
+
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<usize> {
+        let source = fs::read_to_string(path.as_ref())?;
+        Ok(self.add_file(path.as_ref().display().to_string(), source))
+    }
+
+    /// The file `file_id` names. The prelude is known to every source map
+    /// without being added.
+    pub fn file(&self, file_id: usize) -> &SourceFile {
+        if file_id == prelude::FILE_ID {
+            static PRELUDE: OnceLock<SourceFile> = OnceLock::new();
+            return PRELUDE.get_or_init(|| SourceFile::new(prelude::FILE_NAME.to_string(), prelude::SOURCE.to_string()));
+        }
+        &self.files[file_id]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (usize, &SourceFile)> {
+        self.files.iter().enumerate()
+    }
+
+    pub fn location(&self, file_id: usize, offset: usize) -> Location<'_> {
+        let file = self.file(file_id);
+        let offset = offset.min(file.source.len());
+        let line = file.line_index(offset);
+        let line_start = file.line_starts[line];
+        Location {
+            file: &file.name,
+            line: line + 1,
+            column: file.source[line_start..offset].chars().count() + 1,
+        }
+    }
+
+    pub fn span_start(&self, span: Span) -> Location<'_> {
+        self.location(span.file_id, span.start)
+    }
+
+    pub fn snippet(&self, span: Span) -> &str {
+        let source = &self.file(span.file_id).source;
+        &source[span.start.min(source.len())..span.end.min(source.len())]
+    }
+}
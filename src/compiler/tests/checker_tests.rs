@@ -0,0 +1,803 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{Compiler, Diagnostic};
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.message.as_str()).collect()
+    }
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        Compiler::new(source.to_string()).compile().err().unwrap_or_default()
+    }
+
+    const SHAPES: &str = r#"
+        interface Shape {
+            fn area(): Float;
+            fn describe(prefix: String): String;
+        }
+
+        interface Named {
+            fn name(): String;
+        }
+    "#;
+
+    #[test]
+    fn test_conforming_classes() {
+        let source = format!(
+            r#"{}
+            class Base implements Named {{
+                fn name(): String {{ return "base"; }}
+            }}
+
+            class Square extends Base implements Shape {{
+                side: Float = 1;
+                fn area(): Float {{ return self.side * self.side; }}
+                fn describe(prefix: String): String {{ return prefix + self.name(); }}
+            }}
+
+            class Factory {{
+                fn make(): Named {{ return Base(); }}
+            }}
+
+            interface Maker {{
+                fn make(): Named;
+            }}
+
+            class BaseFactory implements Maker {{
+                fn make(): Base {{ return Base(); }}
+            }}
+
+            fn total(shape: Shape): Float {{ return shape.area(); }}
+            "#,
+            SHAPES
+        );
+        assert!(check(&source).is_empty(), "{:?}", check(&source));
+    }
+
+    #[test]
+    fn test_missing_method() {
+        let source = format!("{}\nclass Circle implements Shape, Named {{ fn area(): Float {{ return 3; }} }}", SHAPES);
+        let errors = check(&source);
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "class `Circle` is missing method `describe` required by interface `Shape`",
+                "class `Circle` is missing method `name` required by interface `Named`",
+            ]
+        );
+        assert_eq!(errors[0].code, "E0201");
+        assert_eq!(
+            errors[0].suggestions[0].replacement.as_deref(),
+            Some("fn describe(prefix: String): String { ... }")
+        );
+    }
+
+    #[test]
+    fn test_incompatible_signature() {
+        let source = format!(
+            "{}\nclass Bad implements Shape {{ fn area(): String {{ return \"\"; }} fn describe(prefix: Int): String {{ return \"\"; }} }}",
+            SHAPES
+        );
+        let errors = check(&source);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.code == "E0202"));
+        assert_eq!(errors[1].notes, ["expected `fn describe(prefix: String): String`", "   found `fn describe(prefix: Int): String`"]);
+    }
+
+    #[test]
+    fn test_unknown_interface() {
+        let errors = check("class A { } class B implements A, Missing { }");
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["`A` is a class, not an interface", "cannot find interface `Missing`"]);
+    }
+
+    #[test]
+    fn test_unknown_superclass() {
+        let errors = check(&format!(
+            "{} class A extends Missing {{ }} class B extends Shape {{ }} class C extends D {{ }} class D extends C {{ }}",
+            SHAPES
+        ));
+        assert_eq!(
+            messages(&errors),
+            [
+                "cannot find class `Missing`",
+                "`Shape` is an interface, not a class",
+                "class `C` inherits from itself",
+                "class `D` inherits from itself",
+            ]
+        );
+        assert!(errors.iter().all(|e| e.code == "E0217"));
+    }
+
+    #[test]
+    fn test_assignment_to_immutable_names() {
+        let source = r#"
+            class Counter { n: Int = 0; fn bump() { self.n += 1; } }
+            fn f(counter: Counter) {
+                var total = 0;
+                total += 1;
+                counter.n = 2;
+                let shared = Counter();
+                shared.n = 3;
+            }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            const LIMIT = 10;
+            fn f(n: Int, names: List<String>) {
+                let k = 1;
+                k = 2;
+                n += 1;
+                names[0] = "ada";
+                LIMIT = 20;
+                for name in names { name = "grace"; }
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "cannot assign to immutable variable `k`",
+                "cannot assign to immutable variable `n`",
+                "cannot assign to immutable variable `names`",
+                "cannot assign to constant `LIMIT`",
+                "cannot assign to immutable variable `name`",
+            ]
+        );
+        assert_eq!(errors[0].notes, ["only variables declared with `var` can be reassigned"]);
+    }
+
+    #[test]
+    fn test_well_typed_program() {
+        let source = r#"
+            const LIMIT: Float = 10;
+            const NAMES = ["ada", "grace"];
+
+            class Animal {
+                name: String;
+                legs: Int = 4;
+                init(name: String) { self.name = name; }
+                fn speak(): String { return self.name + " says " + self.sound(); }
+                fn sound(): String { return "..."; }
+                static fn kind(): String { return "animal"; }
+            }
+
+            class Dog extends Animal {
+                tricks: Int = 0;
+                init(name: String, tricks: Int) { super.init(name); self.tricks = tricks; }
+                fn speak(): String { return super.speak() + " and knows " + self.tricks + " tricks"; }
+                fn learn() { self.tricks += 1; }
+                static fn kind(): String { return "canine " + super.kind(); }
+            }
+
+            fn describe(animal: Animal): String { return animal.speak(); }
+
+            fn total(values: List<Float>): Float {
+                var sum: Float = 0;
+                for value in values { sum = sum + value; }
+                return sum;
+            }
+
+            service Zoo {
+                const GREETING = "Hi";
+
+                fn shout(s: String): String { return s.toUpper() + "!"; }
+
+                @get("/dog")
+                endpoint dog(name: String, tricks: Int): String {
+                    let dog = Dog(name, tricks);
+                    dog.learn();
+                    if dog.legs > 3 && name.length < LIMIT {
+                        return shout(GREETING + " " + describe(dog));
+                    }
+                    var names = NAMES;
+                    for i in 0..names.length { names = names + [name.substring(i)]; }
+                    return Dog.kind() + names[0];
+                }
+            }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+    }
+
+    #[test]
+    fn test_return_type_mismatch() {
+        let errors = check(
+            r#"
+            fn count(): Int { return "many"; }
+            service S { @get("/x") endpoint x(): String { return 1 + 2; } }
+            fn skip(flag: Bool) { if flag { return; } let kept = 1; }
+            fn nothing(): Int { return; }
+            fn log() { return null; }
+            "#,
+        );
+        let labels: Vec<_> = errors.iter().map(|e| e.primary.message.as_deref().unwrap()).collect();
+        assert_eq!(
+            labels,
+            [
+                "expected `Int`, found `String`",
+                "expected `String`, found `Int`",
+                "expected `Int`, found `Void`",
+                "expected `Void`, found `null`",
+            ]
+        );
+        assert_eq!(errors[0].notes, ["`count` is declared to return `Int`"]);
+        assert_eq!(errors[1].notes, ["endpoint `GET /x` is declared to return `String`"]);
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let source = r#"
+            fn sign(x: Int): Int { if x > 0 { return 1; } else if x < 0 { return -1; } else { return 0; } }
+            fn must(x: Int?): Int { if x == null { throw Error("missing"); } return x; }
+            fn guarded(): String { try { return "ok"; } catch (e) { throw e; } }
+            fn log(message: String) { if message == "" { return; } }
+            fn first(xs: List<Int>): Int { while true { for x in xs { break; } return 0; } }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            fn f(x: Int): Int { if x > 0 { return 1; } }
+            class C { fn name(): String { let n = "c"; } }
+            service S { @get("/x") endpoint x(): String { while true { if true { break; } return "x"; } } }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "`f` may reach its end without returning a value",
+                "`name` may reach its end without returning a value",
+                "endpoint `GET /x` may reach its end without returning a value",
+            ]
+        );
+        assert!(errors.iter().all(|e| e.code == "E0215"));
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Int` to be returned before here"));
+        assert_eq!(errors[0].secondary[0].message.as_deref(), Some("declared return type"));
+    }
+
+    #[test]
+    fn test_undefined_names_and_types() {
+        let errors = check(
+            r#"
+            fn f(a: Strin): Int {
+                let b: Widget = missing;
+                return helper(a);
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "cannot find type `Strin`",
+                "cannot find type `Widget`",
+                "cannot find value `missing` in this scope",
+                "cannot find function `helper` in this scope",
+            ]
+        );
+        assert_eq!(errors.iter().map(|e| e.code).collect::<Vec<_>>(), ["E0203", "E0203", "E0205", "E0205"]);
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let errors = check(
+            r#"
+            class Point { x: Int = 0; fn norm(): Float { return 1; } }
+            fn f(p: Point, s: String) {
+                if s { }
+                let a = p.y;
+                let b = p.norm(1);
+                let c = s.shout();
+                let d = true - 1;
+                for c in 3 { }
+                let e: Int = 1.5;
+                let f: Int = 3.0;
+                let g: Float = 3.0;
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "mismatched types",
+                "no field `y` on type `Point`",
+                "`norm` takes 0 arguments but 1 were supplied",
+                "no method `shout` on type `String`",
+                "cannot apply `-` to `Bool` and `Int`",
+                "cannot iterate over `Int`",
+                "mismatched types",
+                "mismatched types",
+            ]
+        );
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Bool`, found `String`"));
+        assert_eq!(errors[6].primary.message.as_deref(), Some("expected `Int`, found `Float`"));
+        assert_eq!(errors[7].primary.message.as_deref(), Some("expected `Int`, found `Float`"));
+    }
+
+    #[test]
+    fn test_collection_types() {
+        let source = r#"
+            fn totals(scores: Map<String, List<Int>>): Map<String, Int> {
+                var result: Map<String, Int> = {};
+                for name in scores {
+                    var sum = 0;
+                    for score in scores[name] { sum += score; }
+                    result[name] = sum;
+                }
+                return result;
+            }
+            fn names(): List<String> { return ["a", "b"] + ["c"]; }
+            fn ratios(): List<Float> { return [1, 2.5]; }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            fn f(xs: List<Int>, m: Map<String, Bool>) {
+                let a: List<String> = xs;
+                let b: Int = m["k"];
+                let c = xs["0"];
+                let d: List<Int, Int> = [];
+                let e: Map<Int, String> = {};
+                let g: Int<String> = 1;
+            }
+            "#,
+        );
+        let labels: Vec<_> = errors.iter().map(|e| (e.code, e.message.as_str())).collect();
+        assert_eq!(
+            labels,
+            [
+                ("E0204", "mismatched types"),
+                ("E0204", "mismatched types"),
+                ("E0204", "mismatched types"),
+                ("E0209", "`List` takes 1 type argument but 2 were supplied"),
+                ("E0209", "map keys must be `String`, found `Int`"),
+                ("E0209", "`Int` takes 0 type arguments but 1 were supplied"),
+            ]
+        );
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `List<String>`, found `List<Int>`"));
+        assert_eq!(errors[1].primary.message.as_deref(), Some("expected `Int`, found `Bool`"));
+    }
+
+    #[test]
+    fn test_interpolated_strings() {
+        let source = r#"
+            struct User { name: String; age: Int; }
+            fn greet(user: User): String { return "Hi \(user.name.toUpper()), \(user.age + 1)"; }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            struct User { name: String; }
+            fn log(message: String) { }
+            fn f(user: User): Int {
+                return "\(user.nme) \(log("x")) \(missing)";
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "no field `nme` on type `User`",
+                "cannot interpolate a value of type `Void`",
+                "cannot find value `missing` in this scope",
+                "mismatched types",
+            ]
+        );
+        assert_eq!(errors[3].primary.message.as_deref(), Some("expected `Int`, found `String`"));
+    }
+
+    #[test]
+    fn test_optional_types() {
+        let source = r#"
+            class User { name: String = ""; }
+            fn find(id: Int): User? {
+                if id == 0 { return null; }
+                return User();
+            }
+            fn name(id: Int): String {
+                let user = find(id);
+                if user != null { return user.name; }
+                return "nobody";
+            }
+            fn first(id: Int): String {
+                let user = find(id);
+                if user == null { return "nobody"; }
+                return user.name;
+            }
+            fn label(title: String?): String { return title ?? "untitled"; }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            class User { name: String = ""; }
+            fn f(user: User?, count: Int?) {
+                let a: User = null;
+                let b = user.name;
+                let c: Int = count;
+                let d: Int? = 1;
+            }
+            "#,
+        );
+        assert_eq!(messages(&errors), ["mismatched types", "value of type `User?` may be null", "mismatched types"]);
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `User`, found `null`"));
+        assert_eq!(errors[1].code, "E0210");
+        assert_eq!(errors[2].primary.message.as_deref(), Some("expected `Int`, found `Int?`"));
+    }
+
+    #[test]
+    fn test_dynamic_opts_out_of_checking() {
+        let source = r#"
+            class Point { x: Int = 0; }
+            fn parse(raw: String): dynamic { return raw; }
+            fn f(raw: any): Int {
+                let n: Int = parse("1");
+                let p: Point = raw;
+                let items: List<dynamic> = [1, "two", Point()];
+                var anything: dynamic = 1;
+                anything = "now a string";
+                return raw.count + raw.items[0].size() + items.length + n + p.x;
+            }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check("fn f(raw: dynamic): String { let n: Int = \"1\"; return raw; }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Int`, found `String`"));
+    }
+
+    #[test]
+    fn test_struct_types() {
+        let source = r#"
+            struct Address { city: String; zip: String?; }
+            record User { name: String; age: Int = 0; address: Address; }
+            fn make(name: String): User {
+                var user = User { name: name, address: Address { city: "Oslo" } };
+                user.age = user.age + 1;
+                return user;
+            }
+            fn city(user: User): String { return user.address.city; }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            struct Point { x: Int; y: Int; }
+            class Shape { }
+            fn f(): Point {
+                let a = Point { x: 1, y: "2" };
+                let b = Point { x: 1, z: 2, x: 3 };
+                let c = Shape { };
+                let d = a.z;
+                return Point { };
+            }
+            service S {
+                @post("/p")
+                endpoint p(a: Point, b: Point): Int { return a.x + b.x; }
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "mismatched types",
+                "no field `z` on type `Point`",
+                "field `x` is given more than once",
+                "missing field `y` in `Point`",
+                "cannot find struct `Shape`",
+                "no field `z` on type `Point`",
+                "missing fields `x`, `y` in `Point`",
+                "endpoint `POST /p` has more than one request body parameter",
+            ]
+        );
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Int`, found `String`"));
+        assert_eq!(errors[4].suggestions[0].message, "`Shape` is a class; create it with `Shape(...)`");
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let source = r#"
+            enum Status { Pending, Failed(reason: String, code: Int), Done(at: Int?) }
+            fn describe(status: Status): String {
+                return match status {
+                    Status.Pending => "pending",
+                    Status.Failed(reason, 500) => "server: " + reason,
+                    Status.Failed(_, code) => "failed with " + code,
+                    Status.Done(null) => "done",
+                    Status.Done(at) => "done at " + at,
+                };
+            }
+            fn flag(on: Bool, status: Status?): Int {
+                match on {
+                    true => { return 1; }
+                    false => { }
+                }
+                match status {
+                    null => { return 0; }
+                    other => { return 2; }
+                }
+            }
+            fn fail(): Status { return Status.Failed("boom", 500); }
+        "#;
+        assert!(check(source).is_empty(), "{:?}", check(source));
+
+        let errors = check(
+            r#"
+            enum Status { Pending, Failed(reason: String) }
+            fn f(status: Status, n: Int): String {
+                let a = Status.Failed;
+                let b = Status.Failed(1);
+                let c = Status.Gone;
+                match n { 1 => { } -1 => { } }
+                match status { Status.Pending => { } }
+                match status { Status.Failed(_, _) => { } Status.Lost => { } _ => { } }
+                return match status { Status.Pending => "p", Status.Failed(_) => 1 };
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "`Failed` takes 1 argument but 0 were supplied",
+                "mismatched types",
+                "no variant `Gone` on type `Status`",
+                "non-exhaustive match: `_` not covered",
+                "non-exhaustive match: `Status.Failed(_)` not covered",
+                "variant `Status.Failed` has 1 field but the pattern has 2 fields",
+                "no variant `Lost` on type `Status`",
+                "`match` arms have incompatible types",
+            ]
+        );
+        assert_eq!(errors[4].suggestions[0].message, "add an arm for it, or a `_` wildcard arm");
+        assert_eq!(errors[7].primary.message.as_deref(), Some("expected `String`, found `Int`"));
+    }
+
+    #[test]
+    fn test_generics() {
+        let source = format!(
+            r#"{}
+            fn first<T>(items: List<T>): T? {{ if items.length == 0 {{ return null; }} return items[0]; }}
+            fn pick<T>(a: T, b: T, left: Bool): T {{ if left {{ return a; }} return b; }}
+            fn label<T: Named>(item: T): String {{ return item.name(); }}
+
+            class Person implements Named {{ fn name(): String {{ return "ada"; }} }}
+
+            class Box<T> {{
+                value: T;
+                init(value: T) {{ self.value = value; }}
+                fn get(): T {{ return self.value; }}
+                fn map<U>(f: U): Box<U> {{ return Box(f); }}
+            }}
+            struct Page<T> {{ items: List<T>; total: Int = 0; }}
+            enum Result<T> {{ Ok(value: T), Err(message: String) }}
+
+            fn use(): Int {{
+                let n: Int? = first([1, 2]);
+                let f: Float = pick(1, 2.5, true);
+                let s: String = label(Person());
+                let b = Box(3);
+                let i: Int = b.get() + b.value;
+                let t: String = b.map("x").get();
+                let p = Page {{ items: ["a"] }};
+                let names: List<String> = p.items;
+                let r: Result<Int> = Result.Ok(1);
+                let e: Result<Int> = Result.Err("no");
+                return match r {{ Result.Ok(value) => value, Result.Err(_) => 0 }};
+            }}
+            "#,
+            SHAPES
+        );
+        assert!(check(&source).is_empty(), "{:?}", check(&source));
+
+        let errors = check(&format!(
+            r#"{}
+            fn label<T: Named>(item: T): String {{ return item.name(); }}
+            fn same<T>(a: T, b: T): T {{ return a; }}
+            fn broken<T>(item: T): Int {{ return item.size(); }}
+            class Box<T: Named> {{ value: T; init(value: T) {{ self.value = value; }} }}
+            struct Pair<A, B> {{ a: A; b: B; }}
+            fn bad<T: Box>(item: T) {{ }}
+
+            fn use() {{
+                let s = label(1);
+                let x: Int = same(1, "a");
+                let b: Box<Int> = Box(1);
+                let p: Pair<Int> = Pair {{ a: 1, b: 2 }};
+                let q: Pair<Int, String> = Pair {{ a: 1, b: 2 }};
+            }}
+            "#,
+            SHAPES
+        ));
+        assert_eq!(
+            messages(&errors),
+            [
+                "no method `size` on type `T`",
+                "cannot find interface `Box`",
+                "`Int` does not implement `Named`",
+                "mismatched types",
+                "`Int` does not implement `Named`",
+                "`Int` does not implement `Named`",
+                "`Pair` takes 2 type arguments but 1 were supplied",
+                "mismatched types",
+            ]
+        );
+        assert_eq!(errors[2].primary.message.as_deref(), Some("required by the bound `T: Named`"));
+        assert_eq!(errors[3].primary.message.as_deref(), Some("expected `Int`, found `String`"));
+        assert_eq!(errors[7].primary.message.as_deref(), Some("expected `Pair<Int, String>`, found `Pair<Int, Int>`"));
+    }
+
+    #[test]
+    fn test_subtyping_and_interfaces() {
+        let source = format!(
+            r#"{}
+            class Square implements Shape {{
+                fn area(): Float {{ return 1; }}
+                fn describe(prefix: String): String {{ return prefix; }}
+            }}
+            class Other {{ }}
+            fn measure(shape: Shape): Float {{ return shape.area() + shape.describe("x").length; }}
+            fn f() {{
+                let a = measure(Square());
+                let b = measure(Other());
+            }}
+            "#,
+            SHAPES
+        );
+        let errors = check(&source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Shape`, found `Other`"));
+    }
+
+    #[test]
+    fn test_names_must_be_imported() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.ath"), "import { a } from \"./lib.ath\";\nfn f(): Int { return a() + b(); }\n").unwrap();
+        std::fs::write(dir.path().join("lib.ath"), "export fn a(): Int { return b(); }\nexport fn b(): Int { return 1; }\n").unwrap();
+
+        let errors = Compiler::from_file(dir.path().join("main.ath")).unwrap().compile().unwrap_err();
+        assert_eq!(messages(&errors), ["cannot find function `b` in this scope"]);
+        assert_eq!(errors[0].suggestions[0].message, "import `b` from the file declaring it");
+    }
+
+    #[test]
+    fn test_try_catch_and_throw() {
+        let errors = check(
+            r#"
+            class NotFound extends Error { key: String = ""; }
+            fn find(key: String): Int {
+                if key == "" { throw NotFound("no key"); }
+                return key.length;
+            }
+            fn describe(key: String): String {
+                try {
+                    return "found " + find(key);
+                } catch (e: NotFound) {
+                    return e.key + e.message;
+                } catch (e) {
+                    return e.stack[0];
+                }
+            }
+            fn f() {
+                let n: Int = throw Error("unreachable");
+                throw "oops";
+                try { } catch (e: String) { let s: String = e; }
+                try { } catch (e) { let key = e.key; }
+            }
+            "#,
+        );
+        assert_eq!(messages(&errors), ["mismatched types", "mismatched types", "no field `key` on type `Error`"]);
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Error`, found `String`"));
+        assert_eq!(errors[0].notes, ["only `Error` and its subclasses can be thrown"]);
+        assert_eq!(errors[1].notes, ["only `Error` and its subclasses can be caught"]);
+    }
+
+    #[test]
+    fn test_defer() {
+        let errors = check(
+            r#"
+            fn f(): Int {
+                var total = 0;
+                defer total = "done";
+                defer { let note = "x"; }
+                let note = 1;
+                return total + note;
+            }
+            "#,
+        );
+        assert_eq!(messages(&errors), ["mismatched types"]);
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `Int`, found `String`"));
+    }
+
+    #[test]
+    fn test_results_and_propagation() {
+        let errors = check(
+            r#"
+            struct NotFound { message: String; status: Int = 404; }
+            fn parse(s: String): Result<Int, String> {
+                if s == "" { return Result.Err("empty"); }
+                return Result.Ok(s.length);
+            }
+            fn double(s: String): Result<Int, String> { return Result.Ok(parse(s)? * 2); }
+            fn first(items: List<Int>): Option<Int> {
+                if items.length == 0 { return Option.None; }
+                return Option.Some(items[0]);
+            }
+            fn label(items: List<Int>): Option<String> {
+                let n: Int = first(items)?;
+                return Option.Some("n" + n);
+            }
+            fn describe(s: String): String {
+                return match parse(s) {
+                    Result.Ok(n) => "ok " + n,
+                    Result.Err(error) => error,
+                };
+            }
+            fn wrong(s: String): Result<Int, NotFound> { return Result.Ok(parse(s)?); }
+            fn plain(s: String): Int { return parse(s)?; }
+            fn other(items: List<Int>): Result<Int, String> { return Result.Ok(first(items)?); }
+            fn number(): Result<Int, String> { return Result.Ok(1?); }
+            fn typed(): Result<String, String> { return double("a"); }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "mismatched types",
+                "the `?` operator can only be used in a body that returns `Result`",
+                "the `?` operator can only be used in a body that returns `Option`",
+                "the `?` operator can only be applied to a `Result` or an `Option`",
+                "mismatched types",
+            ]
+        );
+        assert_eq!(errors[0].primary.message.as_deref(), Some("expected `NotFound`, found `String`"));
+        assert_eq!(errors[0].notes, ["`wrong` is declared to return `Result<Int, NotFound>`"]);
+        assert_eq!(errors[1].notes, ["`plain` is declared to return `Int`"]);
+        assert_eq!(
+            errors[4].primary.message.as_deref(),
+            Some("expected `Result<String, String>`, found `Result<Int, String>`")
+        );
+    }
+
+    #[test]
+    fn test_async_and_await() {
+        let errors = check(
+            r#"
+            interface Loader { fn load(id: Int): Future<String>; }
+            class Remote implements Loader {
+                async fn load(id: Int): String { await sleep(10); return "item " + id; }
+            }
+            async fn count(): Int { return 1; }
+            async fn total(): Int {
+                let counts: List<Int> = await all([count(), count()]);
+                let first: Int = await race([count(), timeout(count(), 100)]);
+                return counts[0] + first + await count();
+            }
+            fn later(): Future<Int> { return count(); }
+            fn blocking(): Int { return await count(); }
+            async fn number(): Int { return await 1; }
+            async fn typed(): String { return await count(); }
+            async fn mixed(): Int { let n = await all([count(), sleep(1)]); return await timeout(1, 2); }
+            service Api {
+                @get("/total")
+                endpoint get_total(): Int { return await total() + (await Remote().load(1)).length; }
+            }
+            "#,
+        );
+        assert_eq!(
+            messages(&errors),
+            [
+                "`await` is only allowed in `async` functions and endpoints",
+                "cannot `await` a value of type `Int`",
+                "mismatched types",
+                "mismatched types",
+            ]
+        );
+        assert_eq!(errors[0].suggestions[0].message, "make `blocking` an `async fn`");
+        assert_eq!(errors[2].primary.message.as_deref(), Some("expected `String`, found `Int`"));
+        assert_eq!(errors[3].primary.message.as_deref(), Some("expected `Future<dynamic>`, found `Int`"));
+    }
+}
@@ -0,0 +1,20 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Compiler;
+
+    #[test]
+    fn test_hello_world_service() {
+        let source = r#"
+            service HelloService {
+                @get("/hello")
+                endpoint greet(name: String): String {
+                    return "Hello, World!";
+                }
+            }
+        "#;
+
+        let compiler = Compiler::new(source.to_string());
+        let result = compiler.compile();
+        assert!(result.is_ok());
+    }
+}
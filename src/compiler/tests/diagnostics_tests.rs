@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::diagnostics::{Diagnostic, Label};
+    use crate::compiler::{Compiler, SourceMap, Span};
+
+    /// Compile `source`, returning each error with its 1-based (line, column).
+    fn compile_errors(source: &str) -> Vec<(Diagnostic, (usize, usize))> {
+        let compiler = Compiler::new(source.to_string());
+        let errors = compiler.compile().expect_err("expected compilation to fail");
+        errors
+            .into_iter()
+            .map(|error| {
+                let location = compiler.source_map.span_start(error.primary.span);
+                let position = (location.line, location.column);
+                (error, position)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_every_error_in_file() {
+        let source = r#"service Broken {
+    @get("/a")
+    endpoint a(): String {
+        return "a"
+    }
+
+    @get("/b")
+    endpoint b() String {
+        return "b";
+    }
+
+    @get("/c")
+    endpoint c(): String {
+        return "c" +;
+    }
+}"#;
+        let errors = compile_errors(source);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].1 .0, 4);
+        assert_eq!(errors[1].1 .0, 8);
+        assert_eq!(errors[2].1 .0, 14);
+    }
+
+    #[test]
+    fn test_missing_semicolon_points_after_expression() {
+        let errors = compile_errors("service S {\n    @get(\"/\")\n    endpoint e(): String {\n        return x\n    }\n}");
+        assert_eq!(errors.len(), 1);
+        let (error, position) = &errors[0];
+        assert_eq!(error.code, "E0100");
+        assert_eq!(*position, (4, 17));
+        assert_eq!(error.secondary.len(), 1);
+        assert_eq!(error.suggestions[0].replacement.as_deref(), Some(";"));
+    }
+
+    #[test]
+    fn test_unknown_character_is_reported_and_skipped() {
+        let errors = compile_errors("service S # {\n}");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0.code, "E0001");
+        assert_eq!(errors[0].1, (1, 11));
+    }
+
+    #[test]
+    fn test_unclosed_service() {
+        let errors = compile_errors("service S {\n    @get(\"/\")\n    endpoint e(): String { return \"x\"; }\n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].0.message.contains("expected `}`"));
+    }
+
+    #[test]
+    fn test_render_with_carets() {
+        let source = "service S {\n    @get(\"/\")\n    endpoint e(): String {\n        return );\n    }\n}";
+        let compiler = Compiler::new(source.to_string());
+        let errors = compiler.compile().unwrap_err();
+        let rendered = compiler.render_diagnostics(&errors);
+        let expected = "\
+error[E0104]: expected an expression, found `)`
+ --> <input>:4:16
+  |
+4 |         return );
+  |                ^ expected an expression
+
+error: aborting due to 1 previous error
+";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_render_notes_and_secondary_labels() {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file("file.ath", "ab\n    cde\n");
+        let diagnostic = Diagnostic::error("E9999", "something broke", Label::new(Span::new(file_id, 7, 10)).with_message("here"))
+            .with_secondary(Label::new(Span::new(file_id, 0, 2)).with_message("because of this"))
+            .with_note("a note")
+            .with_suggestion("try this", Some("fix".to_string()));
+        let rendered = diagnostic.render(&source_map);
+        let expected = "\
+error[E9999]: something broke
+ --> file.ath:2:5
+  |
+1 | ab
+  | -- because of this
+2 |     cde
+  |     ^^^ here
+  = note: a note
+  = help: try this: `fix`
+";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_multiline_span_is_underlined_to_end_of_first_line() {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file("file.ath", "let a = {\n    b\n}");
+        let diagnostic = Diagnostic::error("E9999", "bad block", Label::new(Span::new(file_id, 8, 17)));
+        let rendered = diagnostic.render(&source_map);
+        assert!(rendered.contains("1 | let a = {\n  |         ^\n"));
+    }
+
+    #[test]
+    fn test_lexer_errors_do_not_truncate_the_service() {
+        let source = "service S {\n    @get(\"/a\")\n    endpoint a(): String { return \"a; }\n    @get(\"/b\")\n    endpoint b(): String { return 1.2.3; }\n}";
+        let errors = compile_errors(source);
+        let codes: Vec<&str> = errors.iter().map(|(e, _)| e.code).collect();
+        assert!(codes.contains(&"E0003"));
+        assert!(codes.contains(&"E0004"));
+        assert!(!codes.contains(&"E0104"));
+        assert_eq!(errors.iter().find(|(e, _)| e.code == "E0004").unwrap().1, (5, 35));
+    }
+
+    #[test]
+    fn test_missing_semicolon_before_a_statement_is_recovered() {
+        let source = "service S {\n    @get(\"/\")\n    endpoint e(): Int {\n        let x = 1\n        return x +;\n    }\n}";
+        let errors = compile_errors(source);
+        let found: Vec<(&str, usize)> = errors.iter().map(|(e, position)| (e.code, position.0)).collect();
+        assert_eq!(found, [("E0100", 4), ("E0104", 5)]);
+
+        // On one line, only a keyword is taken to start the next statement
+        let errors = compile_errors("fn f(): Int {\n    let x = 1 let y = 2;\n    let z = x y;\n    return z;\n}");
+        let found: Vec<(&str, usize)> = errors.iter().map(|(e, position)| (e.code, position.0)).collect();
+        assert_eq!(found, [("E0100", 2), ("E0100", 3)]);
+    }
+
+    #[test]
+    fn test_parse_errors_after_lexer_errors_are_not_reported() {
+        let errors = compile_errors("fn f(): Int {\n    let x = 5 $ 3;\n    return x;\n}");
+        let codes: Vec<&str> = errors.iter().map(|(e, _)| e.code).collect();
+        assert_eq!(codes, ["E0001"]);
+
+        let errors = compile_errors("fn f(): String {\n    return \"a;\n}");
+        let codes: Vec<&str> = errors.iter().map(|(e, _)| e.code).collect();
+        assert_eq!(codes, ["E0003"]);
+    }
+
+    #[test]
+    fn test_items_that_parse_are_still_checked() {
+        let source = "fn broken(): Int {\n    return 1 +;\n}\n\nfn typed(): String {\n    return broken();\n}";
+        let errors = compile_errors(source);
+        let found: Vec<(&str, usize)> = errors.iter().map(|(e, position)| (e.code, position.0)).collect();
+        assert_eq!(found, [("E0104", 2), ("E0204", 6)]);
+    }
+}
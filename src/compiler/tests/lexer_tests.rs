@@ -0,0 +1,242 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::lexer::{LexError, Lexer, Token, TokenType};
+
+    fn tokens(source: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if token.token_type == TokenType::EOF {
+                break;
+            }
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    fn token_types(source: &str) -> Vec<TokenType> {
+        tokens(source).into_iter().map(|t| t.token_type).collect()
+    }
+
+    #[test]
+    fn test_line_comments_are_skipped() {
+        let tokens = token_types("service // the service\n// a whole line\nS");
+        assert_eq!(
+            tokens,
+            vec![TokenType::Service, TokenType::Identifier("S".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_nested_block_comments_are_skipped() {
+        let tokens = token_types("{ /* outer /* inner */ still outer */ }");
+        assert_eq!(tokens, vec![TokenType::LeftBrace, TokenType::RightBrace]);
+    }
+
+    #[test]
+    fn test_block_comment_keeps_line_numbers() {
+        let mut lexer = Lexer::new("/* one\ntwo\n*/ service");
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, TokenType::Service);
+        assert_eq!((token.line, token.column), (3, 3));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let tokens = tokens("service /* never closed");
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::Error(LexError::UnterminatedBlockComment)
+        );
+        assert_eq!(tokens[1].span.start, 8);
+    }
+
+    #[test]
+    fn test_doc_comments_are_tokens() {
+        let tokens = token_types("/// Greets people.\n///\n////not docs\nservice");
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::DocComment("Greets people.".to_string()),
+                TokenType::DocComment(String::new()),
+                TokenType::Service,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_character_is_an_error_token() {
+        let tokens = tokens("service # S");
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::Error(LexError::UnexpectedCharacter('#'))
+        );
+        assert_eq!((tokens[1].span.start, tokens[1].span.end), (8, 9));
+        assert_eq!((tokens[1].line, tokens[1].column), (1, 8));
+        assert_eq!(tokens[2].token_type, TokenType::Identifier("S".to_string()));
+    }
+
+    #[test]
+    fn test_all_operators_and_punctuation() {
+        use TokenType::*;
+        assert_eq!(
+            token_types("{ } ( ) [ ] -> => : ; , . .. @ = == != < <= > >= + - * / % && || ! ? ??"),
+            vec![
+                LeftBrace, RightBrace, LeftParen, RightParen, LeftBracket, RightBracket,
+                Arrow, FatArrow, Colon, Semicolon, Comma, Dot, DotDot, At,
+                Equal, EqualEqual, BangEqual, Less, LessEqual, Greater, GreaterEqual,
+                Plus, Minus, Star, Slash, Percent, AndAnd, OrOr, Bang, Question, QuestionQuestion,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_longest_match_without_spaces() {
+        use TokenType::*;
+        let ident = |name: &str| Identifier(name.to_string());
+        assert_eq!(
+            token_types("a>=b==c!=!d=>e->f??g?.h"),
+            vec![
+                ident("a"), GreaterEqual, ident("b"), EqualEqual, ident("c"), BangEqual, Bang,
+                ident("d"), FatArrow, ident("e"), Arrow, ident("f"), QuestionQuestion, ident("g"),
+                Question, Dot, ident("h"),
+            ]
+        );
+        assert_eq!(
+            token_types("0..10 x.y"),
+            vec![
+                NumberLiteral(0.0), DotDot, NumberLiteral(10.0), ident("x"), Dot, ident("y"),
+            ]
+        );
+        assert_eq!(token_types("a-1"), vec![ident("a"), Minus, NumberLiteral(1.0)]);
+        assert_eq!(token_types("a/b // c"), vec![ident("a"), Slash, ident("b")]);
+    }
+
+    #[test]
+    fn test_single_ampersand_and_pipe_are_errors() {
+        assert_eq!(
+            token_types("& |"),
+            vec![
+                TokenType::Error(LexError::UnexpectedCharacter('&')),
+                TokenType::Error(LexError::UnexpectedCharacter('|')),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string_stops_at_line_end() {
+        let tokens = tokens("\"hello\nservice");
+        assert_eq!(tokens[0].token_type, TokenType::Error(LexError::UnterminatedString));
+        assert_eq!((tokens[0].span.start, tokens[0].span.end), (0, 6));
+        assert_eq!(tokens[1].token_type, TokenType::Service);
+        assert_eq!(tokens[1].line, 2);
+
+        assert_eq!(
+            token_types("\"no end"),
+            vec![TokenType::Error(LexError::UnterminatedString)]
+        );
+    }
+
+    #[test]
+    fn test_interpolated_strings() {
+        let string = |text: &str| text.to_string();
+        assert_eq!(
+            token_types(r#""a \(x) b \(f(1)) c""#),
+            vec![
+                TokenType::StringHead(string("a ")),
+                TokenType::Identifier(string("x")),
+                TokenType::StringMiddle(string(" b ")),
+                TokenType::Identifier(string("f")),
+                TokenType::LeftParen,
+                TokenType::NumberLiteral(1.0),
+                TokenType::RightParen,
+                TokenType::StringTail(string(" c")),
+            ]
+        );
+        // Strings nest inside interpolations, and an escaped backslash
+        // does not start one
+        assert_eq!(
+            token_types(r#""\("in \(y)")" "\\(z)""#),
+            vec![
+                TokenType::StringHead(string("")),
+                TokenType::StringHead(string("in ")),
+                TokenType::Identifier(string("y")),
+                TokenType::StringTail(string("")),
+                TokenType::StringTail(string("")),
+                TokenType::StringLiteral(string("\\(z)")),
+            ]
+        );
+        let tokens = tokens(r#""a \(x)""#);
+        assert_eq!((tokens[2].span.start, tokens[2].span.end), (6, 8));
+    }
+
+    #[test]
+    fn test_escaped_interpolation_is_text() {
+        let string = |text: &str| text.to_string();
+        assert_eq!(
+            token_types(r#""use \\(name) for \\(" "\\""#),
+            vec![
+                TokenType::StringLiteral(string(r"use \(name) for \(")),
+                TokenType::StringLiteral(string(r"\")),
+            ]
+        );
+        // An escaped backslash may still be followed by an interpolation
+        assert_eq!(
+            token_types(r#""\\\(x)""#),
+            vec![
+                TokenType::StringHead(string(r"\")),
+                TokenType::Identifier(string("x")),
+                TokenType::StringTail(string("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_escapes_keep_the_backslash() {
+        assert_eq!(
+            token_types(r#""C:\q\d \n\"""#),
+            vec![TokenType::StringLiteral("C:\\q\\d \n\"".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(
+            token_types("42 3.25 3.0"),
+            vec![TokenType::NumberLiteral(42.0), TokenType::FloatLiteral(3.25), TokenType::FloatLiteral(3.0)]
+        );
+        assert_eq!(
+            token_types("1.2.3 12px"),
+            vec![
+                TokenType::Error(LexError::MalformedNumber("1.2.3".to_string())),
+                TokenType::Error(LexError::MalformedNumber("12px".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_method_call_on_float_literal() {
+        assert_eq!(
+            token_types("1.5.round()"),
+            vec![
+                TokenType::FloatLiteral(1.5),
+                TokenType::Dot,
+                TokenType::Identifier("round".to_string()),
+                TokenType::LeftParen,
+                TokenType::RightParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let tokens = tokens("service\r\n  S\r\n/// doc\r\n{");
+        assert_eq!(tokens[1].token_type, TokenType::Identifier("S".to_string()));
+        assert_eq!((tokens[1].line, tokens[1].column), (2, 2));
+        assert_eq!(tokens[2].token_type, TokenType::DocComment("doc".to_string()));
+        assert_eq!(tokens[3].line, 4);
+    }
+}
@@ -0,0 +1,7 @@
+mod checker_tests;
+mod compiler_tests;
+mod diagnostics_tests;
+mod source_map_tests;
+mod lexer_tests;
+mod modules_tests;
+mod parser_tests;
@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::compiler::parser::ASTNode;
+    use crate::compiler::{Compiler, Diagnostic};
+
+    /// Write `files` under `root`, creating directories as needed.
+    fn write_files(root: &Path, files: &[(&str, &str)]) {
+        for (name, source) in files {
+            let path = root.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+    }
+
+    fn compile(root: &Path, entry: &str) -> Result<ASTNode, Vec<Diagnostic>> {
+        Compiler::from_file(root.join(entry)).unwrap().compile()
+    }
+
+    fn item_names(program: &ASTNode) -> Vec<&str> {
+        let ASTNode::Program { items, .. } = program else { panic!("expected program") };
+        items
+            .iter()
+            .filter_map(|item| match item {
+                ASTNode::Function { name, .. } | ASTNode::Class { name, .. } | ASTNode::Constant { name, .. } => {
+                    Some(name.as_str())
+                }
+                ASTNode::Service { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_links_imports_before_importers() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("aether.toml", "name = \"app\"\n"),
+                ("src/main.ath", "import { greet } from \"./greeting.ath\";\nimport { Base } from \"models/base\";\nservice Api { }\n"),
+                ("src/greeting.ath", "import { Base } from \"./models/base.ath\";\nexport fn greet(): String { return helper(); }\nfn helper(): String { return \"hi\"; }\n"),
+                ("src/models/base.ath", "pub class Base { }\n"),
+            ],
+        );
+
+        let compiler = Compiler::from_file(dir.path().join("src/main.ath")).unwrap();
+        let program = compiler.compile().unwrap();
+        // The shared dependency is loaded once, and first
+        assert_eq!(item_names(&program), ["Base", "greet", "greeting::helper", "Api"]);
+        assert_eq!(compiler.modules.modules.len(), 3);
+        assert_eq!(compiler.modules.modules[0].imports, [1, 2]);
+        assert_eq!(compiler.modules.modules[1].imports, [2]);
+    }
+
+    #[test]
+    fn test_missing_module() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &[("main.ath", "import { a } from \"./nowhere.ath\";\n")]);
+
+        let errors = compile(dir.path(), "main.ath").unwrap_err();
+        assert_eq!(messages(&errors), ["cannot find module `./nowhere.ath`"]);
+        assert_eq!(errors[0].code, "E0300");
+    }
+
+    #[test]
+    fn test_import_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("main.ath", "import { a } from \"./a.ath\";\n"),
+                ("a.ath", "import { b } from \"./b.ath\";\nexport fn a() { }\n"),
+                ("b.ath", "import { a } from \"./a.ath\";\nexport fn b() { }\n"),
+            ],
+        );
+
+        let errors = compile(dir.path(), "main.ath").unwrap_err();
+        assert_eq!(messages(&errors), ["import cycle through module `./a.ath`"]);
+        assert_eq!(errors[0].code, "E0301");
+        let cycle = &errors[0].notes[0];
+        let files: Vec<&str> = cycle["cycle: ".len()..]
+            .split(" -> ")
+            .map(|file| Path::new(file).file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(files, ["a.ath", "b.ath", "a.ath"]);
+    }
+
+    #[test]
+    fn test_imported_names_must_be_exported() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("main.ath", "import { open, hidden, missing } from \"./lib.ath\";\n"),
+                ("lib.ath", "export const open = 1;\nconst hidden = 2;\n"),
+            ],
+        );
+
+        let errors = compile(dir.path(), "main.ath").unwrap_err();
+        assert_eq!(
+            messages(&errors),
+            ["`hidden` is private to module `./lib.ath`", "module `./lib.ath` has no item named `missing`"]
+        );
+        assert_eq!([errors[0].code, errors[1].code], ["E0303", "E0302"]);
+    }
+
+    #[test]
+    fn test_private_names_are_kept_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("main.ath", "import { a } from \"./lib.ath\";\nfn helper(): Int { return a(); }\n"),
+                ("lib.ath", "export fn a(): Int { return helper(); }\nfn helper(): Int { return 1; }\n"),
+            ],
+        );
+
+        let program = compile(dir.path(), "main.ath").unwrap();
+        assert_eq!(item_names(&program), ["a", "lib::helper", "helper"]);
+    }
+
+    #[test]
+    fn test_exported_names_are_unique_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("main.ath", "import { a } from \"./lib.ath\";\nimport { b } from \"./other.ath\";\n"),
+                ("lib.ath", "export fn a() { }\nexport fn shared() { }\n"),
+                ("other.ath", "export fn b() { }\nexport fn shared() { }\n"),
+            ],
+        );
+
+        let errors = compile(dir.path(), "main.ath").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "E0304");
+        assert!(errors[0].message.starts_with("`shared` is declared in both"), "{}", errors[0].message);
+    }
+}
@@ -0,0 +1,690 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::parser::{ASTNode, Parser, Pattern, StringPart};
+
+    /// Render an expression as a fully parenthesised S-expression.
+    fn sexpr(node: &ASTNode) -> String {
+        match node {
+            ASTNode::StringLiteral { value, .. } => format!("{:?}", value),
+            ASTNode::InterpolatedString { parts, .. } => format!(
+                "(str{})",
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        StringPart::Text(text) => format!(" {:?}", text),
+                        StringPart::Expression(expression) => format!(" {}", sexpr(expression)),
+                    })
+                    .collect::<String>()
+            ),
+            ASTNode::NumberLiteral { value, .. } => value.to_string(),
+            ASTNode::BooleanLiteral { value, .. } => value.to_string(),
+            ASTNode::NullLiteral { .. } => "null".to_string(),
+            ASTNode::Identifier { name, .. } => name.clone(),
+            ASTNode::ArrayLiteral { elements, .. } => {
+                format!("[{}]", elements.iter().map(sexpr).collect::<Vec<_>>().join(" "))
+            }
+            ASTNode::ObjectLiteral { fields, .. } => format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, sexpr(v)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            ASTNode::StructLiteral { name, fields, .. } => format!(
+                "{} {{{}}}",
+                name,
+                fields
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, sexpr(v)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            ASTNode::BinaryExpression { operator, left, right, .. } => {
+                format!("({} {} {})", operator, sexpr(left), sexpr(right))
+            }
+            ASTNode::UnaryExpression { operator, operand, .. } => format!("({} {})", operator, sexpr(operand)),
+            ASTNode::CallExpression { callee, arguments, .. } => format!(
+                "(call {}{})",
+                sexpr(callee),
+                arguments.iter().map(|a| format!(" {}", sexpr(a))).collect::<String>()
+            ),
+            ASTNode::MemberExpression { object, member, .. } => format!("(. {} {})", sexpr(object), member),
+            ASTNode::IndexExpression { object, index, .. } => format!("([] {} {})", sexpr(object), sexpr(index)),
+            ASTNode::GroupingExpression { expression, .. } => format!("(group {})", sexpr(expression)),
+            ASTNode::SuperExpression { .. } => "super".to_string(),
+            ASTNode::ThrowExpression { error, .. } => format!("(throw {})", sexpr(error)),
+            ASTNode::PropagateExpression { expression, .. } => format!("(? {})", sexpr(expression)),
+            ASTNode::AwaitExpression { future, .. } => format!("(await {})", sexpr(future)),
+            ASTNode::Match { subject, arms, .. } => format!(
+                "(match {}{})",
+                sexpr(subject),
+                arms.iter()
+                    .map(|arm| format!(" {} => {}", pattern(&arm.pattern), sexpr(&arm.body)))
+                    .collect::<String>()
+            ),
+            other => panic!("not an expression: {:?}", other),
+        }
+    }
+
+    fn pattern(pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Wildcard { .. } => "_".to_string(),
+            Pattern::Binding { name, .. } => format!("${}", name),
+            Pattern::Literal { value, .. } => sexpr(value),
+            Pattern::Variant { enum_name, variant, fields, .. } => format!(
+                "{}.{}{}",
+                enum_name,
+                variant,
+                fields.iter().map(|f| format!(" {}", self::pattern(f))).collect::<String>()
+            ),
+        }
+    }
+
+    fn expr(source: &str) -> String {
+        let mut parser = Parser::new(source);
+        let expression = parser.parse_expression().expect("expression should parse");
+        assert!(parser.take_diagnostics().is_empty());
+        sexpr(&expression)
+    }
+
+    fn parse(source: &str) -> ASTNode {
+        let mut parser = Parser::new(source);
+        let ast = parser.parse_service();
+        let diagnostics = parser.take_diagnostics();
+        assert!(diagnostics.is_empty(), "unexpected diagnostics: {:?}", diagnostics);
+        ast.unwrap()
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_service_and_endpoints() {
+        let ast = parse(
+            r#"
+            /// User management.
+            /// Second line.
+            service Users {
+                /// Says hello.
+                @get("/hello")
+                endpoint hello(): String {
+                    /// Not attached to anything.
+                    return "hi";
+                }
+
+                // An ordinary comment
+                @get("/bye")
+                endpoint bye(): String { return "bye"; }
+            }
+            "#,
+        );
+
+        let ASTNode::Service { doc, endpoints, .. } = ast else { panic!("expected service") };
+        assert_eq!(doc.as_deref(), Some("User management.\nSecond line."));
+
+        let ASTNode::Endpoint { doc, .. } = &endpoints[0] else { panic!("expected endpoint") };
+        assert_eq!(doc.as_deref(), Some("Says hello."));
+
+        let ASTNode::Endpoint { doc, .. } = &endpoints[1] else { panic!("expected endpoint") };
+        assert_eq!(doc.as_deref(), None);
+    }
+
+    #[test]
+    fn test_comma_separated_parameters() {
+        let ast = parse(
+            r#"
+            service S {
+                @get("/add")
+                endpoint add(a: String, b: String,): String { return a; }
+            }
+            "#,
+        );
+
+        let ASTNode::Service { endpoints, .. } = ast else { panic!("expected service") };
+        let ASTNode::Endpoint { params, .. } = &endpoints[0] else { panic!("expected endpoint") };
+        let names: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_binary_precedence() {
+        assert_eq!(expr("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(expr("1 * 2 + 3 % 4"), "(+ (* 1 2) (% 3 4))");
+        assert_eq!(expr("a - b - c"), "(- (- a b) c)");
+        assert_eq!(expr("a || b && c == d"), "(|| a (&& b (== c d)))");
+        assert_eq!(expr("a < b == c >= d"), "(== (< a b) (>= c d))");
+        assert_eq!(expr("a ?? b ?? c"), "(?? a (?? b c))");
+        assert_eq!(expr("x ?? 0 > 5"), "(> (?? x 0) 5)");
+        assert_eq!(expr("0..n + 1"), "(.. 0 (+ n 1))");
+    }
+
+    #[test]
+    fn test_unary_and_grouping() {
+        assert_eq!(expr("-a * b"), "(* (- a) b)");
+        assert_eq!(expr("!!done"), "(! (! done))");
+        assert_eq!(expr("-(1 + 2)"), "(- (group (+ 1 2)))");
+        assert_eq!(expr("!a.ok"), "(! (. a ok))");
+    }
+
+    #[test]
+    fn test_postfix_chains() {
+        assert_eq!(expr("user.name.toUpper()"), "(call (. (. user name) toUpper))");
+        assert_eq!(expr("items[0].tags[i + 1]"), "([] (. ([] items 0) tags) (+ i 1))");
+        assert_eq!(expr("f(1, \"two\", g(x))(y)"), "(call (call f 1 \"two\" (call g x)) y)");
+        assert_eq!(expr("load(id)?.name ?? \"none\""), "(?? (. (? (call load id)) name) \"none\")");
+        assert_eq!(expr("throw Error(a?)"), "(throw (call Error (? a)))");
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(expr("[1, true, null, 2.5,]"), "[1 true null 2.5]");
+        assert_eq!(expr("[]"), "[]");
+        assert_eq!(expr("{ name: \"a\", \"quoted key\": [b], }"), "{name: \"a\" quoted key: [b]}");
+        assert_eq!(expr("{}"), "{}");
+    }
+
+    #[test]
+    fn test_interpolated_strings() {
+        assert_eq!(
+            expr(r#""Hi \(user.name.toUpper())! \(a + b)\(c)""#),
+            r#"(str "Hi " (call (. (. user name) toUpper)) "! " (+ a b) c)"#
+        );
+        assert_eq!(expr(r#""\(P { x: "\(y)" })" + z"#), r#"(+ (str P {x: (str y)}) z)"#);
+
+        let mut parser = Parser::new(r#""\(a b)""#);
+        let error = parser.parse_expression().unwrap_err();
+        assert_eq!(error.message, "expected `)`, found identifier `b`");
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let mut parser = Parser::new("1 + * 2");
+        let error = parser.parse_expression().unwrap_err();
+        assert_eq!(error.code, "E0104");
+        assert!(error.message.contains("found `*`"));
+
+        let mut parser = Parser::new("f(1, 2");
+        assert!(parser.parse_expression().unwrap_err().message.contains("expected `)`"));
+    }
+
+    #[test]
+    fn test_expression_spans() {
+        let source = "a.b(c) + [1, 2][0]";
+        let mut parser = Parser::new(source);
+        let expression = parser.parse_expression().unwrap();
+        let ASTNode::BinaryExpression { left, right, span, .. } = expression else { panic!("expected binary") };
+        assert_eq!(&source[span.start..span.end], source);
+        assert_eq!(&source[left.span().start..left.span().end], "a.b(c)");
+        assert_eq!(&source[right.span().start..right.span().end], "[1, 2][0]");
+    }
+
+    fn block(source: &str) -> Vec<ASTNode> {
+        let mut parser = Parser::new(source);
+        let ASTNode::Block { statements, .. } = parser.parse_block().expect("block should parse") else {
+            panic!("expected block")
+        };
+        assert!(parser.take_diagnostics().is_empty());
+        statements
+    }
+
+    #[test]
+    fn test_variable_declarations() {
+        let statements = block("{ let a: Int = 1; var b; var c: String = \"x\"; }");
+        let ASTNode::VariableDeclaration { name, mutable, type_annotation, initializer, .. } = &statements[0] else {
+            panic!("expected declaration")
+        };
+        assert_eq!((name.as_str(), *mutable, type_annotation.as_ref().map(|t| t.to_string())), ("a", false, Some("Int".to_string())));
+        assert_eq!(sexpr(initializer.as_ref().unwrap()), "1");
+        assert!(matches!(&statements[1], ASTNode::VariableDeclaration { mutable: true, initializer: None, .. }));
+        assert!(matches!(&statements[2], ASTNode::VariableDeclaration { mutable: true, initializer: Some(_), .. }));
+    }
+
+    #[test]
+    fn test_generic_and_optional_types() {
+        let statements = block("{ var a: List<Int>; var b: Map<String, List<Float?>>?; var c: Point?; }");
+        let types: Vec<String> = statements
+            .iter()
+            .map(|statement| match statement {
+                ASTNode::VariableDeclaration { type_annotation: Some(annotation), .. } => annotation.to_string(),
+                other => panic!("expected declaration, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(types, ["List<Int>", "Map<String, List<Float?>>?", "Point?"]);
+
+        let ASTNode::VariableDeclaration { type_annotation: Some(map), .. } = &statements[1] else {
+            unreachable!()
+        };
+        assert!(map.optional && !map.arguments[0].optional);
+        assert_eq!(map.arguments[1].arguments[0].name, "Float");
+
+        let mut parser = Parser::new("{ var a: List<Int; }");
+        parser.parse_block().unwrap();
+        let errors = parser.take_diagnostics();
+        assert_eq!(errors[0].message, "expected `>`, found `;`");
+    }
+
+    #[test]
+    fn test_assignments_and_expression_statements() {
+        let statements = block("{ x = 1; x += 2; a.b[0] *= 3; f(x); }");
+        let rendered: Vec<String> = statements
+            .iter()
+            .map(|statement| match statement {
+                ASTNode::Assignment { target, operator: None, value, .. } => {
+                    format!("{} = {}", sexpr(target), sexpr(value))
+                }
+                ASTNode::Assignment { target, operator: Some(op), value, .. } => {
+                    format!("{} {}= {}", sexpr(target), op, sexpr(value))
+                }
+                ASTNode::ExpressionStatement { expression, .. } => sexpr(expression),
+                other => panic!("unexpected statement: {:?}", other),
+            })
+            .collect();
+        assert_eq!(rendered, ["x = 1", "x += 2", "([] (. a b) 0) *= 3", "(call f x)"]);
+    }
+
+    #[test]
+    fn test_statement_errors() {
+        let mut parser = Parser::new("{ 1 = 2; let x; }");
+        parser.parse_block().unwrap();
+        let codes: Vec<&str> = parser.take_diagnostics().iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["E0105", "E0100"]);
+    }
+
+    #[test]
+    fn test_control_flow_statements() {
+        let statements = block(
+            "{ if a { x = 1; } else if b { x = 2; } else { x = 3; } while i < 3 { i += 1; continue; } for v in 0..n { break; } }",
+        );
+        let ASTNode::IfStatement { condition, else_branch: Some(else_branch), .. } = &statements[0] else {
+            panic!("expected if")
+        };
+        assert_eq!(sexpr(condition), "a");
+        let ASTNode::IfStatement { condition, else_branch: Some(last), .. } = else_branch.as_ref() else {
+            panic!("expected else if")
+        };
+        assert_eq!(sexpr(condition), "b");
+        assert!(matches!(last.as_ref(), ASTNode::Block { .. }));
+
+        let ASTNode::WhileStatement { condition, body, .. } = &statements[1] else { panic!("expected while") };
+        assert_eq!(sexpr(condition), "(< i 3)");
+        assert!(matches!(body.as_ref(), ASTNode::Block { statements, .. } if matches!(statements[1], ASTNode::ContinueStatement { .. })));
+
+        let ASTNode::ForStatement { variable, iterable, .. } = &statements[2] else { panic!("expected for") };
+        assert_eq!((variable.as_str(), sexpr(iterable).as_str()), ("v", "(.. 0 n)"));
+    }
+
+    #[test]
+    fn test_loop_control_outside_loop() {
+        let mut parser = Parser::new("{ break; while true { if x { continue; } } continue; }");
+        parser.parse_block().unwrap();
+        let diagnostics = parser.take_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, "E0106");
+        assert_eq!(diagnostics[0].message, "`break` outside of a loop");
+        assert_eq!(diagnostics[1].message, "`continue` outside of a loop");
+    }
+
+    #[test]
+    fn test_function_declarations() {
+        let mut parser = Parser::new(
+            r#"
+            /// Shared greeting.
+            pub fn greet(name: String): String {
+                return "Hello, \(name)!";
+            }
+
+            service Greeter {
+                fn log(message: String) {
+                    if message == "" { return; }
+                    print(message);
+                }
+
+                @get("/hello")
+                endpoint hello(name: String): String {
+                    return greet(name);
+                }
+            }
+            "#,
+        );
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        assert_eq!(items.len(), 2);
+
+        let ASTNode::Function { name, params, return_type, public, doc, .. } = &items[0] else {
+            panic!("expected function")
+        };
+        assert_eq!((name.as_str(), *public, return_type.as_ref().map(|t| t.to_string())), ("greet", true, Some("String".to_string())));
+        assert_eq!(params[0].param_type.to_string(), "String");
+        assert_eq!(doc.as_deref(), Some("Shared greeting."));
+
+        let ASTNode::Service { functions, endpoints, .. } = &items[1] else { panic!("expected service") };
+        assert_eq!(endpoints.len(), 1);
+        assert!(matches!(
+            &functions[0],
+            ASTNode::Function { name, public: false, return_type: None, .. } if name == "log"
+        ));
+        let ASTNode::Function { body, .. } = &functions[0] else { panic!("expected function") };
+        let ASTNode::Block { statements, .. } = &**body else { panic!("expected block") };
+        let ASTNode::IfStatement { then_branch, .. } = &statements[0] else { panic!("expected if") };
+        assert!(matches!(
+            &**then_branch,
+            ASTNode::Block { statements, .. } if matches!(statements[..], [ASTNode::ReturnStatement { expression: None, .. }])
+        ));
+    }
+
+    #[test]
+    fn test_multiple_services_and_items() {
+        let mut parser = Parser::new(
+            r#"
+            const VERSION: String = "1.0";
+
+            /// Users.
+            @base("/api/users")
+            service Users {
+                const PAGE_SIZE = 20;
+
+                @get("/")
+                endpoint list(): Object { return []; }
+            }
+
+            service Health {
+                @get("/ping")
+                endpoint ping(): String { return VERSION; }
+            }
+
+            pub fn helper(): Int { return 1; }
+            "#,
+        );
+        let ASTNode::Program { module, items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        assert_eq!(module, None);
+        assert_eq!(items.len(), 4);
+        assert!(matches!(&items[0], ASTNode::Constant { name, type_annotation: Some(_), .. } if name == "VERSION"));
+
+        let ASTNode::Service { name, base_path, constants, doc, .. } = &items[1] else { panic!("expected service") };
+        assert_eq!((name.as_str(), base_path.as_deref()), ("Users", Some("/api/users")));
+        assert_eq!(doc.as_deref(), Some("Users."));
+        assert_eq!(constants.len(), 1);
+        assert!(matches!(&items[2], ASTNode::Service { base_path: None, .. }));
+        assert!(matches!(&items[3], ASTNode::Function { public: true, .. }));
+    }
+
+    #[test]
+    fn test_module_root() {
+        let mut parser = Parser::new("module greeter {\n    pub fn greet(name: String): String {\n        return name;\n    }\n}\n");
+        let ASTNode::Program { module, items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        assert_eq!(module.as_deref(), Some("greeter"));
+        assert!(matches!(&items[0], ASTNode::Function { name, .. } if name == "greet"));
+
+        let mut parser = Parser::new("module m { fn f() { } } fn g() { }");
+        parser.parse_program();
+        assert_eq!(parser.take_diagnostics()[0].message, "expected end of file, found `fn`");
+    }
+
+    #[test]
+    fn test_program_errors() {
+        let mut parser = Parser::new("service A { } } 42 @get(\"/x\") service B { } fn f( { } fn g() { break; }");
+        parser.parse_program();
+        let codes: Vec<&str> = parser.take_diagnostics().iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["E0102", "E0102", "E0108", "E0100", "E0106"]);
+    }
+
+    #[test]
+    fn test_class_declarations() {
+        let mut parser = Parser::new(
+            r#"
+            /// A pet.
+            pub class Dog extends Animal {
+                tricks: List<String> = [];
+                var_name: String;
+
+                init(name: String) {
+                    super.init(name);
+                }
+
+                /// Says hello.
+                fn speak(): String {
+                    return super.speak() + "!";
+                }
+
+                static fn create(): Dog {
+                    return Dog("Rex");
+                }
+            }
+            "#,
+        );
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+
+        let ASTNode::Class { name, superclass, fields, constructor, methods, static_methods, public, doc, .. } =
+            &items[0]
+        else {
+            panic!("expected class")
+        };
+        assert_eq!((name.as_str(), superclass.as_deref(), *public), ("Dog", Some("Animal"), true));
+        assert_eq!(doc.as_deref(), Some("A pet."));
+        let fields: Vec<(&str, String, bool)> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.field_type.to_string(), f.default.is_some()))
+            .collect();
+        assert_eq!(fields, [("tricks", "List<String>".to_string(), true), ("var_name", "String".to_string(), false)]);
+        assert!(matches!(constructor.as_deref(), Some(ASTNode::Function { name, params, .. }) if name == "init" && params.len() == 1));
+        assert!(matches!(&methods[0], ASTNode::Function { name, doc: Some(_), .. } if name == "speak"));
+        assert!(matches!(&static_methods[0], ASTNode::Function { name, .. } if name == "create"));
+
+        assert_eq!(expr("super.speak()"), "(call (. super speak))");
+    }
+
+    #[test]
+    fn test_class_errors() {
+        let mut parser = Parser::new(
+            "class A { init() { } init(x: Int) { } 42 x: Int; } service S { class B { } }",
+        );
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        let codes: Vec<&str> = parser.take_diagnostics().iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["E0110", "E0102", "E0109"]);
+        let ASTNode::Class { fields, .. } = &items[0] else { panic!("expected class") };
+        assert_eq!(fields.len(), 1);
+    }
+
+    #[test]
+    fn test_struct_declarations_and_literals() {
+        let mut parser = Parser::new(
+            "/// A user.\nexport struct User { name: String; age: Int = 0; } record Tag { label: String?; 1; }",
+        );
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        let codes: Vec<&str> = parser.take_diagnostics().iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["E0101"]);
+        let ASTNode::Struct { name, fields, public, doc, .. } = &items[0] else { panic!("expected struct") };
+        assert_eq!((name.as_str(), *public, doc.as_deref()), ("User", true, Some("A user.")));
+        let fields: Vec<(&str, String, bool)> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.field_type.to_string(), f.default.is_some()))
+            .collect();
+        assert_eq!(fields, [("name", "String".to_string(), false), ("age", "Int".to_string(), true)]);
+        assert!(matches!(&items[1], ASTNode::Struct { name, fields, .. } if name == "Tag" && fields.len() == 1));
+
+        assert_eq!(expr("User { name: \"a\", age: 1, }"), "User {name: \"a\" age: 1}");
+        assert_eq!(expr("f(P {}, [Q { x: 1 }])"), "(call f P {} [Q {x: 1}])");
+
+        // The brace after an `if` or `while` condition opens its body
+        let statements = block("{ if ready { } while (P { x: 1 }).x > 0 { } for p in ps { } }");
+        assert!(matches!(&statements[0], ASTNode::IfStatement { condition, .. } if sexpr(condition) == "ready"));
+        assert!(matches!(&statements[1], ASTNode::WhileStatement { condition, .. } if sexpr(condition) == "(> (. (group P {x: 1}) x) 0)"));
+        assert!(matches!(&statements[2], ASTNode::ForStatement { iterable, .. } if sexpr(iterable) == "ps"));
+    }
+
+    #[test]
+    fn test_enum_declarations_and_match() {
+        let mut parser = Parser::new("pub enum Status { Pending, Failed(reason: String, code: Int), } enum Empty { }");
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        let ASTNode::Enum { name, variants, public, .. } = &items[0] else { panic!("expected enum") };
+        assert_eq!((name.as_str(), *public), ("Status", true));
+        let variants: Vec<(&str, usize)> = variants.iter().map(|v| (v.name.as_str(), v.fields.len())).collect();
+        assert_eq!(variants, [("Pending", 0), ("Failed", 2)]);
+        assert!(matches!(&items[1], ASTNode::Enum { variants, .. } if variants.is_empty()));
+
+        assert_eq!(
+            expr("match s { Status.Failed(_, 500) => 1, Status.Done(at) => at, -1 => 2, \"x\" => 3, _ => 4 }"),
+            "(match s Status.Failed _ 500 => 1 Status.Done $at => at -1 => 2 \"x\" => 3 _ => 4)"
+        );
+        assert_eq!(expr("match (S { a: 1 }) { true => null, null => x, }"), "(match (group S {a: 1}) true => null null => x)");
+
+        // As a statement, arms may be blocks and need no comma after them
+        let statements = block("{ match n { 0 => { return 1; } _ => f(n), } x = 1; }");
+        let ASTNode::Match { arms, .. } = &statements[0] else { panic!("expected match") };
+        assert!(matches!(arms[0].body, ASTNode::Block { .. }));
+        assert_eq!(sexpr(&arms[1].body), "(call f n)");
+        assert!(matches!(statements[1], ASTNode::Assignment { .. }));
+
+        let mut parser = Parser::new("fn f() { match x { + => 1, y => , _ => 3 } }");
+        parser.parse_program();
+        let codes: Vec<&str> = parser.take_diagnostics().iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["E0111", "E0104"]);
+    }
+
+    #[test]
+    fn test_type_parameters() {
+        let mut parser = Parser::new(
+            "fn first<T>(items: List<T>): T? { } class Sorted<T: Comparable + Named, U> { } struct Page<T> { items: List<T>; } enum Result<T,> { Ok(value: T) }",
+        );
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        let names = |item: &ASTNode| -> Vec<String> {
+            let (ASTNode::Function { type_params, .. }
+            | ASTNode::Class { type_params, .. }
+            | ASTNode::Struct { type_params, .. }
+            | ASTNode::Enum { type_params, .. }) = item
+            else {
+                panic!("expected a generic declaration")
+            };
+            type_params.iter().map(|param| param.name.clone()).collect()
+        };
+        assert_eq!(names(&items[0]), ["T"]);
+        assert_eq!(names(&items[1]), ["T", "U"]);
+        assert_eq!(names(&items[2]), ["T"]);
+        assert_eq!(names(&items[3]), ["T"]);
+        let ASTNode::Class { type_params, .. } = &items[1] else { panic!("expected class") };
+        let bounds: Vec<String> = type_params[0].bounds.iter().map(ToString::to_string).collect();
+        assert_eq!(bounds, ["Comparable", "Named"]);
+
+        let mut parser = Parser::new("fn f<T:>() { } fn g<T U>() { }");
+        parser.parse_program();
+        let messages: Vec<String> = parser.take_diagnostics().iter().map(|d| d.message.clone()).collect();
+        assert_eq!(messages, ["expected a type, found `>`", "expected `>`, found identifier `U`"]);
+    }
+
+    #[test]
+    fn test_interface_declarations() {
+        let mut parser = Parser::new(
+            "interface Shape { fn area(): Float; fn scale(by: Float); } class Sq extends Base implements Shape, Named { }",
+        );
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+
+        let ASTNode::Interface { name, methods, .. } = &items[0] else { panic!("expected interface") };
+        assert_eq!(name, "Shape");
+        assert_eq!((methods[0].name.as_str(), methods[0].return_type.as_ref().map(|t| t.to_string())), ("area", Some("Float".to_string())));
+        assert_eq!((methods[1].params.len(), methods[1].return_type.is_some()), (1, false));
+
+        let ASTNode::Class { superclass, interfaces, .. } = &items[1] else { panic!("expected class") };
+        assert_eq!(superclass.as_deref(), Some("Base"));
+        assert_eq!(interfaces, &["Shape", "Named"]);
+
+        let mut parser = Parser::new("interface I { fn a() { } fn b(); }");
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert_eq!(parser.take_diagnostics()[0].message, "expected `;`, found `{`");
+        assert!(matches!(&items[0], ASTNode::Interface { methods, .. } if methods.len() == 1));
+    }
+
+    #[test]
+    fn test_imports_and_exports() {
+        let mut parser = Parser::new("import { a, B, } from \"./lib.ath\";\nexport fn a() { }\nexport class B { }");
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+
+        let ASTNode::Import { names, path, .. } = &items[0] else { panic!("expected import") };
+        let names: Vec<&str> = names.iter().map(|name| name.name.as_str()).collect();
+        assert_eq!((names, path.as_str()), (vec!["a", "B"], "./lib.ath"));
+        assert!(matches!(&items[1], ASTNode::Function { public: true, .. }));
+        assert!(matches!(&items[2], ASTNode::Class { public: true, .. }));
+
+        for (source, message) in [
+            ("import { } from \"./a.ath\";", "expected imported name, found `}`"),
+            ("import { a } \"./a.ath\";", "expected `from`, found string literal"),
+            ("import { a } from a;", "expected module path string, found identifier `a`"),
+        ] {
+            let mut parser = Parser::new(source);
+            parser.parse_program();
+            assert_eq!(parser.take_diagnostics()[0].message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_try_catch_and_throw() {
+        let mut parser = Parser::new("{ try { throw Error(\"x\"); } catch (e: NotFound) { } catch (e) { } }");
+        let ASTNode::Block { statements, .. } = parser.parse_block().unwrap() else { panic!("expected block") };
+        assert!(parser.take_diagnostics().is_empty());
+
+        let ASTNode::TryStatement { body, catches, .. } = &statements[0] else { panic!("expected try") };
+        let ASTNode::Block { statements, .. } = &**body else { panic!("expected block") };
+        assert!(matches!(
+            &statements[0],
+            ASTNode::ExpressionStatement { expression, .. } if matches!(**expression, ASTNode::ThrowExpression { .. })
+        ));
+        let clauses: Vec<(&str, Option<String>)> = catches
+            .iter()
+            .map(|clause| (clause.name.as_str(), clause.error_type.as_ref().map(ToString::to_string)))
+            .collect();
+        assert_eq!(clauses, [("e", Some("NotFound".to_string())), ("e", None)]);
+
+        let mut parser = Parser::new("fn f() { try { } return; }");
+        parser.parse_program();
+        let diagnostics = parser.take_diagnostics();
+        assert_eq!(diagnostics[0].message, "expected `catch`, found `return`");
+        assert_eq!(diagnostics[0].notes, ["a `try` block needs at least one `catch` clause"]);
+    }
+
+    #[test]
+    fn test_defer_statements() {
+        let mut parser = Parser::new("{ defer close(file); defer { log(\"done\"); } }");
+        let ASTNode::Block { statements, .. } = parser.parse_block().unwrap() else { panic!("expected block") };
+        assert!(parser.take_diagnostics().is_empty());
+        assert!(matches!(
+            &statements[0],
+            ASTNode::DeferStatement { statement, .. } if matches!(**statement, ASTNode::ExpressionStatement { .. })
+        ));
+        assert!(matches!(
+            &statements[1],
+            ASTNode::DeferStatement { statement, .. } if matches!(**statement, ASTNode::Block { .. })
+        ));
+
+        // A deferred statement cannot leave its block, though a loop inside it may be left
+        let mut parser = Parser::new(
+            "fn f() { while true { defer { for x in xs { break; } } defer break; } defer { return 1; } }",
+        );
+        parser.parse_program();
+        let diagnostics = parser.take_diagnostics();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, ["`break` in a deferred statement", "`return` in a deferred statement"]);
+        assert_eq!(diagnostics[0].code, "E0112");
+    }
+
+    #[test]
+    fn test_async_functions_and_await() {
+        let mut parser = Parser::new("async fn f(): Int { } class C { async fn m() { } static async fn s() { } }");
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        assert!(matches!(&items[0], ASTNode::Function { is_async: true, .. }));
+        let ASTNode::Class { methods, static_methods, .. } = &items[1] else { panic!("expected class") };
+        assert!(matches!(&methods[0], ASTNode::Function { is_async: true, .. }));
+        assert!(matches!(&static_methods[0], ASTNode::Function { is_async: true, .. }));
+
+        assert_eq!(expr("await a() + await b.c()"), "(+ (await (call a)) (await (call (. b c))))");
+        assert_eq!(expr("await all([a(), b()])?"), "(await (? (call all [(call a) (call b)])))");
+
+        let mut parser = Parser::new("async const X = 1;");
+        parser.parse_program();
+        assert_eq!(parser.take_diagnostics()[0].message, "expected `fn`, found `const`");
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::lexer::{Lexer, TokenType};
+    use crate::compiler::parser::{ASTNode, Parser};
+    use crate::compiler::diagnostics::{Diagnostic, Label};
+    use crate::compiler::{prelude, Compiler, SourceMap, Span};
+    use std::io::Write;
+
+    #[test]
+    fn test_location_lookup() {
+        let mut source_map = SourceMap::new();
+        let first = source_map.add_file("a.ath", "one\ntwo\n");
+        let second = source_map.add_file("b.ath", "héllo wörld");
+
+        let location = source_map.location(first, 5);
+        assert_eq!((location.file, location.line, location.column), ("a.ath", 2, 2));
+
+        // Columns count characters, not bytes
+        let location = source_map.location(second, "héllo w".len());
+        assert_eq!((location.file, location.line, location.column), ("b.ath", 1, 8));
+
+        assert_eq!(source_map.file(first).line_text(2), "two");
+        assert_eq!(source_map.file(first).line_text(9), "");
+        assert_eq!(source_map.snippet(Span::new(second, 0, "héllo".len())), "héllo");
+    }
+
+    #[test]
+    fn test_prelude_spans_resolve() {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file("a.ath", "throw Error(1);\n");
+        let error_class = prelude::items()
+            .iter()
+            .find(|item| matches!(item, ASTNode::Class { name, .. } if name == "Error"))
+            .expect("the prelude declares Error");
+
+        let location = source_map.span_start(error_class.span());
+        assert_eq!((location.file, location.line), ("<prelude>", 2));
+        assert!(source_map.snippet(error_class.span()).starts_with("pub class Error {"));
+
+        // A label in the prelude renders instead of panicking
+        let diagnostic = Diagnostic::error("E9999", "bad error", Label::new(Span::new(file_id, 6, 11)))
+            .with_secondary(Label::new(error_class.span()).with_message("declared here"));
+        assert!(diagnostic.render(&source_map).contains("2 | pub class Error {"));
+    }
+
+    #[test]
+    fn test_span_merge() {
+        let span = Span::new(0, 4, 8).to(Span::new(0, 10, 12));
+        assert_eq!(span, Span::new(0, 4, 12));
+        assert_eq!(span.len(), 8);
+        assert!(Span::new(0, 3, 3).is_empty());
+    }
+
+    #[test]
+    fn test_token_spans_are_byte_offsets() {
+        let source = "service Café {\n  @get(\"/x\")\n}";
+        let mut lexer = Lexer::with_file_id(source, 3);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if token.token_type == TokenType::EOF {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        for token in &tokens {
+            assert_eq!(token.span.file_id, 3);
+        }
+        assert_eq!(&source[tokens[1].span.start..tokens[1].span.end], "Café");
+        assert_eq!(&source[tokens[2].span.start..tokens[2].span.end], "{");
+        assert_eq!(&source[tokens[6].span.start..tokens[6].span.end], "\"/x\"");
+        assert_eq!((tokens[3].line, tokens[3].column), (2, 2));
+    }
+
+    #[test]
+    fn test_ast_nodes_carry_spans() {
+        let source = "service S {\n    @get(\"/a\")\n    endpoint a(name: String): String {\n        return name;\n    }\n}";
+        let mut parser = Parser::new(source);
+        let service = parser.parse_service().unwrap();
+        assert!(parser.take_diagnostics().is_empty());
+
+        let snippet = |span: Span| &source[span.start..span.end];
+        assert_eq!(snippet(service.span()), source);
+
+        let ASTNode::Service { endpoints, .. } = &service else { panic!("expected service") };
+        let endpoint = &endpoints[0];
+        assert!(snippet(endpoint.span()).starts_with("@get(\"/a\")"));
+        assert!(snippet(endpoint.span()).ends_with('}'));
+
+        let ASTNode::Endpoint { params, body, .. } = endpoint else { panic!("expected endpoint") };
+        assert_eq!(snippet(params[0].span), "name: String");
+
+        let ASTNode::Block { statements, .. } = &**body else { panic!("expected block") };
+        assert_eq!(snippet(statements[0].span()), "return name;");
+        let ASTNode::ReturnStatement { expression: Some(expression), .. } = &statements[0] else {
+            panic!("expected return")
+        };
+        assert_eq!(snippet(expression.span()), "name");
+    }
+
+    #[test]
+    fn test_from_file_registers_file_name() {
+        let mut file = tempfile::Builder::new().suffix(".ath").tempfile().unwrap();
+        write!(file, "service S {{\n    @get(\"/\")\n    endpoint e(): String {{ return ]; }}\n}}").unwrap();
+
+        let compiler = Compiler::from_file(file.path()).unwrap();
+        let errors = compiler.compile().unwrap_err();
+        let location = compiler.source_map.span_start(errors[0].primary.span);
+        assert_eq!(location.file, file.path().display().to_string());
+        assert_eq!((location.line, location.column), (3, 35));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::parser::TypeExpr;
+
+/// The static type of a value, as the checker sees it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+    /// What a function without a return type produces.
+    Void,
+    /// The type of `null`.
+    Null,
+    List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    /// `T?`: either a `T` or `null`.
+    Optional(Box<Type>),
+    /// A `T` being computed concurrently, as an `async` function's call
+    /// gives; `await` waits for it.
+    Future(Box<Type>),
+    /// A class, interface, struct or enum, by name, with the type
+    /// arguments of a generic one.
+    Named(String, Vec<Type>),
+    /// A type parameter of the generic declaration being checked.
+    Param(String),
+    /// Checked only at run time: values declared `dynamic` (or `any`), and
+    /// expressions whose type could not be worked out. Compatible with
+    /// every type, so that one error does not cascade into many.
+    Dynamic,
+}
+
+impl Type {
+    /// The number of type arguments the built-in type `name` takes, or
+    /// `None` if `name` is not built in. `Number` is an alias of `Float`,
+    /// `any` of `dynamic`, and `Object` of `Map<String, dynamic>`.
+    pub fn builtin_arity(name: &str) -> Option<usize> {
+        match name {
+            "Int" | "Float" | "Number" | "Bool" | "String" | "Void" | "Object" | "dynamic" | "any" => Some(0),
+            "List" | "Future" => Some(1),
+            "Map" => Some(2),
+            _ => None,
+        }
+    }
+
+    /// A user type without type arguments.
+    pub fn named(name: impl Into<String>) -> Type {
+        Type::Named(name.into(), Vec::new())
+    }
+
+    /// The type `annotation` spells, taking any name that is not built in
+    /// to be a user type. Missing type arguments are dynamic.
+    pub fn from_annotation(annotation: &TypeExpr) -> Type {
+        Type::from_annotation_in(annotation, &[])
+    }
+
+    /// Like `from_annotation`, but in the scope of the type parameters
+    /// `params`, which the names in `annotation` may refer to.
+    pub fn from_annotation_in(annotation: &TypeExpr, params: &[&str]) -> Type {
+        let argument = |index: usize| {
+            Box::new(
+                annotation
+                    .arguments
+                    .get(index)
+                    .map_or(Type::Dynamic, |argument| Type::from_annotation_in(argument, params)),
+            )
+        };
+        let ty = match annotation.name.as_str() {
+            "Int" => Type::Int,
+            "Float" | "Number" => Type::Float,
+            "Bool" => Type::Bool,
+            "String" => Type::String,
+            "Void" => Type::Void,
+            "dynamic" | "any" => Type::Dynamic,
+            "Object" => Type::Map(Box::new(Type::String), Box::new(Type::Dynamic)),
+            "List" => Type::List(argument(0)),
+            "Map" => Type::Map(argument(0), argument(1)),
+            "Future" => Type::Future(argument(0)),
+            name if annotation.arguments.is_empty() && params.contains(&name) => Type::Param(name.to_string()),
+            name => Type::Named(
+                name.to_string(),
+                annotation
+                    .arguments
+                    .iter()
+                    .map(|argument| Type::from_annotation_in(argument, params))
+                    .collect(),
+            ),
+        };
+        if annotation.optional {
+            ty.optional()
+        } else {
+            ty
+        }
+    }
+
+    /// This type with each type parameter replaced by its binding, or by
+    /// `dynamic` if it has none.
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        let substitute = |ty: &Type| Box::new(ty.substitute(bindings));
+        match self {
+            Type::Param(name) => bindings.get(name).cloned().unwrap_or(Type::Dynamic),
+            Type::List(element) => Type::List(substitute(element)),
+            Type::Map(key, value) => Type::Map(substitute(key), substitute(value)),
+            Type::Future(value) => Type::Future(substitute(value)),
+            Type::Optional(inner) => inner.substitute(bindings).optional(),
+            Type::Named(name, arguments) => {
+                Type::Named(name.clone(), arguments.iter().map(|argument| argument.substitute(bindings)).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// This type, made to also accept `null`.
+    pub fn optional(self) -> Type {
+        match self {
+            Type::Optional(_) | Type::Null | Type::Dynamic => self,
+            other => Type::Optional(Box::new(other)),
+        }
+    }
+
+    /// This type without `null`: `T` for `T?`.
+    pub fn non_null(&self) -> &Type {
+        match self {
+            Type::Optional(inner) => inner,
+            other => other,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int | Type::Float | Type::Dynamic)
+    }
+
+    /// The narrowest type covering both `self` and `other`, as for the
+    /// elements of a list literal.
+    pub fn join(self, other: Type) -> Type {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Type::Int, Type::Float) | (Type::Float, Type::Int) => Type::Float,
+            (Type::Null, other) | (other, Type::Null) => other.optional(),
+            (Type::Optional(a), b) | (b, Type::Optional(a)) => (*a).join(b).optional(),
+            _ => Type::Dynamic,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Bool => write!(f, "Bool"),
+            Type::String => write!(f, "String"),
+            Type::Void => write!(f, "Void"),
+            Type::Null => write!(f, "null"),
+            Type::List(element) => write!(f, "List<{}>", element),
+            Type::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Future(value) => write!(f, "Future<{}>", value),
+            Type::Named(name, arguments) if arguments.is_empty() => write!(f, "{}", name),
+            Type::Named(name, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(ToString::to_string).collect();
+                write!(f, "{}<{}>", name, arguments.join(", "))
+            }
+            Type::Param(name) => write!(f, "{}", name),
+            Type::Dynamic => write!(f, "dynamic"),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::fmt;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AetherList<T> {
     items: Vec<T>,
 }
@@ -30,6 +30,44 @@ impl<T> AetherList<T> {
     pub fn clear(&mut self) {
         self.items.clear();
     }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.items.get_mut(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+}
+
+impl<T> FromIterator<T> for AetherList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            items: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> IntoIterator for AetherList<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AetherList<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -37,6 +75,12 @@ pub struct AetherMap<K, V> {
     items: HashMap<K, V>,
 }
 
+impl<T> Default for AetherList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<K, V> AetherMap<K, V>
 where
     K: std::hash::Hash + Eq,
@@ -74,6 +118,55 @@ where
     pub fn clear(&mut self) {
         self.items.clear();
     }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.items.get_mut(key)
+    }
+
+    /// The value for `key`, inserting `default()` first if there is none.
+    pub fn get_or_insert_with(&mut self, key: K, default: impl FnOnce() -> V) -> &mut V {
+        self.items.entry(key).or_insert_with(default)
+    }
+
+    /// Keys in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.items.keys()
+    }
+
+    /// Entries in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.items.iter()
+    }
+}
+
+impl<K, V> PartialEq for AetherMap<K, V>
+where
+    K: std::hash::Hash + Eq,
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for AetherMap<K, V>
+where
+    K: std::hash::Hash + Eq,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            items: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<K, V> Default for AetherMap<K, V>
+where
+    K: std::hash::Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: fmt::Display> fmt::Display for AetherList<T> {
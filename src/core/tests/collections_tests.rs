@@ -144,4 +144,28 @@ mod tests {
         map.remove(&key);
         assert_eq!(map.get(&key), None);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_list_access_and_iteration() {
+        let mut list: AetherList<i32> = (1..=3).collect();
+        assert_eq!(list.get(1), Some(&2));
+        assert_eq!(list.get(3), None);
+
+        *list.get_mut(0).unwrap() = 10;
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [10, 2, 3]);
+        assert_eq!(list.into_iter().sum::<i32>(), 15);
+    }
+
+    #[test]
+    fn test_map_access_and_iteration() {
+        let mut map: AetherMap<String, i32> = [("a".to_string(), 1), ("b".to_string(), 2)].into_iter().collect();
+        *map.get_mut(&"a".to_string()).unwrap() += 10;
+        *map.get_or_insert_with("c".to_string(), || 0) += 3;
+
+        let mut keys: Vec<&String> = map.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["a", "b", "c"]);
+        assert_eq!(map.iter().map(|(_, value)| value).sum::<i32>(), 16);
+        assert_eq!(map, [("a", 11), ("b", 2), ("c", 3)].into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+    }
+}
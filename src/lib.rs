@@ -28,7 +28,10 @@ impl Aether {
 
     /// Compile and run an Aether program
     pub async fn run(&self) -> Result<(), String> {
-        let ast = self.compiler.compile()?;
+        let ast = self
+            .compiler
+            .compile()
+            .map_err(|diagnostics| self.compiler.render_diagnostics(&diagnostics))?;
         let runtime = Runtime::new(ast, self.port);
         runtime.start().await
     }
@@ -37,7 +40,6 @@ impl Aether {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use reqwest;
     use serde_json::Value;
 
     #[tokio::test]
@@ -57,7 +59,7 @@ mod tests {
         });
 
         // Send a request to the /hello endpoint
-        let response = reqwest::get("http://localhost:8081/hello").await.unwrap();
+        let response = reqwest::get("http://localhost:8081/hello?name=World").await.unwrap();
         let body: Value = response.json().await.unwrap();
 
         // Extract the "data" field from the response
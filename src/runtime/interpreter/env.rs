@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::evaluate;
+use super::value::{Class, Value};
+use crate::compiler::parser::{ASTNode, EnumVariant, Field, TypeParameter};
+use crate::compiler::prelude;
+
+#[derive(Debug, Clone)]
+pub(super) struct Binding {
+    /// `None` until a `var` declared without an initializer is assigned.
+    value: Option<Value>,
+    mutable: bool,
+}
+
+/// Functions, classes, structs, enums and constants visible from anywhere
+/// in a service.
+#[derive(Debug, Clone, Default)]
+pub struct Globals {
+    pub functions: HashMap<String, ASTNode>,
+    pub classes: HashMap<String, Arc<Class>>,
+    /// The fields of each struct, in declaration order.
+    pub structs: HashMap<String, Vec<Field>>,
+    /// The variants of each enum, in declaration order.
+    pub enums: HashMap<String, Vec<EnumVariant>>,
+    /// The type parameters of each generic struct and enum.
+    pub type_params: HashMap<String, Vec<TypeParameter>>,
+    pub constants: HashMap<String, Value>,
+}
+
+impl Globals {
+    /// Collect the functions and classes among `items`, after those of the
+    /// prelude, and evaluate their constants in declaration order. Later
+    /// declarations shadow earlier ones.
+    pub fn from_items<'a>(items: impl IntoIterator<Item = &'a ASTNode>) -> Result<Self, String> {
+        let mut globals = Globals::default();
+        let mut classes = HashMap::new();
+        let mut constants = Vec::new();
+        for item in prelude::items().iter().chain(items) {
+            match item {
+                ASTNode::Function { name, .. } => {
+                    globals.functions.insert(name.clone(), item.clone());
+                }
+                ASTNode::Class { name, .. } => {
+                    classes.insert(name.as_str(), item);
+                }
+                ASTNode::Struct { name, type_params, fields, .. } => {
+                    globals.structs.insert(name.clone(), fields.clone());
+                    globals.type_params.insert(name.clone(), type_params.clone());
+                }
+                ASTNode::Enum { name, type_params, variants, .. } => {
+                    globals.enums.insert(name.clone(), variants.clone());
+                    globals.type_params.insert(name.clone(), type_params.clone());
+                }
+                ASTNode::Constant { .. } => constants.push(item),
+                _ => {}
+            }
+        }
+
+        for name in classes.keys() {
+            resolve_class(name, &classes, &mut globals.classes, &mut Vec::new())?;
+        }
+
+        // Constants may use functions, classes and any constant declared before them
+        for constant in constants {
+            if let ASTNode::Constant { name, value, .. } = constant {
+                let env = Env::with_globals(Arc::new(globals.clone()));
+                let value = evaluate(value, &env)
+                    .map_err(|e| format!("Failed to evaluate constant '{}': {}", name, e))?;
+                globals.constants.insert(name.clone(), value);
+            }
+        }
+        Ok(globals)
+    }
+}
+
+/// Link the class `name` to its superclass, resolving ancestors first.
+/// `visiting` holds the subclasses being resolved, to detect cycles.
+fn resolve_class(
+    name: &str,
+    declarations: &HashMap<&str, &ASTNode>,
+    resolved: &mut HashMap<String, Arc<Class>>,
+    visiting: &mut Vec<String>,
+) -> Result<Arc<Class>, String> {
+    if let Some(class) = resolved.get(name) {
+        return Ok(Arc::clone(class));
+    }
+    if visiting.iter().any(|visited| visited == name) {
+        return Err(format!("Class '{}' inherits from itself", name));
+    }
+    let Some(ASTNode::Class { type_params, superclass, interfaces, fields, constructor, methods, static_methods, .. }) =
+        declarations.get(name).copied()
+    else {
+        return Err(format!("Class '{}' extends unknown class '{}'", visiting.last().map_or("", String::as_str), name));
+    };
+
+    visiting.push(name.to_string());
+    let superclass = match superclass {
+        Some(superclass) => Some(resolve_class(superclass, declarations, resolved, visiting)?),
+        None => None,
+    };
+    visiting.pop();
+
+    let by_name = |functions: &[ASTNode]| {
+        functions
+            .iter()
+            .filter_map(|function| match function {
+                ASTNode::Function { name, .. } => Some((name.clone(), Arc::new(function.clone()))),
+                _ => None,
+            })
+            .collect()
+    };
+    let class = Arc::new(Class {
+        name: name.to_string(),
+        type_params: type_params.clone(),
+        superclass,
+        interfaces: interfaces.clone(),
+        fields: fields.clone(),
+        constructor: constructor.as_ref().map(|init| Arc::new((**init).clone())),
+        methods: by_name(methods),
+        static_methods: by_name(static_methods),
+    });
+    resolved.insert(name.to_string(), Arc::clone(&class));
+    Ok(class)
+}
+
+/// How deeply function calls may nest before evaluation is aborted. Code
+/// run as a task has room for this many calls.
+pub const MAX_CALL_DEPTH: usize = 5_000;
+
+/// Variables visible to the code being executed, innermost scope last.
+#[derive(Debug, Clone)]
+pub struct Env {
+    pub(super) scopes: Vec<HashMap<String, Binding>>,
+    pub(super) globals: Arc<Globals>,
+    /// The class declaring the method being executed, which `super` calls
+    /// start searching from.
+    pub(super) class: Option<Arc<Class>>,
+    pub(super) depth: usize,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Env {
+    /// An environment with no program, where only the prelude is defined.
+    pub fn new() -> Self {
+        Self::with_globals(Arc::new(Globals::from_items([]).unwrap_or_default()))
+    }
+
+    pub fn with_globals(globals: Arc<Globals>) -> Self {
+        Env {
+            scopes: vec![HashMap::new()],
+            globals,
+            class: None,
+            depth: 0,
+        }
+    }
+
+    /// A fresh environment for the body of a called function: it sees the
+    /// same globals but none of the caller's variables.
+    pub(super) fn call_frame(&self) -> Self {
+        Env {
+            scopes: vec![HashMap::new()],
+            globals: Arc::clone(&self.globals),
+            class: None,
+            depth: self.depth + 1,
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Bind an endpoint parameter to the value the request supplied.
+    pub fn define_parameter(&mut self, name: &str, value: Value) {
+        self.insert(name, Binding { value: Some(value), mutable: false });
+    }
+
+    pub fn define(&mut self, name: &str, value: Option<Value>, mutable: bool) -> Result<(), String> {
+        if self.scopes.last().is_some_and(|scope| scope.contains_key(name)) {
+            return Err(format!("Variable '{}' is already declared in this scope", name));
+        }
+        self.insert(name, Binding { value, mutable });
+        Ok(())
+    }
+
+    fn insert(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    /// Whether `name` is a struct declared in the program.
+    pub fn names_struct(&self, name: &str) -> bool {
+        self.globals.structs.contains_key(name)
+    }
+
+    /// Whether `name` refers to a class rather than a variable or constant.
+    pub(super) fn names_class(&self, name: &str) -> bool {
+        self.binding(name).is_none()
+            && !self.globals.constants.contains_key(name)
+            && self.globals.classes.contains_key(name)
+    }
+
+    /// Whether `name` refers to an enum rather than a variable or constant.
+    pub fn names_enum(&self, name: &str) -> bool {
+        self.binding(name).is_none()
+            && !self.globals.constants.contains_key(name)
+            && self.globals.enums.contains_key(name)
+    }
+
+    /// The type parameters of the struct or enum `name`.
+    pub(super) fn type_params(&self, name: &str) -> &[TypeParameter] {
+        self.globals.type_params.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub(super) fn binding(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub fn get(&self, name: &str) -> Result<Value, String> {
+        match self.binding(name) {
+            Some(Binding { value: Some(value), .. }) => Ok(value.clone()),
+            Some(_) => Err(format!("Variable '{}' is used before being assigned", name)),
+            None => match self.globals.constants.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(format!("Undefined identifier '{}'", name)),
+            },
+        }
+    }
+
+    /// Mutable access to a variable's storage for assignment. An unassigned
+    /// `var` is initialised to null so the caller can overwrite it.
+    pub(super) fn slot_mut(&mut self, name: &str) -> Result<&mut Value, String> {
+        let constant = self.globals.constants.contains_key(name);
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .ok_or_else(|| match constant {
+                true => format!("Cannot assign to constant '{}'", name),
+                false => format!("Undefined identifier '{}'", name),
+            })?;
+        if !binding.mutable {
+            return Err(format!("Cannot assign to '{}': it was declared with `let`", name));
+        }
+        Ok(binding.value.get_or_insert(Value::Null))
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use super::value::{lock, Value};
+
+/// Why evaluation stopped: an `Error` the program threw, or a failure of
+/// the program the interpreter detected itself, such as dividing by zero.
+/// Either can be caught by a `try`. The details are boxed to keep results
+/// small, since evaluation recurses deeply.
+#[derive(Debug, Clone)]
+pub struct RuntimeError(Box<ErrorDetails>);
+
+/// What a `RuntimeError` records.
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    pub message: String,
+    /// The `Error` instance thrown, or `None` for a failure detected by
+    /// the interpreter.
+    pub value: Option<Value>,
+    /// The functions the error has unwound through, innermost first.
+    pub stack: Vec<String>,
+    /// Whether this is no error at all but a `?` returning `value`, an
+    /// `Err` or `None`, early from the enclosing function.
+    pub early_return: bool,
+}
+
+impl RuntimeError {
+    /// The error raised by `throw value`. Only instances of `Error` and its
+    /// subclasses can be thrown.
+    pub fn thrown(value: Value) -> Self {
+        let Value::Instance(instance) = &value else {
+            return format!("Cannot throw {}: only an Error can be thrown", value.type_name()).into();
+        };
+        let message = {
+            let instance = lock(instance);
+            if !instance.class.lineage().iter().any(|class| class.name == "Error") {
+                return format!("Cannot throw {}: only an Error can be thrown", instance.class.name).into();
+            }
+            instance.fields.get("message").map(ToString::to_string).unwrap_or_default()
+        };
+        RuntimeError(Box::new(ErrorDetails {
+            message,
+            value: Some(value),
+            stack: Vec::new(),
+            early_return: false,
+        }))
+    }
+
+    /// A `?` returning `value` from the enclosing function. It unwinds like
+    /// an error, but no `catch` can stop it.
+    pub fn returning(value: Value) -> Self {
+        RuntimeError(Box::new(ErrorDetails {
+            message: format!("{} returned outside of a function", value),
+            value: Some(value),
+            stack: Vec::new(),
+            early_return: true,
+        }))
+    }
+
+    /// The value a `?` returned early, or the error itself if it is a real one.
+    pub fn returned(self) -> Result<Value, RuntimeError> {
+        match &self.value {
+            Some(value) if self.early_return => Ok(value.clone()),
+            _ => Err(self),
+        }
+    }
+
+    /// The name of the error's class; failures detected by the interpreter
+    /// are plain `Error`s.
+    pub fn type_name(&self) -> String {
+        self.value.as_ref().map_or_else(|| "Error".to_string(), Value::type_name)
+    }
+}
+
+impl Deref for RuntimeError {
+    type Target = ErrorDetails;
+
+    fn deref(&self) -> &ErrorDetails {
+        &self.0
+    }
+}
+
+impl DerefMut for RuntimeError {
+    fn deref_mut(&mut self) -> &mut ErrorDetails {
+        &mut self.0
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError(Box::new(ErrorDetails {
+            message,
+            value: None,
+            stack: Vec::new(),
+            early_return: false,
+        }))
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures::future;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::watch;
+
+use super::error::RuntimeError;
+use super::task;
+use super::value::Value;
+use crate::core::AetherList;
+
+/// A value being computed concurrently on a tokio task: the result of
+/// calling an `async` function, or of one of the combinators. Clones share
+/// the one computation, and any number of them may wait for it.
+#[derive(Clone)]
+pub struct Future {
+    result: watch::Receiver<Option<Result<Value, RuntimeError>>>,
+}
+
+impl Future {
+    /// Run `work`, which evaluates code, as a task of its own on the
+    /// evaluation threads.
+    pub(super) fn spawn_task(work: impl FnOnce() -> Result<Value, RuntimeError> + Send + 'static) -> Self {
+        let (sender, result) = watch::channel(None);
+        let started = task::start(move || {
+            sender.send_replace(Some(work()));
+        });
+        match started {
+            Ok(()) => Future { result },
+            Err(error) => Future::ready(Err(error)),
+        }
+    }
+
+    fn ready(result: Result<Value, RuntimeError>) -> Self {
+        let (_, result) = watch::channel(Some(result));
+        Future { result }
+    }
+
+    /// Drive `work`, which only waits on timers and other futures, as an
+    /// ordinary task.
+    fn spawn(work: impl std::future::Future<Output = Result<Value, RuntimeError>> + Send + 'static) -> Self {
+        let (sender, result) = watch::channel(None);
+        handle().spawn(async move { sender.send_replace(Some(work.await)) });
+        Future { result }
+    }
+
+    /// The value computed, or the error computing it, once it is ready.
+    async fn result(&self) -> Result<Value, RuntimeError> {
+        let mut result = self.result.clone();
+        let result = match result.wait_for(Option::is_some).await {
+            Ok(result) => result.clone(),
+            Err(_) => None,
+        };
+        result.unwrap_or_else(|| Err("The task computing a future stopped without a result".into()))
+    }
+
+    /// Wait until the result is ready, as `await` does. A task waiting is
+    /// suspended, so the thread is free to run others meanwhile.
+    pub(super) fn wait(&self) -> Result<Value, RuntimeError> {
+        if let Some(result) = self.result.borrow().clone() {
+            return result;
+        }
+        let future = self.clone();
+        task::wait(async move { future.result().await })
+    }
+
+    /// Whether `self` and `other` are the same computation.
+    pub(super) fn same(&self, other: &Future) -> bool {
+        self.result.same_channel(&other.result)
+    }
+}
+
+impl fmt::Debug for Future {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Future")
+    }
+}
+
+/// The runtime futures run on: the one evaluation was started from, such
+/// as the server's, or else one of their own for programs evaluated outside
+/// of tokio.
+fn handle() -> Handle {
+    static FALLBACK: OnceLock<Runtime> = OnceLock::new();
+    Handle::try_current().unwrap_or_else(|_| {
+        FALLBACK
+            .get_or_init(|| Runtime::new().expect("failed to start the runtime for futures"))
+            .handle()
+            .clone()
+    })
+}
+
+/// Call the built-in function `name` on futures, or return `None` if there
+/// is no such function:
+///
+/// - `all(futures)`: every value, in order, or the first error;
+/// - `race(futures)`: the value or error of whichever finishes first;
+/// - `timeout(future, ms)`: the future's value, or an error if it takes
+///   longer than `ms` milliseconds;
+/// - `sleep(ms)`: `null`, after `ms` milliseconds.
+///
+/// Each gives a new future. Futures that lose a race or time out cannot
+/// be cancelled, and keep running.
+pub(super) fn call_builtin(name: &str, arguments: &[Value]) -> Option<Result<Value, String>> {
+    let future = match (name, arguments) {
+        ("all", [Value::List(futures)]) => futures_in(futures).map(|futures| {
+            Future::spawn(async move {
+                let values = future::try_join_all(futures.iter().map(Future::result)).await?;
+                Ok(Value::List(values.into_iter().collect()))
+            })
+        }),
+        ("race", [Value::List(futures)]) => futures_in(futures).and_then(|futures| {
+            if futures.is_empty() {
+                return Err("'race' needs at least one future".to_string());
+            }
+            Ok(Future::spawn(async move {
+                let (result, _, _) = future::select_all(futures.iter().map(|future| Box::pin(future.result()))).await;
+                result
+            }))
+        }),
+        ("timeout", [Value::Future(future), ms]) => duration(ms).map(|limit| {
+            let future = future.clone();
+            Future::spawn(async move {
+                tokio::time::timeout(limit, future.result())
+                    .await
+                    .unwrap_or_else(|_| Err(format!("Timed out after {} ms", limit.as_millis()).into()))
+            })
+        }),
+        ("sleep", [ms]) => duration(ms).map(|length| {
+            Future::spawn(async move {
+                tokio::time::sleep(length).await;
+                Ok(Value::Null)
+            })
+        }),
+        ("all" | "race", _) => Err(format!("'{}' expects a List of Futures", name)),
+        ("timeout", _) => Err("'timeout' expects a Future and a number of milliseconds".to_string()),
+        ("sleep", _) => Err("'sleep' expects a number of milliseconds".to_string()),
+        _ => return None,
+    };
+    Some(future.map(Value::Future))
+}
+
+fn futures_in(values: &AetherList<Value>) -> Result<Vec<Future>, String> {
+    values
+        .iter()
+        .map(|value| match value {
+            Value::Future(future) => Ok(future.clone()),
+            other => Err(format!("Expected a Future, got {}", other.type_name())),
+        })
+        .collect()
+}
+
+fn duration(ms: &Value) -> Result<Duration, String> {
+    ms.as_integer()
+        .and_then(|ms| u64::try_from(ms).ok())
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("Expected a non-negative number of milliseconds, got {}", ms))
+}
@@ -0,0 +1,958 @@
+mod env;
+mod error;
+mod future;
+mod task;
+mod value;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::compiler::parser::{
+    ASTNode, BinaryOperator, CatchClause, MatchArm, Pattern, StringPart, TypeExpr, TypeParameter, UnaryOperator,
+};
+use crate::core::AetherString;
+
+pub use env::{Env, Globals, MAX_CALL_DEPTH};
+pub use error::{ErrorDetails, RuntimeError};
+pub use future::Future;
+pub use task::{block_on, spawn};
+use value::lock;
+pub use value::{Class, Instance, Value};
+
+/// Evaluate any node: an expression to its value, and a statement or block
+/// to the value it returns, or `null` if it runs to its end. A `?` that
+/// returns early returns from the node itself.
+pub fn eval(node: &ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
+    let flow = match node {
+        ASTNode::Block { .. }
+        | ASTNode::ReturnStatement { .. }
+        | ASTNode::VariableDeclaration { .. }
+        | ASTNode::Assignment { .. }
+        | ASTNode::ExpressionStatement { .. }
+        | ASTNode::IfStatement { .. }
+        | ASTNode::WhileStatement { .. }
+        | ASTNode::ForStatement { .. }
+        | ASTNode::BreakStatement { .. }
+        | ASTNode::ContinueStatement { .. }
+        | ASTNode::TryStatement { .. }
+        | ASTNode::DeferStatement { .. } => execute(node, env),
+        ASTNode::Match { arms, .. } if arms.iter().any(|arm| matches!(arm.body, ASTNode::Block { .. })) => {
+            execute(node, env)
+        }
+        expression => evaluate(expression, env).map(Flow::Return),
+    };
+    match flow {
+        Ok(Flow::Return(value)) => Ok(value),
+        Ok(Flow::Normal) => Ok(Value::Null),
+        Ok(Flow::Break | Flow::Continue) => Err("`break` and `continue` can only be used inside a loop".into()),
+        Err(error) => error.returned(),
+    }
+}
+
+/// How a statement finished.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Run statements in a fresh scope, stopping early at `return`, `break` or
+/// `continue`. Statements deferred in the block then run, last first, even
+/// if it failed; the block's own error takes precedence over theirs.
+pub fn execute_block(statements: &[ASTNode], env: &mut Env) -> Result<Flow, RuntimeError> {
+    env.push_scope();
+    let mut deferred = Vec::new();
+    let mut result = execute_statements(statements, &mut deferred, env);
+    for statement in deferred.into_iter().rev() {
+        env.push_scope();
+        let outcome = execute(statement, env);
+        env.pop_scope();
+        if let Err(error) = outcome {
+            if !matches!(&result, Err(failure) if !failure.early_return) {
+                result = Err(error);
+            }
+        }
+    }
+    env.pop_scope();
+    result
+}
+
+fn execute_statements<'a>(
+    statements: &'a [ASTNode],
+    deferred: &mut Vec<&'a ASTNode>,
+    env: &mut Env,
+) -> Result<Flow, RuntimeError> {
+    for statement in statements {
+        if let ASTNode::DeferStatement { statement, .. } = statement {
+            deferred.push(statement);
+            continue;
+        }
+        match execute(statement, env)? {
+            Flow::Normal => {}
+            flow => return Ok(flow),
+        }
+    }
+    Ok(Flow::Normal)
+}
+
+pub fn execute(statement: &ASTNode, env: &mut Env) -> Result<Flow, RuntimeError> {
+    match statement {
+        ASTNode::ReturnStatement { expression, .. } => match expression {
+            Some(expression) => Ok(Flow::Return(evaluate(expression, env)?)),
+            None => Ok(Flow::Return(Value::Null)),
+        },
+        ASTNode::VariableDeclaration { name, mutable, initializer, .. } => {
+            let value = match initializer {
+                Some(initializer) => Some(evaluate(initializer, env)?),
+                None => None,
+            };
+            env.define(name, value, *mutable)?;
+            Ok(Flow::Normal)
+        }
+        ASTNode::Assignment { target, operator, value, .. } => {
+            let value = evaluate(value, env)?;
+            assign(target, *operator, value, env)?;
+            Ok(Flow::Normal)
+        }
+        ASTNode::ExpressionStatement { expression, .. } => {
+            evaluate(expression, env)?;
+            Ok(Flow::Normal)
+        }
+        ASTNode::Block { statements, .. } => execute_block(statements, env),
+        ASTNode::IfStatement { condition, then_branch, else_branch, .. } => {
+            if evaluate_condition(condition, env)? {
+                execute(then_branch, env)
+            } else if let Some(else_branch) = else_branch {
+                execute(else_branch, env)
+            } else {
+                Ok(Flow::Normal)
+            }
+        }
+        ASTNode::WhileStatement { condition, body, .. } => {
+            while evaluate_condition(condition, env)? {
+                task::checkpoint();
+                match execute(body, env)? {
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    Flow::Normal | Flow::Continue => {}
+                }
+            }
+            Ok(Flow::Normal)
+        }
+        ASTNode::ForStatement { variable, iterable, body, .. } => {
+            let items: Vec<Value> = match evaluate(iterable, env)? {
+                Value::List(items) => items.into_iter().collect(),
+                Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+                Value::Map(fields) => {
+                    // Maps are unordered; iterate their keys in sorted order
+                    // so programs behave the same on every run
+                    let mut keys: Vec<String> = fields.keys().cloned().collect();
+                    keys.sort();
+                    keys.into_iter().map(Value::String).collect()
+                }
+                other => return Err(format!("Cannot iterate over {}", other.type_name()).into()),
+            };
+            for item in items {
+                task::checkpoint();
+                // Each iteration binds the loop variable in its own scope
+                env.push_scope();
+                let flow = env
+                    .define(variable, Some(item), false)
+                    .map_err(RuntimeError::from)
+                    .and_then(|_| execute(body, env));
+                env.pop_scope();
+                match flow? {
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    Flow::Normal | Flow::Continue => {}
+                }
+            }
+            Ok(Flow::Normal)
+        }
+        ASTNode::Match { subject, arms, .. } => {
+            let subject = evaluate(subject, env)?;
+            let (arm, bindings) = select_arm(arms, &subject)?;
+            env.push_scope();
+            let flow = bind(bindings, env).map_err(RuntimeError::from).and_then(|_| match &arm.body {
+                body @ ASTNode::Block { .. } => execute(body, env),
+                body => evaluate(body, env).map(|_| Flow::Normal),
+            });
+            env.pop_scope();
+            flow
+        }
+        ASTNode::TryStatement { body, catches, .. } => match execute(body, env) {
+            Err(error) if !error.early_return => catch(error, catches, env),
+            flow => flow,
+        },
+        ASTNode::DeferStatement { .. } => Err("`defer` can only be used inside a block".into()),
+        ASTNode::BreakStatement { .. } => Ok(Flow::Break),
+        ASTNode::ContinueStatement { .. } => Ok(Flow::Continue),
+        _ => Err("Expected a statement".into()),
+    }
+}
+
+/// Run the first clause of a `try` accepting `error`, with the error bound
+/// to the clause's name and its `stack` filled in, or pass the error on if
+/// no clause accepts it.
+fn catch(error: RuntimeError, catches: &[CatchClause], env: &mut Env) -> Result<Flow, RuntimeError> {
+    let value = match &error.value {
+        Some(value) => value.clone(),
+        // Failures the interpreter detects are caught as plain `Error`s
+        None => match env.globals.classes.get("Error") {
+            Some(class) => construct(class, vec![Value::String(error.message.clone())], env)?,
+            None => return Err(error),
+        },
+    };
+    let Some(clause) = catches
+        .iter()
+        .find(|clause| clause.error_type.as_ref().is_none_or(|error_type| value.conforms_to(error_type)))
+    else {
+        return Err(error);
+    };
+    if let Value::Instance(instance) = &value {
+        let stack = error.stack.iter().cloned().map(Value::String).collect();
+        lock(instance).fields.insert("stack".to_string(), Value::List(stack));
+    }
+    env.push_scope();
+    let flow = env
+        .define(&clause.name, Some(value), false)
+        .map_err(RuntimeError::from)
+        .and_then(|_| execute(&clause.body, env));
+    env.pop_scope();
+    flow
+}
+
+/// The names a pattern binds, with their values.
+type Bindings<'a> = Vec<(&'a str, Value)>;
+
+/// The first arm whose pattern matches `subject`, with the names it binds.
+fn select_arm<'a>(arms: &'a [MatchArm], subject: &Value) -> Result<(&'a MatchArm, Bindings<'a>), String> {
+    for arm in arms {
+        let mut bindings = Vec::new();
+        if match_pattern(&arm.pattern, subject, &mut bindings) {
+            return Ok((arm, bindings));
+        }
+    }
+    Err(format!("No match arm matches {}", subject))
+}
+
+fn match_pattern<'a>(pattern: &'a Pattern, value: &Value, bindings: &mut Bindings<'a>) -> bool {
+    match (pattern, value) {
+        (Pattern::Wildcard { .. }, _) => true,
+        (Pattern::Binding { name, .. }, _) => {
+            bindings.push((name, value.clone()));
+            true
+        }
+        (Pattern::Literal { value: literal, .. }, _) => match literal {
+            ASTNode::NumberLiteral { value: n, .. } => *value == Value::Number(*n),
+            ASTNode::StringLiteral { value: s, .. } => matches!(value, Value::String(v) if v == s),
+            ASTNode::BooleanLiteral { value: b, .. } => *value == Value::Bool(*b),
+            _ => *value == Value::Null,
+        },
+        (Pattern::Variant { enum_name, variant, fields: patterns, .. }, Value::Enum { name, variant: actual, fields }) => {
+            enum_name == name
+                && variant == actual
+                && patterns.len() == fields.len()
+                && patterns
+                    .iter()
+                    .zip(fields)
+                    .all(|(pattern, (_, field))| match_pattern(pattern, field, bindings))
+        }
+        (Pattern::Variant { .. }, _) => false,
+    }
+}
+
+fn bind(bindings: Bindings, env: &mut Env) -> Result<(), String> {
+    for (name, value) in bindings {
+        env.define(name, Some(value), false)?;
+    }
+    Ok(())
+}
+
+fn evaluate_condition(condition: &ASTNode, env: &Env) -> Result<bool, RuntimeError> {
+    match evaluate(condition, env)? {
+        Value::Bool(b) => Ok(b),
+        other => Err(format!("Condition must be a Bool, got {}", other.type_name()).into()),
+    }
+}
+
+/// One step from a variable down to the element being assigned.
+enum PathSegment {
+    Field(String),
+    Index(Value),
+}
+
+fn assign(
+    target: &ASTNode,
+    operator: Option<BinaryOperator>,
+    value: Value,
+    env: &mut Env,
+) -> Result<(), RuntimeError> {
+    // Resolve the target to a variable plus a path of fields and indices,
+    // evaluating any index expressions before borrowing the variable mutably.
+    let mut path = Vec::new();
+    let mut node = target;
+    let root = loop {
+        match node {
+            ASTNode::Identifier { name, .. } => break name,
+            ASTNode::MemberExpression { object, member, .. } => {
+                path.push(PathSegment::Field(member.clone()));
+                node = object;
+            }
+            ASTNode::IndexExpression { object, index, .. } => {
+                path.push(PathSegment::Index(evaluate(index, env)?));
+                node = object;
+            }
+            _ => return Err("Invalid assignment target".into()),
+        }
+    };
+    path.reverse();
+
+    if operator.is_some() || !path.is_empty() {
+        // Compound assignment and element updates read the current value first
+        if let instance @ Value::Instance(_) = env.get(root)? {
+            // Instances are shared, so updating a field does not rebind the
+            // variable and is allowed even through a `let`.
+            if !path.is_empty() {
+                return Ok(assign_path(&mut instance.clone(), &path, operator, value)?);
+            }
+        }
+    }
+    Ok(assign_path(env.slot_mut(root)?, &path, operator, value)?)
+}
+
+fn assign_path(
+    place: &mut Value,
+    path: &[PathSegment],
+    operator: Option<BinaryOperator>,
+    value: Value,
+) -> Result<(), String> {
+    let Some((segment, rest)) = path.split_first() else {
+        *place = match operator {
+            Some(operator) => apply_binary(operator, place.clone(), value)?,
+            None => value,
+        };
+        return Ok(());
+    };
+
+    match (place, segment) {
+        (Value::Instance(instance), PathSegment::Field(name)) => {
+            let mut instance = lock(instance);
+            let class = instance.class.name.clone();
+            let field = instance
+                .fields
+                .get_mut(name)
+                .ok_or_else(|| format!("{} has no field '{}'", class, name))?;
+            assign_path(field, rest, operator, value)
+        }
+        (Value::Struct { name, fields }, PathSegment::Field(field)) => {
+            let field = fields
+                .get_mut(field)
+                .ok_or_else(|| format!("{} has no field '{}'", name, field))?;
+            assign_path(field, rest, operator, value)
+        }
+        (Value::Map(fields), PathSegment::Field(key) | PathSegment::Index(Value::String(key))) => {
+            assign_path(fields.get_or_insert_with(key.clone(), || Value::Null), rest, operator, value)
+        }
+        (Value::List(items), PathSegment::Index(index)) => {
+            let length = items.len();
+            let position = index
+                .as_integer()
+                .ok_or_else(|| format!("List index must be a whole number, got {}", index))?;
+            let item = usize::try_from(position)
+                .ok()
+                .and_then(|position| items.get_mut(position))
+                .ok_or_else(|| format!("Index {} out of bounds for length {}", position, length))?;
+            assign_path(item, rest, operator, value)
+        }
+        (other, PathSegment::Field(name)) => Err(format!("{} has no field '{}'", other.type_name(), name)),
+        (other, PathSegment::Index(index)) => {
+            Err(format!("Cannot index {} with {}", other.type_name(), index.type_name()))
+        }
+    }
+}
+
+pub fn evaluate(expr: &ASTNode, env: &Env) -> Result<Value, RuntimeError> {
+    match expr {
+        ASTNode::StringLiteral { value, .. } => Ok(Value::String(value.clone())),
+        ASTNode::InterpolatedString { parts, .. } => {
+            let mut string = String::new();
+            for part in parts {
+                match part {
+                    StringPart::Text(text) => string.push_str(text),
+                    StringPart::Expression(expression) => string.push_str(&evaluate(expression, env)?.to_string()),
+                }
+            }
+            Ok(Value::String(string))
+        }
+        ASTNode::NumberLiteral { value, .. } => Ok(Value::Number(*value)),
+        ASTNode::BooleanLiteral { value, .. } => Ok(Value::Bool(*value)),
+        ASTNode::NullLiteral { .. } => Ok(Value::Null),
+        ASTNode::ArrayLiteral { elements, .. } => elements
+            .iter()
+            .map(|element| evaluate(element, env))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        ASTNode::ObjectLiteral { fields, .. } => fields
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), evaluate(value, env)?)))
+            .collect::<Result<_, RuntimeError>>()
+            .map(Value::Map),
+        ASTNode::StructLiteral { name, fields, .. } => {
+            let fields = fields
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), evaluate(value, env)?)))
+                .collect::<Result<_, RuntimeError>>()?;
+            build_struct(name, fields, env)
+        }
+        ASTNode::Identifier { name, .. } => Ok(env.get(name)?),
+        ASTNode::GroupingExpression { expression, .. } => evaluate(expression, env),
+        ASTNode::UnaryExpression { operator, operand, .. } => {
+            let operand = evaluate(operand, env)?;
+            match (operator, operand) {
+                (UnaryOperator::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                (UnaryOperator::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
+                (operator, operand) => Err(format!(
+                    "Cannot apply '{}' to {}",
+                    operator,
+                    operand.type_name()
+                ).into()),
+            }
+        }
+        ASTNode::BinaryExpression { operator, left, right, .. } => {
+            evaluate_binary(*operator, left, right, env)
+        }
+        ASTNode::MemberExpression { object, member, .. } => match &**object {
+            ASTNode::Identifier { name, .. } if env.names_enum(name) => Ok(build_variant(name, member, Vec::new(), env)?),
+            object => Ok(get_member(&evaluate(object, env)?, member)?),
+        },
+        ASTNode::IndexExpression { object, index, .. } => {
+            let object = evaluate(object, env)?;
+            let index = evaluate(index, env)?;
+            Ok(get_index(&object, &index)?)
+        }
+        ASTNode::CallExpression { callee, arguments, .. } => {
+            // The receiver is evaluated before the arguments
+            let receiver = match &**callee {
+                ASTNode::MemberExpression { object, .. } => match &**object {
+                    ASTNode::SuperExpression { .. } => None,
+                    ASTNode::Identifier { name, .. } if env.names_class(name) || env.names_enum(name) => None,
+                    object => Some(evaluate(object, env)?),
+                },
+                _ => None,
+            };
+            let arguments = arguments
+                .iter()
+                .map(|argument| evaluate(argument, env))
+                .collect::<Result<Vec<_>, _>>()?;
+            match (&**callee, receiver) {
+                (ASTNode::MemberExpression { member, .. }, Some(Value::Instance(instance))) => {
+                    let class = Arc::clone(&lock(&instance).class);
+                    let (owner, method) = class
+                        .find_method(member)
+                        .ok_or_else(|| format!("{} has no method '{}'", class.name, member))?;
+                    invoke(&method, arguments, Some(Value::Instance(instance)), Some(owner), env)
+                }
+                (ASTNode::MemberExpression { member, .. }, Some(receiver)) => {
+                    Ok(call_method(&receiver, member, &arguments)?)
+                }
+                (ASTNode::MemberExpression { object, member, .. }, None) => match &**object {
+                    ASTNode::Identifier { name, .. } if env.names_enum(name) => {
+                        Ok(build_variant(name, member, arguments, env)?)
+                    }
+                    ASTNode::Identifier { name, .. } => {
+                        let class = &env.globals.classes[name];
+                        let (owner, method) = class
+                            .find(|class| class.static_methods.get(member))
+                            .ok_or_else(|| format!("{} has no static method '{}'", name, member))?;
+                        invoke(&method, arguments, None, Some(owner), env)
+                    }
+                    _ => call_super(member, arguments, env),
+                },
+                (ASTNode::Identifier { name, .. }, _) => match env.globals.classes.get(name) {
+                    Some(class) => construct(class, arguments, env),
+                    None => call_function(name, arguments, env),
+                },
+                _ => Err("Expression is not callable".into()),
+            }
+        }
+        ASTNode::Match { subject, arms, .. } => {
+            let subject = evaluate(subject, env)?;
+            let (arm, bindings) = select_arm(arms, &subject)?;
+            if bindings.is_empty() {
+                return evaluate(&arm.body, env);
+            }
+            let mut scope = env.clone();
+            scope.push_scope();
+            bind(bindings, &mut scope)?;
+            evaluate(&arm.body, &scope)
+        }
+        ASTNode::SuperExpression { .. } => Err("`super` can only be used to call a method".into()),
+        ASTNode::ThrowExpression { error, .. } => Err(RuntimeError::thrown(evaluate(error, env)?)),
+        ASTNode::PropagateExpression { expression, .. } => {
+            let value = evaluate(expression, env)?;
+            let Value::Enum { name, variant, fields } = &value else {
+                return Err(format!("Cannot apply '?' to {}", value.type_name()).into());
+            };
+            match (name.as_str(), variant.as_str()) {
+                ("Result", "Ok") | ("Option", "Some") => Ok(fields.first().map_or(Value::Null, |(_, value)| value.clone())),
+                ("Result", "Err") | ("Option", "None") => Err(RuntimeError::returning(value)),
+                _ => Err(format!("Cannot apply '?' to {}", name).into()),
+            }
+        }
+        ASTNode::AwaitExpression { future, .. } => wait_for(evaluate(future, env)?),
+        _ => Err("Statement used where an expression was expected".into()),
+    }
+}
+
+/// `await value`: wait until the future `value` has a result.
+fn wait_for(value: Value) -> Result<Value, RuntimeError> {
+    match value {
+        Value::Future(future) => future.wait(),
+        other => Err(format!("Cannot await {}", other.type_name()).into()),
+    }
+}
+
+/// A value of the struct `name` from the fields given for it. Fields left
+/// out take their default, or `null` if their type is optional.
+fn build_struct(name: &str, mut given: BTreeMap<String, Value>, env: &Env) -> Result<Value, RuntimeError> {
+    let declared = env
+        .globals
+        .structs
+        .get(name)
+        .ok_or_else(|| format!("Undefined struct '{}'", name))?;
+    let params = env.type_params(name);
+    let defaults = env.call_frame();
+    let mut fields = BTreeMap::new();
+    for field in declared {
+        let value = match (given.remove(&field.name), &field.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => evaluate(default, &defaults)?,
+            (None, None) if field.field_type.optional => Value::Null,
+            (None, None) => return Err(format!("Missing field '{}' of struct '{}'", field.name, name).into()),
+        };
+        let field_type = field.field_type.substitute(params, &[]);
+        if !value.conforms_to(&field_type) {
+            return Err(format!(
+                "Field '{}' of '{}' expects {}, got {}",
+                field.name,
+                name,
+                field_type,
+                value.type_name()
+            ).into());
+        }
+        fields.insert(field.name.clone(), value);
+    }
+    match given.into_keys().next() {
+        Some(unknown) => Err(format!("Struct '{}' has no field '{}'", name, unknown).into()),
+        None => Ok(Value::Struct {
+            name: name.to_string(),
+            fields,
+        }),
+    }
+}
+
+/// The variant `variant` of the enum `name`, with `arguments` as its payload.
+fn build_variant(name: &str, variant: &str, arguments: Vec<Value>, env: &Env) -> Result<Value, String> {
+    let declared = env
+        .globals
+        .enums
+        .get(name)
+        .ok_or_else(|| format!("Undefined enum '{}'", name))?
+        .iter()
+        .find(|declared| declared.name == variant)
+        .ok_or_else(|| format!("Enum '{}' has no variant '{}'", name, variant))?;
+    if arguments.len() != declared.fields.len() {
+        return Err(format!(
+            "Variant '{}.{}' expects {} argument{}, got {}",
+            name,
+            variant,
+            declared.fields.len(),
+            if declared.fields.len() == 1 { "" } else { "s" },
+            arguments.len()
+        ));
+    }
+    let params = env.type_params(name);
+    let mut fields = Vec::new();
+    for (field, value) in declared.fields.iter().zip(arguments) {
+        let field_type = field.param_type.substitute(params, &[]);
+        if !value.conforms_to(&field_type) {
+            return Err(format!(
+                "Field '{}' of '{}.{}' expects {}, got {}",
+                field.name,
+                name,
+                variant,
+                field_type,
+                value.type_name()
+            ));
+        }
+        fields.push((field.name.clone(), value));
+    }
+    Ok(Value::Enum {
+        name: name.to_string(),
+        variant: variant.to_string(),
+        fields,
+    })
+}
+
+fn call_function(name: &str, arguments: Vec<Value>, env: &Env) -> Result<Value, RuntimeError> {
+    match env.globals.functions.get(name) {
+        Some(function) => invoke(function, arguments, None, None, env),
+        None => match future::call_builtin(name, &arguments) {
+            Some(result) => Ok(result?),
+            None => Err(match env.binding(name) {
+                Some(_) => format!("'{}' is not a function", name),
+                None => format!("Undefined function '{}'", name),
+            }
+            .into()),
+        },
+    }
+}
+
+/// Create an instance of `class`: fill in field defaults, root class first,
+/// then run the nearest constructor.
+fn construct(class: &Arc<Class>, arguments: Vec<Value>, env: &Env) -> Result<Value, RuntimeError> {
+    let mut fields = BTreeMap::new();
+    let defaults = env.call_frame();
+    for ancestor in class.lineage() {
+        for field in &ancestor.fields {
+            let value = match &field.default {
+                Some(default) => evaluate(default, &defaults)?,
+                None => Value::Null,
+            };
+            fields.insert(field.name.clone(), value);
+        }
+    }
+    let instance = Value::Instance(Arc::new(Mutex::new(Instance {
+        class: Arc::clone(class),
+        fields,
+    })));
+
+    match class.find(|class| class.constructor.as_ref()) {
+        Some((owner, init)) => {
+            invoke(&init, arguments, Some(instance.clone()), Some(owner), env)?;
+        }
+        None if arguments.is_empty() => {}
+        None => {
+            return Err(format!(
+                "Class '{}' has no constructor and takes no arguments, got {}",
+                class.name,
+                arguments.len()
+            ).into())
+        }
+    }
+    Ok(instance)
+}
+
+/// `super.name(...)`: call the implementation inherited by the class
+/// declaring the running method, on the same `self`.
+fn call_super(name: &str, arguments: Vec<Value>, env: &Env) -> Result<Value, RuntimeError> {
+    let class = env
+        .class
+        .as_ref()
+        .ok_or("`super` can only be used inside a class method")?;
+    let superclass = class
+        .superclass
+        .as_ref()
+        .ok_or_else(|| format!("Class '{}' has no superclass", class.name))?;
+
+    let this = env.get("self").ok();
+    let found = match (&this, name) {
+        (Some(_), "init") => superclass.find(|class| class.constructor.as_ref()),
+        (Some(_), _) => superclass.find_method(name),
+        (None, _) => superclass.find(|class| class.static_methods.get(name)),
+    };
+    let (owner, method) = found.ok_or_else(|| format!("{} has no method '{}'", superclass.name, name))?;
+    invoke(&method, arguments, this, Some(owner), env)
+}
+
+/// Run a function, method or constructor body in a new call frame. Methods
+/// receive their instance as `self`; `owner` is the class declaring them.
+/// The body of an `async` function runs on a task of its own, and the call
+/// gives a `Future` of its result.
+fn invoke(
+    function: &ASTNode,
+    arguments: Vec<Value>,
+    this: Option<Value>,
+    owner: Option<Arc<Class>>,
+    env: &Env,
+) -> Result<Value, RuntimeError> {
+    // Arguments are bound in a function of their own so that its locals are
+    // off the stack while the body runs: evaluation recurses deeply
+    let (frame, generics) = enter(function, arguments, this, owner, env)?;
+    task::checkpoint();
+    match function {
+        ASTNode::Function { is_async: true, .. } => {
+            let function = function.clone();
+            Ok(Value::Future(Future::spawn_task(move || run(&function, &generics, frame))))
+        }
+        _ => run(function, &generics, frame),
+    }
+}
+
+/// The call frame for `function`, with its parameters bound to `arguments`,
+/// and the type parameters in scope of it.
+fn enter(
+    function: &ASTNode,
+    arguments: Vec<Value>,
+    this: Option<Value>,
+    owner: Option<Arc<Class>>,
+    env: &Env,
+) -> Result<(Env, Vec<TypeParameter>), RuntimeError> {
+    let ASTNode::Function { name, type_params, params, .. } = function else {
+        return Err("Expression is not callable".into());
+    };
+    // Type parameters are only checked statically, so values of them are
+    // checked here as `dynamic`
+    let generics: Vec<TypeParameter> = owner
+        .iter()
+        .flat_map(|owner| owner.type_params.iter())
+        .chain(type_params)
+        .cloned()
+        .collect();
+    if arguments.len() != params.len() {
+        return Err(format!(
+            "Function '{}' expects {} argument{}, got {}",
+            name,
+            params.len(),
+            if params.len() == 1 { "" } else { "s" },
+            arguments.len()
+        ).into());
+    }
+    if env.depth >= MAX_CALL_DEPTH {
+        return Err(format!("Maximum call depth of {} exceeded in '{}'", MAX_CALL_DEPTH, name).into());
+    }
+
+    let mut frame = env.call_frame();
+    frame.class = owner;
+    if let Some(this) = this {
+        frame.define("self", Some(this), false)?;
+    }
+    for (param, value) in params.iter().zip(arguments) {
+        let param_type = param.param_type.substitute(&generics, &[]);
+        if !value.conforms_to(&param_type) {
+            return Err(format!(
+                "Parameter '{}' of '{}' expects {}, got {}",
+                param.name,
+                name,
+                param_type,
+                value.type_name()
+            ).into());
+        }
+        frame.define(&param.name, Some(value), false)?;
+    }
+    Ok((frame, generics))
+}
+
+/// Run the body of `function` in the call frame `invoke` prepared for it.
+fn run(function: &ASTNode, generics: &[TypeParameter], mut frame: Env) -> Result<Value, RuntimeError> {
+    let ASTNode::Function { name, return_type, body, .. } = function else {
+        return Err("Expression is not callable".into());
+    };
+    let ASTNode::Block { statements, .. } = &**body else {
+        return Err(format!("Invalid body for function '{}'", name).into());
+    };
+    let result = execute_block(statements, &mut frame)
+        .map(|flow| match flow {
+            Flow::Return(value) => value,
+            _ => Value::Null,
+        })
+        .or_else(RuntimeError::returned)
+        .and_then(|value| {
+            let return_type = return_type.as_ref().map(|return_type| return_type.substitute(generics, &[]));
+            check_return(&value, return_type.as_ref(), &format!("'{}'", name))?;
+            Ok(value)
+        });
+    // Record the call on the way out of any error, for its stack trace
+    result.map_err(|mut error| {
+        error.stack.push(match &frame.class {
+            Some(owner) => format!("{}.{}", owner.name, name),
+            None => name.clone(),
+        });
+        error
+    })
+}
+
+/// Check that `value`, returned by `returner`, has the declared type.
+pub fn check_return(value: &Value, return_type: Option<&TypeExpr>, returner: &str) -> Result<(), String> {
+    match return_type {
+        Some(return_type) if !value.conforms_to(return_type) => Err(format!(
+            "{} should return {}, got {}",
+            returner,
+            return_type,
+            value.type_name()
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn evaluate_binary(
+    operator: BinaryOperator,
+    left: &ASTNode,
+    right: &ASTNode,
+    env: &Env,
+) -> Result<Value, RuntimeError> {
+    // Short-circuiting operators evaluate the right side lazily
+    match operator {
+        BinaryOperator::And | BinaryOperator::Or => {
+            let Value::Bool(l) = evaluate(left, env)? else {
+                return Err(format!("Operands of '{}' must be Bool", operator).into());
+            };
+            if (operator == BinaryOperator::And) != l {
+                return Ok(Value::Bool(l));
+            }
+            return match evaluate(right, env)? {
+                Value::Bool(r) => Ok(Value::Bool(r)),
+                _ => Err(format!("Operands of '{}' must be Bool", operator).into()),
+            };
+        }
+        BinaryOperator::NullCoalesce => {
+            return match evaluate(left, env)? {
+                Value::Null => evaluate(right, env),
+                value => Ok(value),
+            };
+        }
+        _ => {}
+    }
+
+    let left = evaluate(left, env)?;
+    let right = evaluate(right, env)?;
+    Ok(apply_binary(operator, left, right)?)
+}
+
+/// Apply a binary operator that does not short-circuit to two values.
+fn apply_binary(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+    match (operator, &left, &right) {
+        (BinaryOperator::Equal, l, r) => Ok(Value::Bool(l == r)),
+        (BinaryOperator::NotEqual, l, r) => Ok(Value::Bool(l != r)),
+        (BinaryOperator::Add, Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+        (BinaryOperator::Add, Value::String(_), _) | (BinaryOperator::Add, _, Value::String(_)) => {
+            Ok(Value::String(format!("{}{}", left, right)))
+        }
+        (BinaryOperator::Add, Value::List(l), Value::List(r)) => {
+            Ok(Value::List(l.iter().chain(r).cloned().collect()))
+        }
+        (BinaryOperator::Subtract, Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
+        (BinaryOperator::Multiply, Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
+        (BinaryOperator::Divide | BinaryOperator::Modulo, Value::Number(_), Value::Number(r)) if *r == 0.0 => {
+            Err("Division by zero".to_string())
+        }
+        (BinaryOperator::Divide, Value::Number(l), Value::Number(r)) => Ok(Value::Number(l / r)),
+        (BinaryOperator::Modulo, Value::Number(l), Value::Number(r)) => Ok(Value::Number(l % r)),
+        (BinaryOperator::Less, Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l < r)),
+        (BinaryOperator::LessEqual, Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l <= r)),
+        (BinaryOperator::Greater, Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l > r)),
+        (BinaryOperator::GreaterEqual, Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l >= r)),
+        (BinaryOperator::Less, Value::String(l), Value::String(r)) => Ok(Value::Bool(l < r)),
+        (BinaryOperator::LessEqual, Value::String(l), Value::String(r)) => Ok(Value::Bool(l <= r)),
+        (BinaryOperator::Greater, Value::String(l), Value::String(r)) => Ok(Value::Bool(l > r)),
+        (BinaryOperator::GreaterEqual, Value::String(l), Value::String(r)) => Ok(Value::Bool(l >= r)),
+        (BinaryOperator::Range, _, _) => match (left.as_integer(), right.as_integer()) {
+            (Some(start), Some(end)) => Ok(Value::List(
+                (start..end).map(|n| Value::Number(n as f64)).collect(),
+            )),
+            _ => Err("Range bounds must be whole numbers".to_string()),
+        },
+        _ => Err(format!(
+            "Cannot apply '{}' to {} and {}",
+            operator,
+            left.type_name(),
+            right.type_name()
+        )),
+    }
+}
+
+fn get_member(object: &Value, member: &str) -> Result<Value, String> {
+    match (object, member) {
+        (Value::String(s), "length") => Ok(Value::Number(AetherString::new(s.as_str()).length() as f64)),
+        (Value::List(items), "length") => Ok(Value::Number(items.len() as f64)),
+        (Value::Map(fields), _) => fields
+            .get(&member.to_string())
+            .cloned()
+            .ok_or_else(|| format!("Map has no key '{}'", member)),
+        (Value::Struct { name, fields }, _) => fields
+            .get(member)
+            .cloned()
+            .ok_or_else(|| format!("{} has no field '{}'", name, member)),
+        (Value::Instance(instance), _) => {
+            let instance = lock(instance);
+            instance
+                .fields
+                .get(member)
+                .cloned()
+                .ok_or_else(|| format!("{} has no field '{}'", instance.class.name, member))
+        }
+        _ => Err(format!("{} has no member '{}'", object.type_name(), member)),
+    }
+}
+
+fn get_index(object: &Value, index: &Value) -> Result<Value, String> {
+    match (object, index) {
+        (Value::List(items), index) => {
+            let position = index
+                .as_integer()
+                .ok_or_else(|| format!("List index must be a whole number, got {}", index))?;
+            usize::try_from(position)
+                .ok()
+                .and_then(|position| items.get(position))
+                .cloned()
+                .ok_or_else(|| format!("Index {} out of bounds for length {}", position, items.len()))
+        }
+        (Value::Map(fields), Value::String(key)) => fields
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("Map has no key '{}'", key)),
+        (Value::String(s), index) => {
+            let position = index
+                .as_integer()
+                .ok_or_else(|| format!("String index must be a whole number, got {}", index))?;
+            usize::try_from(position)
+                .ok()
+                .and_then(|position| s.chars().nth(position))
+                .map(|c| Value::String(c.to_string()))
+                .ok_or_else(|| format!("Index {} out of bounds for length {}", position, s.chars().count()))
+        }
+        _ => Err(format!(
+            "Cannot index {} with {}",
+            object.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+fn call_method(receiver: &Value, method: &str, arguments: &[Value]) -> Result<Value, String> {
+    let Value::String(s) = receiver else {
+        return Err(format!("{} has no method '{}'", receiver.type_name(), method));
+    };
+    let string = AetherString::new(s.as_str());
+    let result = match (method, arguments) {
+        ("toUpper", []) => string.to_uppercase(),
+        ("toLower", []) => string.to_lowercase(),
+        ("trim", []) => string.trim(),
+        ("concat", [Value::String(other)]) => string.concat(&AetherString::new(other.as_str())),
+        ("substring", [start]) | ("substring", [start, Value::Null]) => {
+            string.substring(to_position(start)?, None)
+        }
+        ("substring", [start, end]) => string.substring(to_position(start)?, Some(to_position(end)?)),
+        ("contains", [Value::String(needle)]) => return Ok(Value::Bool(s.contains(needle.as_str()))),
+        ("startsWith", [Value::String(prefix)]) => return Ok(Value::Bool(s.starts_with(prefix.as_str()))),
+        ("endsWith", [Value::String(suffix)]) => return Ok(Value::Bool(s.ends_with(suffix.as_str()))),
+        _ => {
+            return Err(format!(
+                "String has no method '{}' taking {} argument(s) of those types",
+                method,
+                arguments.len()
+            ))
+        }
+    };
+    Ok(Value::String(result.to_string()))
+}
+
+fn to_position(value: &Value) -> Result<usize, String> {
+    value
+        .as_integer()
+        .and_then(|n| usize::try_from(n).ok())
+        .ok_or_else(|| format!("Expected a non-negative whole number, got {}", value))
+}
@@ -0,0 +1,187 @@
+use std::cell::Cell;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+
+use corosensei::stack::DefaultStack;
+use corosensei::{Coroutine, CoroutineResult, Yielder};
+use futures::future::LocalBoxFuture;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::LocalSet;
+
+use super::env::MAX_CALL_DEPTH;
+use super::error::RuntimeError;
+use super::value::Value;
+
+/// The stack of each task. A call takes about 20 KiB in a debug build, so
+/// this leaves headroom for nested expressions within each of
+/// `MAX_CALL_DEPTH` calls. Only the pages used are committed.
+pub const STACK_SIZE: usize = MAX_CALL_DEPTH * 64 * 1024;
+
+/// How many calls and loop iterations a task runs before it lets the other
+/// tasks on its thread run.
+const SLICE: u32 = 1_000;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// What a suspended task waits for, and the result it is resumed with.
+type Wait = LocalBoxFuture<'static, Result<Value, RuntimeError>>;
+type TaskYielder = Yielder<Result<Value, RuntimeError>, Wait>;
+
+thread_local! {
+    /// The yielder of the task running on this thread, if one is.
+    static CURRENT: Cell<*const TaskYielder> = const { Cell::new(std::ptr::null()) };
+    /// Calls and loop iterations left before the running task yields.
+    static BUDGET: Cell<u32> = const { Cell::new(SLICE) };
+}
+
+/// Run `work` as a task and wait for what it returns, without holding up
+/// the caller's thread.
+///
+/// Evaluation is synchronous and recurses deeply, so each task is a
+/// coroutine with a `STACK_SIZE` stack of its own. A fixed set of threads
+/// runs them, and a task waiting on a `Future` is suspended so the others
+/// on its thread can run meanwhile. Failing to start the task, or a panic
+/// in `work`, is an error.
+pub async fn spawn<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, RuntimeError> {
+    let (sender, result) = oneshot::channel();
+    start(move || {
+        let _ = sender.send(work());
+    })?;
+    result
+        .await
+        .map_err(|_| "Evaluation stopped without a result".into())
+}
+
+/// Run `work` as a task, blocking the current thread until it is done.
+pub fn block_on<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, RuntimeError> {
+    futures::executor::block_on(spawn(work))
+}
+
+/// Hand `work` to the next evaluation thread, along with the stack it runs
+/// on.
+pub(super) fn start(work: impl FnOnce() + Send + 'static) -> Result<(), RuntimeError> {
+    let pool = pool()?;
+    let stack = DefaultStack::new(STACK_SIZE)
+        .map_err(|e| format!("Failed to allocate a stack for evaluation: {}", e))?;
+    let worker = pool.next.fetch_add(1, Ordering::Relaxed) % pool.workers.len();
+    pool.workers[worker]
+        .send((stack, Box::new(work)))
+        .map_err(|_| "The evaluation thread has stopped".into())
+}
+
+/// Wait for `future` to finish: by suspending the running task, or by
+/// blocking the thread when not on one.
+pub(super) fn wait(future: impl std::future::Future<Output = Result<Value, RuntimeError>> + 'static) -> Result<Value, RuntimeError> {
+    if CURRENT.get().is_null() {
+        return futures::executor::block_on(future);
+    }
+    suspend(Box::pin(future))
+}
+
+/// Count a call or loop iteration against the running task's slice, and
+/// let the other tasks on its thread run once it is used up.
+pub(super) fn checkpoint() {
+    let left = BUDGET.get().saturating_sub(1);
+    if left > 0 {
+        BUDGET.set(left);
+        return;
+    }
+    BUDGET.set(SLICE);
+    if !CURRENT.get().is_null() {
+        let _ = suspend(Box::pin(async {
+            tokio::task::yield_now().await;
+            Ok(Value::Null)
+        }));
+    }
+}
+
+/// Suspend the task running on this thread until `wait` finishes.
+fn suspend(wait: Wait) -> Result<Value, RuntimeError> {
+    let yielder = CURRENT.get();
+    // SAFETY: `CURRENT` is only set while its task runs on this thread, and
+    // points into the coroutine running it
+    let result = unsafe { &*yielder }.suspend(wait);
+    // Other tasks ran on this thread meanwhile
+    CURRENT.set(yielder);
+    result
+}
+
+struct Pool {
+    workers: Vec<mpsc::UnboundedSender<(DefaultStack, Job)>>,
+    next: AtomicUsize,
+}
+
+/// The evaluation threads, one per available core, started on first use.
+fn pool() -> Result<&'static Pool, RuntimeError> {
+    static POOL: OnceLock<Result<Pool, String>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let count = std::thread::available_parallelism().map_or(4, usize::from);
+        let workers = (0..count)
+            .map(|index| {
+                let (sender, jobs) = mpsc::unbounded_channel();
+                std::thread::Builder::new()
+                    .name(format!("aether-eval-{}", index))
+                    .spawn(move || run_worker(jobs))
+                    .map(|_| sender)
+                    .map_err(|e| format!("Failed to start a thread for evaluation: {}", e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Pool {
+            workers,
+            next: AtomicUsize::new(0),
+        })
+    })
+    .as_ref()
+    .map_err(|e| e.as_str().into())
+}
+
+/// Run every task handed to this thread, each on its own stack. The thread
+/// has a runtime of its own for the timers and futures the tasks start.
+fn run_worker(mut jobs: mpsc::UnboundedReceiver<(DefaultStack, Job)>) {
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() else {
+        return;
+    };
+    LocalSet::new().block_on(&runtime, async move {
+        while let Some((stack, job)) = jobs.recv().await {
+            tokio::task::spawn_local(Task {
+                coroutine: Coroutine::with_stack(stack, move |yielder: &TaskYielder, _| {
+                    CURRENT.set(yielder);
+                    job();
+                }),
+                waiting: None,
+            });
+        }
+    });
+}
+
+/// A task being driven by its thread's runtime.
+struct Task {
+    coroutine: Coroutine<Result<Value, RuntimeError>, Wait, (), DefaultStack>,
+    /// What the task is suspended on, if anything.
+    waiting: Option<Wait>,
+}
+
+impl std::future::Future for Task {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            let input = match &mut self.waiting {
+                Some(waiting) => match waiting.as_mut().poll(cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => return Poll::Pending,
+                },
+                None => Ok(Value::Null),
+            };
+            self.waiting = None;
+            let step = self.coroutine.resume(input);
+            CURRENT.set(std::ptr::null());
+            match step {
+                CoroutineResult::Yield(wait) => self.waiting = Some(wait),
+                CoroutineResult::Return(()) => return Poll::Ready(()),
+            }
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde_json::json;
+
+use super::{build_struct, build_variant, Env, Future};
+use crate::compiler::parser::{ASTNode, Field, TypeExpr, TypeParameter};
+use crate::core::{AetherList, AetherMap};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(AetherList<Value>),
+    Map(AetherMap<String, Value>),
+    /// A value of a `struct` type. Unlike instances, structs are copied
+    /// when assigned or passed.
+    Struct {
+        name: String,
+        fields: BTreeMap<String, Value>,
+    },
+    /// A variant of an `enum`, with its payload fields in declaration order.
+    Enum {
+        name: String,
+        variant: String,
+        fields: Vec<(String, Value)>,
+    },
+    /// An instance of a class. Instances are shared by reference, so
+    /// changes made through one copy are visible through every other.
+    Instance(Arc<Mutex<Instance>>),
+    /// A value still being computed, which `await` waits for.
+    Future(Future),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Struct { name: a, fields: x }, Value::Struct { name: b, fields: y }) => a == b && x == y,
+            (
+                Value::Enum { name: a, variant: v, fields: x },
+                Value::Enum { name: b, variant: w, fields: y },
+            ) => a == b && v == w && x == y,
+            (Value::Instance(a), Value::Instance(b)) => Arc::ptr_eq(a, b),
+            (Value::Future(a), Value::Future(b)) => a.same(b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Null => "Null".to_string(),
+            Value::Bool(_) => "Bool".to_string(),
+            Value::Number(_) => "Number".to_string(),
+            Value::String(_) => "String".to_string(),
+            Value::List(_) => "List".to_string(),
+            Value::Map(_) => "Map".to_string(),
+            Value::Struct { name, .. } | Value::Enum { name, .. } => name.clone(),
+            Value::Instance(instance) => lock(instance).class.name.clone(),
+            Value::Future(_) => "Future".to_string(),
+        }
+    }
+
+    /// Convert a raw query-string value according to the declared parameter type.
+    pub fn from_param(raw: &str, param_type: &TypeExpr) -> Result<Value, String> {
+        match param_type.name.as_str() {
+            "Int" => raw
+                .parse::<i64>()
+                .map(|n| Value::Number(n as f64))
+                .map_err(|_| format!("expected a whole number, got '{}'", raw)),
+            "Float" | "Number" => raw
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| format!("expected a number, got '{}'", raw)),
+            "Bool" => raw
+                .parse()
+                .map(Value::Bool)
+                .map_err(|_| format!("expected true or false, got '{}'", raw)),
+            _ => Ok(Value::String(raw.to_string())),
+        }
+    }
+
+    /// Convert JSON, such as a request body, into a value of type `ty`,
+    /// reporting where it does not fit. Struct defaults are evaluated in
+    /// `env`.
+    pub fn from_json(json: &serde_json::Value, ty: &TypeExpr, env: &Env) -> Result<Value, String> {
+        use serde_json::Value as Json;
+        let mismatch = || Err(format!("expected {}, got {}", ty, json_kind(json)));
+        match (json, ty.name.as_str()) {
+            (_, "dynamic" | "any") | (Json::Object(_), "Object") => Ok(Value::from(json.clone())),
+            (Json::Null, _) if ty.optional => Ok(Value::Null),
+            (Json::Number(n), "Int") => n.as_i64().map(|n| Value::Number(n as f64)).map_or_else(mismatch, Ok),
+            (Json::Number(n), "Float" | "Number") => Ok(Value::Number(n.as_f64().unwrap_or_default())),
+            (Json::Bool(b), "Bool") => Ok(Value::Bool(*b)),
+            (Json::String(s), "String") => Ok(Value::String(s.clone())),
+            (Json::Array(items), "List") => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| match ty.arguments.first() {
+                    Some(element) => {
+                        Value::from_json(item, element, env).map_err(|e| format!("[{}]: {}", index, e))
+                    }
+                    None => Ok(Value::from(item.clone())),
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            (Json::Object(fields), "Map") => fields
+                .iter()
+                .map(|(key, field)| match ty.arguments.get(1) {
+                    Some(value) => Value::from_json(field, value, env)
+                        .map(|value| (key.clone(), value))
+                        .map_err(|e| format!("'{}': {}", key, e)),
+                    None => Ok((key.clone(), Value::from(field.clone()))),
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::Map),
+            (Json::Object(given), name) if env.globals.structs.contains_key(name) => {
+                let params = env.type_params(name);
+                let mut fields = BTreeMap::new();
+                for field in &env.globals.structs[name] {
+                    if let Some(json) = given.get(&field.name) {
+                        let value = Value::from_json(json, &field.field_type.substitute(params, &ty.arguments), env)
+                            .map_err(|e| format!("field '{}': {}", field.name, e))?;
+                        fields.insert(field.name.clone(), value);
+                    }
+                }
+                // Keys the struct does not declare are ignored
+                build_struct(name, fields, env).map_err(|e| e.to_string())
+            }
+            (Json::String(variant), name) if env.globals.enums.contains_key(name) => {
+                build_variant(name, variant, Vec::new(), env)
+            }
+            (Json::Object(given), name) if env.globals.enums.contains_key(name) && given.len() == 1 => {
+                let (variant, payload) = given.iter().next().expect("object has one key");
+                let declared = env.globals.enums[name]
+                    .iter()
+                    .find(|declared| declared.name == *variant)
+                    .ok_or_else(|| format!("Enum '{}' has no variant '{}'", name, variant))?;
+                let params = env.type_params(name);
+                let mut arguments = Vec::new();
+                for field in &declared.fields {
+                    let json = payload.get(&field.name).unwrap_or(&Json::Null);
+                    let value = Value::from_json(json, &field.param_type.substitute(params, &ty.arguments), env)
+                        .map_err(|e| format!("{}.{}: field '{}': {}", name, variant, field.name, e))?;
+                    arguments.push(value);
+                }
+                build_variant(name, variant, arguments, env)
+            }
+            _ => mismatch(),
+        }
+    }
+
+    /// This value as JSON. An instance that contains itself, directly or
+    /// through other values, is `null` where it recurs.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.to_json_within(&mut Vec::new())
+    }
+
+    /// `to_json`, for a value inside the instances `enclosing`.
+    fn to_json_within(&self, enclosing: &mut Vec<*const Mutex<Instance>>) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => json!(b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => json!(*n as i64),
+            Value::Number(n) => json!(n),
+            Value::String(s) => json!(s),
+            Value::List(items) => {
+                serde_json::Value::Array(items.iter().map(|item| item.to_json_within(enclosing)).collect())
+            }
+            Value::Map(fields) => serde_json::Value::Object(
+                fields.iter().map(|(k, v)| (k.clone(), v.to_json_within(enclosing))).collect(),
+            ),
+            Value::Struct { fields, .. } => serde_json::Value::Object(
+                fields.iter().map(|(k, v)| (k.clone(), v.to_json_within(enclosing))).collect(),
+            ),
+            // Enums are tagged by variant: `"Pending"`, or
+            // `{"Failed": {"reason": ...}}` for a variant with a payload
+            Value::Enum { variant, fields, .. } if fields.is_empty() => json!(variant),
+            Value::Enum { variant, fields, .. } => json!({
+                variant.clone(): serde_json::Value::Object(
+                    fields.iter().map(|(k, v)| (k.clone(), v.to_json_within(enclosing))).collect(),
+                )
+            }),
+            Value::Instance(instance) => {
+                let id = Arc::as_ptr(instance);
+                if enclosing.contains(&id) {
+                    return serde_json::Value::Null;
+                }
+                // Converting the fields may lock this instance again, so
+                // they are copied out rather than converted under the lock
+                let fields = lock(instance).fields.clone();
+                enclosing.push(id);
+                let json = fields.iter().map(|(k, v)| (k.clone(), v.to_json_within(enclosing))).collect();
+                enclosing.pop();
+                serde_json::Value::Object(json)
+            }
+            // Nothing is known of a future's value until it is awaited
+            Value::Future(_) => serde_json::Value::Null,
+        }
+    }
+
+    /// The field `name` of a struct, instance or map.
+    pub fn field(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::Struct { fields, .. } => fields.get(name).cloned(),
+            Value::Instance(instance) => lock(instance).fields.get(name).cloned(),
+            Value::Map(fields) => fields.get(&name.to_string()).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Whether this value is one of those `ty` describes. Values declared
+    /// `dynamic` are only ever checked here, where they reach a parameter
+    /// or a return of a declared type.
+    pub fn conforms_to(&self, ty: &TypeExpr) -> bool {
+        let argument = |index: usize| ty.arguments.get(index);
+        match (self, ty.name.as_str()) {
+            (_, "dynamic" | "any") => true,
+            (Value::Null, name) => ty.optional || name == "Void",
+            (Value::Number(n), "Int") => n.fract() == 0.0,
+            (Value::Number(_), "Float" | "Number") => true,
+            (Value::Bool(_), "Bool") | (Value::String(_), "String") | (Value::Map(_), "Object") => true,
+            (Value::Future(_), "Future") => true,
+            (Value::Struct { name, .. } | Value::Enum { name, .. }, expected) => name == expected,
+            (Value::List(items), "List") => {
+                argument(0).is_none_or(|element| items.iter().all(|item| item.conforms_to(element)))
+            }
+            (Value::Map(fields), "Map") => {
+                argument(1).is_none_or(|value| fields.iter().all(|(_, field)| field.conforms_to(value)))
+            }
+            (Value::Instance(instance), name) => lock(instance)
+                .class
+                .lineage()
+                .iter()
+                .any(|class| class.name == name || class.interfaces.iter().any(|interface| interface == name)),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => None,
+        }
+    }
+}
+
+/// Untyped conversion: objects become maps and arrays lists.
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or_default()),
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(items) => Value::List(items.into_iter().map(Value::from).collect()),
+            serde_json::Value::Object(fields) => {
+                Value::Map(fields.into_iter().map(|(key, value)| (key, Value::from(value))).collect())
+            }
+        }
+    }
+}
+
+/// How a JSON value is described in conversion errors.
+fn json_kind(json: &serde_json::Value) -> &'static str {
+    match json {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => "a whole number",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s),
+            Value::Future(_) => write!(f, "Future"),
+            Value::Enum { name, variant, fields } if fields.is_empty() => write!(f, "{}.{}", name, variant),
+            Value::Enum { name, variant, fields } => {
+                let fields = fields.iter().map(|(_, value)| value.to_json().to_string()).collect::<Vec<_>>();
+                write!(f, "{}.{}({})", name, variant, fields.join(", "))
+            }
+            other => write!(f, "{}", other.to_json()),
+        }
+    }
+}
+
+/// A class declaration linked to its superclass.
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    /// Type parameters, which are erased to `dynamic` when checking values.
+    pub(super) type_params: Vec<TypeParameter>,
+    pub superclass: Option<Arc<Class>>,
+    /// The interfaces the class declares it implements.
+    pub interfaces: Vec<String>,
+    pub(super) fields: Vec<Field>,
+    pub(super) constructor: Option<Arc<ASTNode>>,
+    pub(super) methods: HashMap<String, Arc<ASTNode>>,
+    pub(super) static_methods: HashMap<String, Arc<ASTNode>>,
+}
+
+impl Class {
+    /// Search this class and then its ancestors, returning the first match
+    /// together with the class that declares it.
+    pub(super) fn find(
+        self: &Arc<Self>,
+        select: impl Fn(&Class) -> Option<&Arc<ASTNode>>,
+    ) -> Option<(Arc<Class>, Arc<ASTNode>)> {
+        let mut current = Some(self);
+        while let Some(class) = current {
+            if let Some(found) = select(class) {
+                return Some((Arc::clone(class), Arc::clone(found)));
+            }
+            current = class.superclass.as_ref();
+        }
+        None
+    }
+
+    pub(super) fn find_method(self: &Arc<Self>, name: &str) -> Option<(Arc<Class>, Arc<ASTNode>)> {
+        self.find(|class| class.methods.get(name))
+    }
+
+    /// This class and its ancestors, root class first.
+    pub(super) fn lineage(self: &Arc<Self>) -> Vec<Arc<Class>> {
+        let mut lineage = Vec::new();
+        let mut current = Some(self);
+        while let Some(class) = current {
+            lineage.push(Arc::clone(class));
+            current = class.superclass.as_ref();
+        }
+        lineage.reverse();
+        lineage
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Arc<Class>,
+    pub fields: BTreeMap<String, Value>,
+}
+
+pub(super) fn lock(instance: &Mutex<Instance>) -> MutexGuard<'_, Instance> {
+    // A panic while an instance was locked cannot leave it half-updated in a
+    // way later code relies on, so a poisoned lock is still usable.
+    instance.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
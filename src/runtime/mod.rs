@@ -1,18 +1,27 @@
 use axum::{
-    routing::{get, post, put, delete},
+    body::Bytes,
+    routing::{get, post, put, delete, MethodRouter},
     Router,
-    response::Json,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
     extract::Query,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::SocketAddr;
-use crate::compiler::parser::{ASTNode, Parameter}; // Added Parameter
+use crate::compiler::parser::{ASTNode, Parameter, TypeExpr}; // Added Parameter
+use interpreter::{Env, Globals, Value};
+use std::sync::Arc;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 use tracing::info;
 use tokio::net::TcpListener;
 use serde_json::json;
 
+pub mod interpreter;
+
+#[cfg(test)]
+mod tests;
+
 pub struct Runtime {
     ast: ASTNode,
     port: u16,
@@ -24,6 +33,13 @@ impl Runtime {
     }
 
     pub async fn start(&self) -> Result<(), String> {
+        if self.services().next().is_none() {
+            return Err("No service to run: the entry file does not declare a `service`".to_string());
+        }
+        let app = self.build_router()?
+            .layer(ServiceBuilder::new()
+                .layer(TraceLayer::new_for_http()));
+
         // Initialize the tracing subscriber
         tracing_subscriber::fmt()
             .with_max_level(tracing::Level::INFO)
@@ -34,10 +50,6 @@ impl Runtime {
             .compact()
             .init();
 
-        let app = self.build_router()
-            .layer(ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http()));
-
         let addr = SocketAddr::from(([127, 0, 0, 1], self.port));
         info!("🚀 Aether service running on http://{}", addr);
         info!("Press Ctrl+C to stop the server");
@@ -53,81 +65,120 @@ impl Runtime {
         Ok(())
     }
 
-    fn build_router(&self) -> Router {
-        let mut router = Router::new();
-        let mut has_health_check = false;
+    /// Top-level items of the program; a bare service is its own only item.
+    fn items(&self) -> &[ASTNode] {
+        match &self.ast {
+            ASTNode::Program { items, .. } => items,
+            other => std::slice::from_ref(other),
+        }
+    }
+
+    /// The services of the entry file. Those of the files it imports are
+    /// not mounted.
+    fn services(&self) -> impl Iterator<Item = &ASTNode> {
+        let entry = self.ast.span().file_id;
+        self.items()
+            .iter()
+            .filter(move |item| matches!(item, ASTNode::Service { .. }) && item.span().file_id == entry)
+    }
+
+    /// Mount the endpoints of every service, each under its base path. Fails
+    /// if two endpoints claim the same method and path, or if a constant
+    /// cannot be evaluated.
+    fn build_router(&self) -> Result<Router, String> {
+        let mut routes: BTreeMap<String, MethodRouter> = BTreeMap::new();
+        let mut owners: HashMap<(String, String), &str> = HashMap::new();
+
+        for service in self.services() {
+            let ASTNode::Service { name, base_path, endpoints, functions, constants, .. } = service else {
+                continue;
+            };
+            // Each service sees the top-level declarations plus its own
+            let globals = Globals::from_items(self.items().iter().chain(functions).chain(constants))
+                .map_err(|e| format!("Service '{}': {}", name, e))?;
+            let globals = Arc::new(globals);
 
-        if let ASTNode::Service { endpoints, .. } = &self.ast {
             for endpoint_ast in endpoints {
-                if let ASTNode::Endpoint { path, method, params, body, .. } = endpoint_ast {
-                    if path == "/health" {
-                        has_health_check = true;
+                if let ASTNode::Endpoint { path, method, params, return_type, body, .. } = endpoint_ast {
+                    let current_path = join_path(base_path.as_deref(), path);
+                    if let Some(owner) = owners.insert((method.clone(), current_path.clone()), name) {
+                        return Err(if owner == name {
+                            format!("Route {} {} is declared twice in service '{}'", method.to_uppercase(), current_path, name)
+                        } else {
+                            format!(
+                                "Route {} {} is declared by both service '{}' and service '{}'",
+                                method.to_uppercase(), current_path, owner, name
+                            )
+                        });
                     }
 
-                    let current_path = path.clone();
                     // Clone necessary AST parts for the handler
                     let endpoint_params_ast: Vec<Parameter> = params.clone(); 
                     let endpoint_body_ast: Box<ASTNode> = body.clone();
+                    let endpoint_return_type = return_type.clone();
+                    let route = format!("Endpoint {} {}", method.to_uppercase(), current_path);
+                    let globals = Arc::clone(&globals);
 
-                    router = router.route(&current_path, match method.as_str() {
-                        "get" => {
-                            get(move |query_params: Query<HashMap<String, String>>| {
-                                let captured_endpoint_params = endpoint_params_ast.clone();
-                                let captured_body = endpoint_body_ast.clone();
-                                async move {
-                                    if let ASTNode::Block { statements } = *captured_body {
-                                        if let Some(ASTNode::ReturnStatement { expression }) = statements.first() {
-                                            match &**expression {
-                                                ASTNode::StringLiteral { value } => {
-                                                    let mut processed_value = value.clone();
-                                                    for ast_param in captured_endpoint_params {
-                                                        if let Some(param_val) = query_params.0.get(&ast_param.name) {
-                                                            // Construct the pattern \(name) directly
-                                                            let slash = "\\"; // Literal backslash
-                                                            let open_paren = "(";
-                                                            let close_paren = ")";
-                                                            let pattern_to_search = format!("{}{}{}{}", slash, open_paren, ast_param.name, close_paren);
-                                                            processed_value = processed_value.replace(&pattern_to_search, param_val);
-                                                        } else {
-                                                            // Parameter not found in query
-                                                            return Json(json!({ "error": format!("Missing required parameter: {}", ast_param.name) }));
-                                                        }
-                                                    }
-                                                    Json(json!({ "data": processed_value }))
-                                                }
-                                                ASTNode::Identifier { name } => {
-                                                    if let Some(param_val) = query_params.0.get(name) {
-                                                        Json(json!({ "data": param_val }))
-                                                    } else {
-                                                        Json(json!({ "error": format!("Identifier '{}' not found in query parameters", name) }))
-                                                    }
-                                                }
-                                                _ => Json(json!({ "error": "Invalid return type for dynamic processing" })),
-                                            }
-                                        } else {
-                                            Json(json!({ "error": "No return statement in endpoint body" }))
-                                        }
-                                    } else {
-                                        Json(json!({ "error": "Invalid endpoint body structure" }))
-                                    }
-                                }
+                    let handler = move |query_params: Query<HashMap<String, String>>, request_body: Bytes| {
+                        let captured_endpoint_params = endpoint_params_ast.clone();
+                        let captured_body = endpoint_body_ast.clone();
+                        let captured_return_type = endpoint_return_type.clone();
+                        let route = route.clone();
+                        let globals = Arc::clone(&globals);
+                        // Evaluation is synchronous and recurses deeply, so it runs
+                        // as a task with a stack of its own rather than on an
+                        // async worker
+                        async move {
+                            let failed = format!("{} failed", route);
+                            interpreter::spawn(move || {
+                                execute_endpoint(
+                                    globals,
+                                    &captured_endpoint_params,
+                                    &captured_return_type,
+                                    &route,
+                                    &captured_body,
+                                    &query_params.0,
+                                    &request_body,
+                                )
+                            })
+                            .await
+                            .unwrap_or_else(|e| {
+                                let message = format!("{}: {}", failed, e);
+                                error_response(StatusCode::INTERNAL_SERVER_ERROR, message, "Error", &[])
                             })
-                        },
-                        "post" => post(|| async { Json(json!({ "message": "POST not fully implemented for dynamic params yet" })) }),
-                        "put" => put(|| async { Json(json!({ "message": "PUT not fully implemented for dynamic params yet" })) }),
-                        "delete" => delete(|| async { Json(json!({ "message": "DELETE not fully implemented for dynamic params yet" })) }),
-                        _ => get(|| async { Json(json!({ "error": "Method not supported" })) }),
-                    });
+                        }
+                    };
+                    let handler = match method.as_str() {
+                        "get" => get(handler),
+                        "post" => post(handler),
+                        "put" => put(handler),
+                        "delete" => delete(handler),
+                        _ => return Err(format!("Unsupported HTTP method '@{}' on {}", method, current_path)),
+                    };
+                    let handler = match routes.remove(&current_path) {
+                        Some(existing) => existing.merge(handler),
+                        None => handler,
+                    };
+                    routes.insert(current_path, handler);
                 }
             }
         }
 
+        let mut available_routes: Vec<String> = routes.keys().cloned().collect();
+        let has_health_check = routes.contains_key("/health");
+        let mut router = Router::new();
+        for (path, handler) in routes {
+            router = router.route(&path, handler);
+        }
+
         // Add default endpoints
         if !has_health_check {
             router = router.route("/health", get(|| async { 
                 Json(json!({ "status": "OK", "timestamp": chrono::Utc::now().to_rfc3339() }))
             }));
+            available_routes.push("/health".to_string());
         }
+        available_routes.extend(["/system/info".to_string(), "/system/routes".to_string()]);
 
         // Add system endpoints
         router = router
@@ -139,16 +190,138 @@ impl Runtime {
                     "description": env!("CARGO_PKG_DESCRIPTION")
                 }))
             }))
-            .route("/system/routes", get(|| async {
+            .route("/system/routes", get(move || async move {
                 Json(json!({
-                    "available_routes": [
-                        "/health",
-                        "/system/info",
-                        "/system/routes"
-                    ]
+                    "available_routes": available_routes
                 }))
             }));
 
-        router
+        Ok(router)
     }
-}
\ No newline at end of file
+}
+
+/// Prefix an endpoint path with its service's base path, normalising the
+/// slashes between them.
+fn join_path(base_path: Option<&str>, path: &str) -> String {
+    let mut joined = String::new();
+    for segment in [base_path.unwrap_or(""), path] {
+        let segment = segment.trim_matches('/');
+        if !segment.is_empty() {
+            joined.push('/');
+            joined.push_str(segment);
+        }
+    }
+    if joined.is_empty() {
+        joined.push('/');
+    }
+    joined
+}
+
+/// Bind parameters and run an endpoint body, producing the JSON response.
+/// A struct-typed parameter is read from the JSON request body, any other
+/// from the query string. A required parameter the request leaves out, or
+/// one that cannot be read, is a 400 response before the body runs.
+/// `route` names the endpoint in errors. An error the body does not catch
+/// is a 500 response describing it, and a result is answered as `respond`
+/// describes.
+fn execute_endpoint(
+    globals: Arc<Globals>,
+    params: &[Parameter],
+    return_type: &TypeExpr,
+    route: &str,
+    body: &ASTNode,
+    query: &HashMap<String, String>,
+    request_body: &[u8],
+) -> Response {
+    let mut env = Env::with_globals(globals);
+    for param in params {
+        if env.names_struct(&param.param_type.name) {
+            let value = match serde_json::from_slice::<serde_json::Value>(request_body) {
+                Ok(json) => Value::from_json(&json, &param.param_type, &env),
+                Err(_) if request_body.iter().all(u8::is_ascii_whitespace) => {
+                    Value::from_json(&serde_json::Value::Null, &param.param_type, &env)
+                        .map_err(|_| "missing JSON body".to_string())
+                }
+                Err(e) => Err(format!("malformed JSON: {}", e)),
+            };
+            match value {
+                Ok(value) => env.define_parameter(&param.name, value),
+                Err(e) => return bad_request(format!("Invalid request body for '{}': {}", param.name, e)),
+            }
+            continue;
+        }
+        let value = match query.get(&param.name) {
+            Some(raw) => {
+                let value = if env.names_enum(&param.param_type.name) {
+                    // An enum parameter names one of its unit variants
+                    Value::from_json(&json!(raw), &param.param_type, &env)
+                } else {
+                    Value::from_param(raw, &param.param_type)
+                };
+                match value {
+                    Ok(value) => value,
+                    Err(e) => return bad_request(format!("Invalid parameter '{}': {}", param.name, e)),
+                }
+            }
+            // An optional parameter the request leaves out is `null`
+            None if param.param_type.optional => Value::Null,
+            None => return bad_request(format!("Missing required parameter: {}", param.name)),
+        };
+        env.define_parameter(&param.name, value);
+    }
+
+    // The body is a block, which gets its own scope so locals may shadow
+    // parameters
+    let result = interpreter::eval(body, &mut env).and_then(|value| {
+        interpreter::check_return(&value, Some(return_type), route)?;
+        Ok(value)
+    });
+    match result {
+        Ok(value) => respond(value),
+        Err(error) => {
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, error.message.clone(), &error.type_name(), &error.stack)
+        }
+    }
+}
+
+/// A 400 response for a request whose parameters cannot be bound.
+fn bad_request(message: String) -> Response {
+    error_response(StatusCode::BAD_REQUEST, message, "Error", &[])
+}
+
+/// Every error response has the same shape: the message, the name of the
+/// error's type, and the calls it was thrown through, which are empty for an
+/// error that was not thrown.
+fn error_response(status: StatusCode, message: String, type_name: &str, stack: &[String]) -> Response {
+    (status, Json(json!({ "error": message, "type": type_name, "stack": stack }))).into_response()
+}
+
+/// The response to an endpoint's result. A `Result` or `Option` is unwrapped:
+/// the data is what `Ok` or `Some` holds, or `null` for `None`. An `Err` is an
+/// error response instead, with the status in the error's `status` field if
+/// it has one, or 500 otherwise.
+fn respond(value: Value) -> Response {
+    let (kind, payload) = match &value {
+        Value::Enum { name, variant, fields } => (format!("{}.{}", name, variant), fields.first().map(|(_, value)| value)),
+        _ => (String::new(), None),
+    };
+    match (kind.as_str(), payload) {
+        ("Result.Ok" | "Option.Some", Some(data)) => Json(json!({ "data": data.to_json() })).into_response(),
+        ("Option.None", _) => Json(json!({ "data": null })).into_response(),
+        ("Result.Err", Some(error)) => {
+            let status = error
+                .field("status")
+                .and_then(|status| status.as_integer())
+                .and_then(|status| u16::try_from(status).ok())
+                .and_then(|status| StatusCode::from_u16(status).ok())
+                .filter(|status| status.is_client_error() || status.is_server_error())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            let message = match error.field("message") {
+                Some(Value::String(message)) => message,
+                _ => error.to_string(),
+            };
+            error_response(status, message, &error.type_name(), &[])
+        }
+        _ => Json(json!({ "data": value.to_json() })).into_response(),
+    }
+}
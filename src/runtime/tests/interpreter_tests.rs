@@ -0,0 +1,637 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::parser::{ASTNode, Parser};
+    use crate::runtime::interpreter::{self, evaluate, execute_block, Env, Flow, Globals, Value, MAX_CALL_DEPTH};
+    use std::sync::Arc;
+    use serde_json::json;
+
+    fn eval_with(source: &str, env: &Env) -> Result<Value, String> {
+        let mut parser = Parser::new(source);
+        let expression = parser.parse_expression().expect("expression should parse");
+        evaluate(&expression, env).map_err(|e| e.to_string())
+    }
+
+    fn eval(source: &str) -> Value {
+        eval_with(source, &Env::new()).unwrap()
+    }
+
+    fn param(raw: &str, param_type: &str) -> Result<Value, String> {
+        let param_type = Parser::new(param_type).parse_type().expect("type should parse");
+        Value::from_param(raw, &param_type)
+    }
+
+    #[test]
+    fn test_arithmetic_and_comparison() {
+        assert_eq!(eval("1 + 2 * 3"), Value::Number(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Value::Number(9.0));
+        assert_eq!(eval("10 % 4 - -1"), Value::Number(3.0));
+        assert_eq!(eval("7 / 2"), Value::Number(3.5));
+        assert_eq!(eval("2 <= 2 && \"a\" < \"b\""), Value::Bool(true));
+        assert_eq!(eval("[1, 2] == [1, 2]"), Value::Bool(true));
+        assert_eq!(eval("!(1 != 1)"), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_strings_and_methods() {
+        assert_eq!(eval("\"n=\" + 3"), Value::String("n=3".to_string()));
+        assert_eq!(eval("\"  Hi  \".trim().toUpper()"), Value::String("HI".to_string()));
+        assert_eq!(eval("\"héllo\".length"), Value::Number(5.0));
+        assert_eq!(eval("\"hello\".substring(1, 3)"), Value::String("el".to_string()));
+        assert_eq!(eval("\"hello\"[1]"), Value::String("e".to_string()));
+        assert_eq!(eval("\"hello\".startsWith(\"he\")"), Value::Bool(true));
+        assert_eq!(
+            eval(r#""\("ada".toUpper()) has \(1 + 2) items: \([1, 2]), \(null)""#),
+            Value::String("ADA has 3 items: [1,2], null".to_string())
+        );
+        assert_eq!(eval(r#""no \\(interpolation)""#), Value::String("no \\(interpolation)".to_string()));
+    }
+
+    #[test]
+    fn test_collections() {
+        assert_eq!(eval("[10, 20, 30][1]"), Value::Number(20.0));
+        assert_eq!(eval("{ a: { b: [1, 2] } }.a.b.length"), Value::Number(2.0));
+        assert_eq!(eval("{ a: 1 }[\"a\"]"), Value::Number(1.0));
+        assert_eq!(eval("0..3").to_json(), json!([0, 1, 2]));
+        assert_eq!(eval("[1] + [2]").to_json(), json!([1, 2]));
+    }
+
+    #[test]
+    fn test_short_circuit_and_null_coalescing() {
+        // The right-hand sides would fail if evaluated
+        assert_eq!(eval("false && missing"), Value::Bool(false));
+        assert_eq!(eval("true || missing"), Value::Bool(true));
+        assert_eq!(eval("1 ?? missing"), Value::Number(1.0));
+        assert_eq!(eval("null ?? \"default\""), Value::String("default".to_string()));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let env = Env::new();
+        assert_eq!(eval_with("1 / 0", &env).unwrap_err(), "Division by zero");
+        assert_eq!(eval_with("missing", &env).unwrap_err(), "Undefined identifier 'missing'");
+        assert_eq!(eval_with("[1][5]", &env).unwrap_err(), "Index 5 out of bounds for length 1");
+        assert_eq!(eval_with("true + 1", &env).unwrap_err(), "Cannot apply '+' to Bool and Number");
+        assert_eq!(eval_with("-\"a\"", &env).unwrap_err(), "Cannot apply '-' to String");
+    }
+
+    #[test]
+    fn test_parameters() {
+        let mut env = Env::new();
+        env.define_parameter("a", param("4", "Int").unwrap());
+        assert_eq!(eval_with("a * a", &env).unwrap(), Value::Number(16.0));
+        assert!(param("four", "Int").is_err());
+        assert_eq!(param("true", "Bool").unwrap(), Value::Bool(true));
+        assert_eq!(param("7", "Int?").unwrap(), Value::Number(7.0));
+    }
+
+    #[test]
+    fn test_json_conversion() {
+        assert_eq!(eval("{ n: 2, f: 2.5, s: \"x\", l: [null, true] }").to_json(), json!({
+            "n": 2, "f": 2.5, "s": "x", "l": [null, true]
+        }));
+    }
+
+    fn run(source: &str) -> Result<Flow, String> {
+        let mut parser = Parser::new(source);
+        let ASTNode::Block { statements, .. } = parser.parse_block().expect("block should parse") else {
+            panic!("expected block")
+        };
+        execute_block(&statements, &mut Env::new()).map_err(|e| e.to_string())
+    }
+
+    fn returned(source: &str) -> Value {
+        match run(source).unwrap() {
+            Flow::Return(value) => value,
+            other => panic!("block did not return: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eval_runs_any_node() {
+        let mut env = Env::new();
+        let mut parser = Parser::new("{ var total = 0; for n in 1..4 { total += n; } return total; }");
+        let block = parser.parse_block().unwrap();
+        assert_eq!(interpreter::eval(&block, &mut env).unwrap(), Value::Number(6.0));
+
+        // Statements run in the given environment, so later nodes see them
+        let declaration = Parser::new("{ let greeting = \"hi\"; }").parse_block().unwrap();
+        let ASTNode::Block { statements, .. } = declaration else { panic!("expected block") };
+        assert_eq!(interpreter::eval(&statements[0], &mut env).unwrap(), Value::Null);
+        let expression = Parser::new("greeting + \"!\"").parse_expression().unwrap();
+        assert_eq!(interpreter::eval(&expression, &mut env).unwrap(), Value::String("hi!".into()));
+
+        let escape = Parser::new("{ break; }").parse_block().unwrap();
+        assert_eq!(
+            interpreter::eval(&escape, &mut env).unwrap_err().message,
+            "`break` and `continue` can only be used inside a loop"
+        );
+    }
+
+    #[test]
+    fn test_variables_and_assignment() {
+        assert_eq!(returned("{ let a = 2; var b = a * 3; b += 1; return b; }"), Value::Number(7.0));
+        assert_eq!(returned("{ var s; s = \"hi\"; s += \"!\"; return s; }"), Value::String("hi!".into()));
+        assert_eq!(returned("{ var x = 1; { var x = 5; x += 1; } return x; }"), Value::Number(1.0));
+        assert_eq!(
+            returned("{ var o = { list: [1, 2] }; o.list[1] *= 10; o.name = \"n\"; return o; }").to_json(),
+            json!({ "list": [1, 20], "name": "n" })
+        );
+        assert_eq!(run("{ let a = 1; a; }").unwrap(), Flow::Normal);
+    }
+
+    #[test]
+    fn test_statement_errors() {
+        assert_eq!(run("{ let a = 1; a = 2; }").unwrap_err(), "Cannot assign to 'a': it was declared with `let`");
+        assert_eq!(run("{ var a; return a; }").unwrap_err(), "Variable 'a' is used before being assigned");
+        assert_eq!(run("{ var a; a += 1; }").unwrap_err(), "Variable 'a' is used before being assigned");
+        assert_eq!(run("{ b = 1; }").unwrap_err(), "Undefined identifier 'b'");
+        assert_eq!(run("{ var a = 1; var a = 2; }").unwrap_err(), "Variable 'a' is already declared in this scope");
+    }
+
+    #[test]
+    fn test_control_flow() {
+        for (n, expected) in [(-2, "negative"), (0, "zero"), (5, "positive")] {
+            let source = format!(
+                "{{ let n = {}; if n < 0 {{ return \"negative\"; }} else if n == 0 {{ return \"zero\"; }} else {{ return \"positive\"; }} }}",
+                n
+            );
+            assert_eq!(returned(&source), Value::String(expected.into()));
+        }
+
+        assert_eq!(
+            returned("{ var i = 0; var sum = 0; while true { i += 1; if i > 10 { break; } if i % 2 == 0 { continue; } sum += i; } return sum; }"),
+            Value::Number(25.0)
+        );
+        assert_eq!(
+            returned("{ var out = []; for c in \"abc\" { if c == \"b\" { continue; } out += [c.toUpper()]; } return out; }").to_json(),
+            json!(["A", "C"])
+        );
+        assert_eq!(
+            returned("{ for i in 1..100 { if i * i > 50 { return i; } } return 0; }"),
+            Value::Number(8.0)
+        );
+        assert_eq!(returned("{ var keys = \"\"; for k in { b: 1, a: 2 } { keys += k; } return keys; }"), Value::String("ab".into()));
+    }
+
+    #[test]
+    fn test_control_flow_errors() {
+        assert_eq!(run("{ if 1 { return 1; } }").unwrap_err(), "Condition must be a Bool, got Number");
+        assert_eq!(run("{ for x in 3 { } }").unwrap_err(), "Cannot iterate over Number");
+        assert_eq!(run("{ for x in [1] { } return x; }").unwrap_err(), "Undefined identifier 'x'");
+        assert_eq!(run("{ for x in [1] { x = 2; } }").unwrap_err(), "Cannot assign to 'x': it was declared with `let`");
+    }
+
+    /// Evaluate `expression` with the declarations in `program` in scope.
+    fn call(program: &str, expression: &str) -> Result<Value, String> {
+        let mut parser = Parser::new(program);
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        assert!(parser.take_diagnostics().is_empty());
+        let globals = Globals::from_items(&items)?;
+        eval_with(expression, &Env::with_globals(Arc::new(globals)))
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let program = r#"
+            pub fn greet(name: String): String {
+                return "Hello, " + name + "!";
+            }
+
+            fn factorial(n: Int): Int {
+                if n <= 1 { return 1; }
+                return n * factorial(n - 1);
+            }
+
+            fn fib(n: Int): Int {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn nothing() {
+                let unused = 1;
+            }
+
+            fn early(n: Int) {
+                if n > 0 { return; }
+                throw Error("unreachable for positive n");
+            }
+        "#;
+        assert_eq!(call(program, "greet(\"World\")").unwrap(), Value::String("Hello, World!".into()));
+        assert_eq!(call(program, "factorial(10)").unwrap(), Value::Number(3628800.0));
+        assert_eq!(call(program, "fib(15)").unwrap(), Value::Number(610.0));
+        assert_eq!(call(program, "nothing()").unwrap(), Value::Null);
+        assert_eq!(call(program, "early(1)").unwrap(), Value::Null);
+        assert_eq!(call(program, "greet(\"a\").toUpper()").unwrap(), Value::String("HELLO, A!".into()));
+    }
+
+    #[test]
+    fn test_function_call_errors() {
+        let program = "fn one(a: Int): Int { return a; } fn leak(): Int { return outer; } fn forever(n: Int): Int { return forever(n + 1); }";
+        assert_eq!(call(program, "one()").unwrap_err(), "Function 'one' expects 1 argument, got 0");
+        assert_eq!(call(program, "missing(1)").unwrap_err(), "Undefined function 'missing'");
+        assert_eq!(call(program, "leak()").unwrap_err(), "Undefined identifier 'outer'");
+        assert_eq!(
+            interpreter::block_on(move || call(program, "forever(0)")).unwrap().unwrap_err(),
+            format!("Maximum call depth of {} exceeded in 'forever'", MAX_CALL_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        let program = r#"
+            const LIMIT: Int = double(BASE);
+            fn double(n: Int): Int { return n * 2; }
+            const BASE = 21;
+        "#;
+        assert_eq!(
+            call(program, "BASE").unwrap_err(),
+            "Failed to evaluate constant 'LIMIT': Undefined identifier 'BASE'"
+        );
+
+        let program = "const BASE = 21; const LIMIT = double(BASE); fn double(n: Int): Int { return n * 2; } fn limit(): Int { return LIMIT; }";
+        assert_eq!(call(program, "limit()").unwrap(), Value::Number(42.0));
+
+        let mut parser = Parser::new("const A = 1;");
+        let ASTNode::Program { items, .. } = parser.parse_program() else { panic!("expected program") };
+        let mut env = Env::with_globals(Arc::new(Globals::from_items(&items).unwrap()));
+        let assign = Parser::new("{ A = 2; }").parse_block().unwrap();
+        assert_eq!(
+            crate::runtime::interpreter::execute(&assign, &mut env).unwrap_err().message,
+            "Cannot assign to constant 'A'"
+        );
+    }
+
+    const ANIMALS: &str = r#"
+        class Animal {
+            name: String;
+            sound: String = "...";
+            legs: Int = 4;
+
+            init(name: String) {
+                self.name = name;
+            }
+
+            fn speak(): String {
+                return self.name + " says " + self.sound();
+            }
+
+            fn sound(): String {
+                return "...";
+            }
+
+            static fn kind(): String {
+                return "animal";
+            }
+        }
+
+        class Dog extends Animal {
+            tricks: Int = 0;
+
+            init(name: String, tricks: Int) {
+                super.init(name);
+                self.tricks = tricks;
+            }
+
+            fn sound(): String {
+                return "woof";
+            }
+
+            fn speak(): String {
+                return super.speak() + " and knows " + self.tricks + " tricks";
+            }
+
+            fn learn() {
+                self.tricks += 1;
+            }
+        }
+
+        class Puppy extends Dog {
+            fn sound(): String {
+                return "yip";
+            }
+
+            static fn kind(): String {
+                return "young " + super.kind();
+            }
+        }
+
+        fn describe(animal: Animal): String {
+            return animal.speak();
+        }
+    "#;
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(call(ANIMALS, "Animal(\"Cat\").speak()").unwrap(), Value::String("Cat says ...".into()));
+        assert_eq!(
+            call(ANIMALS, "describe(Dog(\"Rex\", 2))").unwrap(),
+            Value::String("Rex says woof and knows 2 tricks".into())
+        );
+        // Dispatch goes to the most derived override, even from inherited code
+        assert_eq!(
+            call(ANIMALS, "describe(Puppy(\"Bit\", 0))").unwrap(),
+            Value::String("Bit says yip and knows 0 tricks".into())
+        );
+        assert_eq!(call(ANIMALS, "Puppy.kind()").unwrap(), Value::String("young animal".into()));
+        assert_eq!(call(ANIMALS, "Dog.kind()").unwrap(), Value::String("animal".into()));
+        assert_eq!(
+            call(ANIMALS, "Dog(\"Rex\", 1)").unwrap().to_json(),
+            json!({ "name": "Rex", "sound": "...", "legs": 4, "tricks": 1 })
+        );
+        assert_eq!(call(ANIMALS, "Dog(\"Rex\", 1).legs").unwrap(), Value::Number(4.0));
+    }
+
+    #[test]
+    fn test_instances_are_shared_references() {
+        let program = format!(
+            "{}\nfn train(): Int {{ let dog = Dog(\"Rex\", 0); let alias = dog; alias.learn(); dog.legs = 3; return dog.tricks * 10 + alias.legs; }}",
+            ANIMALS
+        );
+        assert_eq!(call(&program, "train()").unwrap(), Value::Number(13.0));
+        let program = format!("{}\nfn same(): Bool {{ let a = Animal(\"x\"); let b = a; return a == b && a != Animal(\"x\"); }}", ANIMALS);
+        assert_eq!(call(&program, "same()").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_self_referencing_instances() {
+        let program = r#"
+            class Node {
+                value: Int = 1;
+                next: Node? = null;
+            }
+
+            fn cycle(): Node { let n = Node(); n.next = n; return n; }
+            fn pair(): Node { let a = Node(); let b = Node(); a.next = b; b.next = a; return a; }
+            fn text(): String { return "\(cycle())"; }
+        "#;
+        assert_eq!(call(program, "cycle()").unwrap().to_json(), json!({ "value": 1, "next": null }));
+        assert_eq!(
+            call(program, "pair()").unwrap().to_json(),
+            json!({ "value": 1, "next": { "value": 1, "next": null } })
+        );
+        assert_eq!(call(program, "text()").unwrap(), Value::String("{\"next\":null,\"value\":1}".into()));
+        assert_eq!(call(program, "cycle()").unwrap().to_string(), "{\"next\":null,\"value\":1}");
+    }
+
+    #[test]
+    fn test_class_errors() {
+        assert_eq!(call(ANIMALS, "Animal()").unwrap_err(), "Function 'init' expects 1 argument, got 0");
+        assert_eq!(call(ANIMALS, "Animal(\"a\").fly()").unwrap_err(), "Animal has no method 'fly'");
+        assert_eq!(call(ANIMALS, "Animal(\"a\").wings").unwrap_err(), "Animal has no field 'wings'");
+        assert_eq!(call(ANIMALS, "Animal.create()").unwrap_err(), "Animal has no static method 'create'");
+        assert_eq!(
+            call("class A { } fn f(): Int { return super.x(); }", "f()").unwrap_err(),
+            "`super` can only be used inside a class method"
+        );
+        assert_eq!(
+            call("class A { x: Int; }", "A(1)").unwrap_err(),
+            "Class 'A' has no constructor and takes no arguments, got 1"
+        );
+        assert_eq!(
+            call("class A extends B { }", "1").unwrap_err(),
+            "Class 'A' extends unknown class 'B'"
+        );
+        assert_eq!(
+            call("class A extends A { }", "1").unwrap_err(),
+            "Class 'A' inherits from itself"
+        );
+    }
+
+    #[test]
+    fn test_interface_typed_parameters() {
+        let program = r#"
+            interface Greeter { fn greet(name: String): String; }
+            class English implements Greeter { fn greet(name: String): String { return "Hello " + name; } }
+            class French implements Greeter { fn greet(name: String): String { return "Bonjour " + name; } }
+            fn pick(french: Bool): Greeter { if french { return French(); } return English(); }
+            fn welcome(greeter: Greeter, name: String): String { return greeter.greet(name); }
+        "#;
+        assert_eq!(call(program, "welcome(pick(true), \"Ana\")").unwrap(), Value::String("Bonjour Ana".into()));
+        assert_eq!(call(program, "welcome(pick(false), \"Ana\")").unwrap(), Value::String("Hello Ana".into()));
+    }
+
+    #[test]
+    fn test_dynamic_values_are_checked_at_boundaries() {
+        let program = r#"
+            class Cat { }
+            fn parse(raw: String): dynamic { if raw == "cat" { return Cat(); } return raw.length; }
+            fn double(n: Int): Int { return n * 2; }
+            fn count(raw: String): Int { return parse(raw); }
+            fn half(n: any): Int { return n / 2; }
+            fn pets(raw: List<dynamic>): List<Cat> { return raw; }
+        "#;
+        assert_eq!(call(program, "double(parse(\"four\"))").unwrap(), Value::Number(8.0));
+        assert_eq!(
+            call(program, "double(parse(\"cat\"))").unwrap_err(),
+            "Parameter 'n' of 'double' expects Int, got Cat"
+        );
+        assert_eq!(call(program, "count(\"cat\")").unwrap_err(), "'count' should return Int, got Cat");
+        assert_eq!(call(program, "half(3)").unwrap_err(), "'half' should return Int, got Number");
+        assert!(call(program, "pets([Cat(), Cat()])").is_ok());
+        assert_eq!(call(program, "pets([Cat(), 1])").unwrap_err(), "'pets' should return List<Cat>, got List");
+    }
+
+    #[test]
+    fn test_struct_values() {
+        let program = r#"
+            struct Point { x: Int; y: Int = 0; label: String?; }
+            fn moved(p: Point): Point { var q = p; q.x += 10; return q; }
+            fn original(): Int { let p = Point { x: 1 }; let q = moved(p); return p.x * 100 + q.x; }
+        "#;
+        assert_eq!(
+            call(program, "Point { x: 1 }").unwrap().to_json(),
+            json!({ "x": 1, "y": 0, "label": null })
+        );
+        // Structs are copied, so changing one copy leaves the other alone
+        assert_eq!(call(program, "original()").unwrap(), Value::Number(111.0));
+        assert_eq!(call(program, "Point { x: 1 } == Point { x: 1, y: 0 }").unwrap(), Value::Bool(true));
+        assert_eq!(call(program, "Point { y: 1 }").unwrap_err(), "Missing field 'x' of struct 'Point'");
+        assert_eq!(call(program, "Point { x: 1, z: 2 }").unwrap_err(), "Struct 'Point' has no field 'z'");
+        assert_eq!(call(program, "Point { x: \"1\" }").unwrap_err(), "Field 'x' of 'Point' expects Int, got String");
+    }
+
+    #[test]
+    fn test_generics_are_erased() {
+        let program = r#"
+            fn first<T>(items: List<T>): T? { if items.length == 0 { return null; } return items[0]; }
+            class Box<T> {
+                value: T;
+                init(value: T) { self.value = value; }
+                fn get(): T { return self.value; }
+                fn or<U>(other: U): List<dynamic> { return [self.value, other]; }
+            }
+            struct Pair<A, B> { a: A; b: B?; }
+            enum Maybe<T> { Some(value: T), None }
+        "#;
+        assert_eq!(call(program, "first([\"a\"])").unwrap(), Value::String("a".to_string()));
+        assert_eq!(call(program, "first([])").unwrap(), Value::Null);
+        assert_eq!(call(program, "Box(1).get() + Box(\"x\").get()").unwrap(), Value::String("1x".to_string()));
+        assert_eq!(call(program, "Box(true).or(2)").unwrap().to_json(), json!([true, 2]));
+        assert_eq!(call(program, "Pair { a: 1 }").unwrap().to_json(), json!({ "a": 1, "b": null }));
+        assert_eq!(call(program, "Maybe.Some([1])").unwrap().to_json(), json!({ "Some": { "value": [1] } }));
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let program = r#"
+            enum Status { Pending, Failed(reason: String, code: Int) }
+            fn describe(status: Status): String {
+                return match status {
+                    Status.Pending => "pending",
+                    Status.Failed(reason, 500) => "server: " + reason,
+                    Status.Failed(_, code) => "failed with " + code,
+                };
+            }
+            fn sign(n: Int): Int {
+                match n {
+                    0 => { return 0; }
+                    -1 => { return -1; }
+                    _ => { }
+                }
+                return 1;
+            }
+        "#;
+        assert_eq!(call(program, "describe(Status.Pending)").unwrap(), Value::String("pending".to_string()));
+        assert_eq!(call(program, "describe(Status.Failed(\"db\", 500))").unwrap(), Value::String("server: db".to_string()));
+        assert_eq!(call(program, "describe(Status.Failed(\"x\", 404))").unwrap(), Value::String("failed with 404".to_string()));
+        assert_eq!(call(program, "[sign(0), sign(-1), sign(7)]").unwrap().to_json(), json!([0, -1, 1]));
+        assert_eq!(call(program, "Status.Failed(\"x\", 1) == Status.Failed(\"x\", 1)").unwrap(), Value::Bool(true));
+        assert_eq!(call(program, "Status.Pending == Status.Failed(\"x\", 1)").unwrap(), Value::Bool(false));
+        assert_eq!(call(program, "match 3 { 1 => 2 }").unwrap_err(), "No match arm matches 3");
+        assert_eq!(call(program, "Status.Gone").unwrap_err(), "Enum 'Status' has no variant 'Gone'");
+        assert_eq!(
+            call(program, "Status.Failed(1, 2)").unwrap_err(),
+            "Field 'reason' of 'Status.Failed' expects String, got Number"
+        );
+        assert_eq!(call(program, "Status.Failed(\"x\")").unwrap_err(), "Variant 'Status.Failed' expects 2 arguments, got 1");
+
+        // Unit variants serialise as their name, others tagged by variant
+        assert_eq!(call(program, "[Status.Pending, Status.Failed(\"x\", 1)]").unwrap().to_json(), json!(["Pending", { "Failed": { "reason": "x", "code": 1 } }]));
+    }
+
+    #[test]
+    fn test_try_catch_and_throw() {
+        let program = r#"
+            class NotFound extends Error { }
+            fn find(key: String): Int {
+                if key == "" { throw NotFound("empty key"); }
+                return key.length;
+            }
+            fn describe(key: String): String {
+                try {
+                    return "found " + find(key);
+                } catch (e: NotFound) {
+                    return e.message + " in " + e.stack[0];
+                }
+            }
+            fn divide(n: Int): dynamic {
+                try { return n / 0; } catch (e) { return [e.message, e.stack]; }
+            }
+            fn uncaught(): Int {
+                try { throw Error("inner"); } catch (e: NotFound) { return 0; }
+                return 1;
+            }
+        "#;
+        assert_eq!(call(program, "describe(\"ab\")").unwrap(), Value::String("found 2".to_string()));
+        assert_eq!(call(program, "describe(\"\")").unwrap(), Value::String("empty key in find".to_string()));
+        assert_eq!(call(program, "divide(1)").unwrap().to_json(), json!(["Division by zero", []]));
+        assert_eq!(call(program, "uncaught()").unwrap_err(), "inner");
+        assert_eq!(call(program, "Error(\"x\").stack").unwrap().to_json(), json!([]));
+        assert_eq!(call(program, "throw 1").unwrap_err(), "Cannot throw Number: only an Error can be thrown");
+    }
+
+    #[test]
+    fn test_defer() {
+        let program = r#"
+            class Log {
+                entries: List<String> = [];
+                fn add(entry: String) { self.entries = self.entries + [entry]; }
+            }
+            fn work(log: Log, fail: Bool): Int {
+                defer log.add("first");
+                defer { log.add("second"); }
+                if fail { throw Error("failed"); }
+                return log.entries.length;
+            }
+            fn run(fail: Bool): List<String> {
+                let log = Log();
+                try { log.add("result " + work(log, fail)); } catch (e) { log.add(e.message); }
+                return log.entries;
+            }
+            fn each(): List<String> {
+                let log = Log();
+                for i in 0..2 {
+                    defer log.add("end " + i);
+                    log.add("start " + i);
+                }
+                return log.entries;
+            }
+            fn cleanup(fail: Bool): Int {
+                defer throw Error("cleanup");
+                if fail { throw Error("work"); }
+                return 1;
+            }
+        "#;
+        assert_eq!(call(program, "run(false)").unwrap().to_json(), json!(["second", "first", "result 0"]));
+        assert_eq!(call(program, "run(true)").unwrap().to_json(), json!(["second", "first", "failed"]));
+        assert_eq!(call(program, "each()").unwrap().to_json(), json!(["start 0", "end 0", "start 1", "end 1"]));
+        assert_eq!(call(program, "cleanup(false)").unwrap_err(), "cleanup");
+        assert_eq!(call(program, "cleanup(true)").unwrap_err(), "work");
+    }
+
+    #[test]
+    fn test_results_and_propagation() {
+        let program = r#"
+            fn parse(s: String): Result<Int, String> {
+                if s == "" { return Result.Err("empty"); }
+                return Result.Ok(s.length);
+            }
+            fn sum(a: String, b: String): Result<Int, String> { return Result.Ok(parse(a)? + parse(b)?); }
+            fn first(items: List<Int>): Option<Int> {
+                if items.length == 0 { return Option.None; }
+                return Option.Some(items[0]);
+            }
+            fn label(items: List<Int>): Option<String> { return Option.Some("n" + first(items)?); }
+            fn guarded(s: String): Result<Int, String> {
+                try { return Result.Ok(parse(s)?); } catch (e) { return Result.Err("caught"); }
+            }
+        "#;
+        assert_eq!(call(program, "sum(\"ab\", \"c\")").unwrap().to_json(), json!({ "Ok": { "value": 3 } }));
+        assert_eq!(call(program, "sum(\"ab\", \"\")").unwrap().to_json(), json!({ "Err": { "error": "empty" } }));
+        assert_eq!(call(program, "label([4])").unwrap().to_json(), json!({ "Some": { "value": "n4" } }));
+        assert_eq!(call(program, "label([])").unwrap().to_json(), json!("None"));
+        // `?` returns past a `try` rather than being caught by it
+        assert_eq!(call(program, "guarded(\"\")").unwrap().to_json(), json!({ "Err": { "error": "empty" } }));
+        assert_eq!(call(program, "1?").unwrap_err(), "Cannot apply '?' to Number");
+    }
+
+    #[test]
+    fn test_async_and_await() {
+        let program = r#"
+            async fn slow(ms: Int, label: String): String { await sleep(ms); return label; }
+            async fn failing(): Int { throw Error("failed"); }
+            async fn safe(): String {
+                try { await failing(); return "no"; } catch (e) { return e.message; }
+            }
+        "#;
+        assert_eq!(call(program, "await slow(1, \"a\")").unwrap(), Value::String("a".to_string()));
+        assert_eq!(call(program, "slow(1, \"a\")").unwrap().type_name(), "Future");
+
+        // The calls run concurrently, so together they take about as long as one
+        let started = std::time::Instant::now();
+        let all = call(program, "await all([slow(100, \"a\"), slow(100, \"b\"), slow(100, \"c\")])").unwrap();
+        assert_eq!(all.to_json(), json!(["a", "b", "c"]));
+        assert!(started.elapsed() < std::time::Duration::from_millis(250), "took {:?}", started.elapsed());
+
+        assert_eq!(call(program, "await race([slow(500, \"slow\"), slow(1, \"fast\")])").unwrap(), Value::String("fast".to_string()));
+        assert_eq!(call(program, "await timeout(slow(500, \"a\"), 10)").unwrap_err(), "Timed out after 10 ms");
+        assert_eq!(call(program, "await timeout(slow(1, \"a\"), 500)").unwrap(), Value::String("a".to_string()));
+
+        // Errors surface where the future is awaited
+        assert_eq!(call(program, "await all([slow(1, \"a\"), failing()])").unwrap_err(), "failed");
+        assert_eq!(call(program, "await safe()").unwrap(), Value::String("failed".to_string()));
+        assert_eq!(call(program, "await 1").unwrap_err(), "Cannot await Number");
+        assert_eq!(call(program, "race([])").unwrap_err(), "'race' needs at least one future");
+        assert_eq!(call(program, "slow(\"a\", \"b\")").unwrap_err(), "Parameter 'ms' of 'slow' expects Int, got String");
+    }
+}
@@ -0,0 +1,2 @@
+mod interpreter_tests;
+mod runtime_tests;
@@ -0,0 +1,516 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use crate::compiler::parser::Parser;
+    use crate::compiler::Compiler;
+    use crate::runtime::{join_path, Runtime};
+
+    fn runtime(source: &str) -> Runtime {
+        let compiler = Compiler::new(source.to_string());
+        Runtime::new(compiler.compile().expect("source should compile"), 0)
+    }
+
+    async fn get(runtime: &Runtime, uri: &str) -> (StatusCode, Value) {
+        send(runtime, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    async fn post(runtime: &Runtime, uri: &str, body: &str) -> (StatusCode, Value) {
+        let request = Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        send(runtime, request).await
+    }
+
+    async fn send(runtime: &Runtime, request: Request<Body>) -> (StatusCode, Value) {
+        let router = runtime.build_router().expect("router should build");
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn test_join_path() {
+        assert_eq!(join_path(None, "/users"), "/users");
+        assert_eq!(join_path(Some("/api/"), "/users"), "/api/users");
+        assert_eq!(join_path(Some("api"), "/"), "/api");
+        assert_eq!(join_path(None, "/"), "/");
+    }
+
+    #[tokio::test]
+    async fn test_mounts_every_service_under_its_base_path() {
+        let runtime = runtime(
+            r#"
+            const GREETING = "Hello";
+
+            fn shout(s: String): String { return s.toUpper(); }
+
+            @base("/api/users")
+            service Users {
+                @get("/")
+                endpoint list(): List<String> { return ["ada"]; }
+
+                @get("/greet")
+                endpoint greet(name: String): String { return shout(GREETING + " " + name); }
+            }
+
+            service Status {
+                const STATE = "up";
+
+                @get("/status")
+                endpoint status(): String { return STATE; }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/api/users").await, (StatusCode::OK, json!({ "data": ["ada"] })));
+        assert_eq!(
+            get(&runtime, "/api/users/greet?name=ada").await.1,
+            json!({ "data": "HELLO ADA" })
+        );
+        assert_eq!(get(&runtime, "/status").await.1, json!({ "data": "up" }));
+        assert_eq!(get(&runtime, "/greet").await.0, StatusCode::NOT_FOUND);
+
+        let routes = get(&runtime, "/system/routes").await.1;
+        assert_eq!(
+            routes["available_routes"],
+            json!(["/api/users", "/api/users/greet", "/status", "/health", "/system/info", "/system/routes"])
+        );
+    }
+
+    #[test]
+    fn test_rejects_conflicting_routes() {
+        let runtime = runtime(
+            r#"
+            service A { @get("/x") endpoint a(): Int { return 1; } }
+            @base("/") service B { @get("/x/") endpoint b(): Int { return 2; } }
+            "#,
+        );
+        assert_eq!(
+            runtime.build_router().unwrap_err(),
+            "Route GET /x is declared by both service 'A' and service 'B'"
+        );
+    }
+
+    #[test]
+    fn test_service_constants_are_scoped() {
+        let source = r#"
+            service A { const X = 1; @get("/a") endpoint a(): Int { return X; } }
+            service B { @get("/b") endpoint b(): Int { return X; } }
+        "#;
+        let errors = Compiler::new(source.to_string()).compile().unwrap_err();
+        assert_eq!(errors[0].message, "cannot find value `X` in this scope");
+
+        // Past the checker, the runtime keeps the scopes apart too
+        let mut parser = Parser::new(source);
+        let runtime = Runtime::new(parser.parse_program(), 0);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        assert_eq!(rt.block_on(get(&runtime, "/a")).1, json!({ "data": 1 }));
+        assert_eq!(
+            rt.block_on(get(&runtime, "/b")),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                json!({ "error": "Undefined identifier 'X'", "type": "Error", "stack": [] })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serves_imported_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("main.ath"),
+            "import { shout } from \"./text.ath\";\nconst MARK = \"?\";\nservice Api { @get(\"/hi\") endpoint hi(): String { return shout(\"hi\") + MARK; } }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("text.ath"),
+            "export fn shout(s: String): String { return s.toUpper() + MARK; }\nconst MARK = \"!\";\nservice Text { @get(\"/text\") endpoint text(): String { return MARK; } }\n",
+        )
+        .unwrap();
+
+        let compiler = Compiler::from_file(dir.path().join("main.ath")).unwrap();
+        let runtime = Runtime::new(compiler.compile().expect("program should compile"), 0);
+        // Each file has its own `MARK`, and only the entry's service is mounted
+        assert_eq!(get(&runtime, "/hi").await, (StatusCode::OK, json!({ "data": "HI!?" })));
+        assert_eq!(get(&runtime, "/text").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_collections_and_optional_parameters() {
+        let runtime = runtime(
+            r#"
+            service Scores {
+                @get("/scores")
+                endpoint scores(bonus: Int?): Map<String, Int> {
+                    let all: Map<String, List<Int>> = { ada: [3, 4], bob: [5] };
+                    var totals: Map<String, Int> = {};
+                    for name in all {
+                        var sum = bonus ?? 0;
+                        for score in all[name] { sum += score; }
+                        totals[name] = sum;
+                    }
+                    return totals;
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/scores").await.1, json!({ "data": { "ada": 7, "bob": 5 } }));
+        assert_eq!(get(&runtime, "/scores?bonus=10").await.1, json!({ "data": { "ada": 17, "bob": 15 } }));
+    }
+
+    #[tokio::test]
+    async fn test_missing_required_parameters_are_bad_requests() {
+        let runtime = runtime(
+            r#"
+            service Greeter {
+                @get("/hello")
+                endpoint hello(name: String, greeting: String?): String { return "Hello!"; }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/hello?name=ada").await, (StatusCode::OK, json!({ "data": "Hello!" })));
+        // Required even though the body never reads it
+        assert_eq!(
+            get(&runtime, "/hello?greeting=hi").await,
+            (StatusCode::BAD_REQUEST, json!({ "error": "Missing required parameter: name", "type": "Error", "stack": [] }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checks_dynamic_endpoint_results() {
+        let runtime = runtime(
+            r#"
+            fn lookup(key: String): any { if key == "n" { return 1; } return key; }
+
+            service Config {
+                @get("/number")
+                endpoint number(key: String, scale: Int?): Int { return lookup(key) * (scale ?? 1); }
+
+                @get("/value")
+                endpoint value(key: String): Int { return lookup(key); }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/number?key=n&scale=3").await.1, json!({ "data": 3 }));
+        assert_eq!(
+            get(&runtime, "/number?key=n&scale=1.5").await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid parameter 'scale': expected a whole number, got '1.5'", "type": "Error", "stack": [] })
+            )
+        );
+        assert_eq!(get(&runtime, "/value?key=n").await.1, json!({ "data": 1 }));
+        assert_eq!(
+            get(&runtime, "/value?key=x").await,
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                json!({ "error": "Endpoint GET /value should return Int, got String", "type": "Error", "stack": [] })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_recurse_deeply() {
+        let runtime = runtime(
+            r#"
+            fn depth(n: Int): Int { if n == 0 { return 0; } return 1 + depth(n - 1); }
+
+            service Recursion {
+                @get("/depth")
+                endpoint measure(n: Int): Int { return depth(n); }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/depth?n=4000").await, (StatusCode::OK, json!({ "data": 4000 })));
+        let (status, body) = get(&runtime, "/depth?n=6000").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"], "Maximum call depth of 5000 exceeded in 'depth'");
+    }
+
+    #[tokio::test]
+    async fn test_structs_in_request_and_response_bodies() {
+        let runtime = runtime(
+            r#"
+            struct Item { name: String; quantity: Int = 1; }
+            struct Order { customer: String; items: List<Item>; note: String?; }
+            struct Receipt { customer: String; total: Int; }
+
+            service Shop {
+                @post("/orders")
+                endpoint place(order: Order, discount: Int?): Receipt {
+                    var total = 0;
+                    for item in order.items { total += item.quantity; }
+                    return Receipt { customer: order.customer, total: total - (discount ?? 0) };
+                }
+            }
+            "#,
+        );
+
+        let body = r#"{ "customer": "ada", "items": [{ "name": "tea", "quantity": 3 }, { "name": "cake" }] }"#;
+        assert_eq!(
+            post(&runtime, "/orders", body).await,
+            (StatusCode::OK, json!({ "data": { "customer": "ada", "total": 4 } }))
+        );
+        assert_eq!(post(&runtime, "/orders?discount=1", body).await.1, json!({ "data": { "customer": "ada", "total": 3 } }));
+        assert_eq!(
+            post(&runtime, "/orders", r#"{ "customer": "ada", "items": [{ "quantity": 2 }] }"#).await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid request body for 'order': field 'items': [0]: Missing field 'name' of struct 'Item'", "type": "Error", "stack": [] })
+            )
+        );
+        assert_eq!(
+            post(&runtime, "/orders", r#"{ "customer": 7, "items": [] }"#).await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid request body for 'order': field 'customer': expected String, got a whole number", "type": "Error", "stack": [] })
+            )
+        );
+        assert_eq!(
+            post(&runtime, "/orders", "").await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid request body for 'order': missing JSON body", "type": "Error", "stack": [] })
+            )
+        );
+        assert_eq!(get(&runtime, "/orders").await.0, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_enums_in_requests_and_responses() {
+        let runtime = runtime(
+            r#"
+            enum Priority { Low, High }
+            enum Outcome { Queued(position: Int), Rejected(reason: String) }
+            struct Job { name: String; priority: Priority; }
+
+            service Jobs {
+                @post("/jobs")
+                endpoint submit(job: Job): Outcome {
+                    return match job.priority {
+                        Priority.High => Outcome.Queued(1),
+                        Priority.Low => Outcome.Rejected("busy"),
+                    };
+                }
+
+                @get("/jobs")
+                endpoint list(priority: Priority): Priority {
+                    return priority;
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(
+            post(&runtime, "/jobs", r#"{ "name": "build", "priority": "High" }"#).await.1,
+            json!({ "data": { "Queued": { "position": 1 } } })
+        );
+        assert_eq!(
+            post(&runtime, "/jobs", r#"{ "name": "build", "priority": "Low" }"#).await.1,
+            json!({ "data": { "Rejected": { "reason": "busy" } } })
+        );
+        assert_eq!(
+            post(&runtime, "/jobs", r#"{ "name": "build", "priority": "Urgent" }"#).await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid request body for 'job': field 'priority': Enum 'Priority' has no variant 'Urgent'", "type": "Error", "stack": [] })
+            )
+        );
+        assert_eq!(get(&runtime, "/jobs?priority=Low").await.1, json!({ "data": "Low" }));
+        assert_eq!(
+            get(&runtime, "/jobs?priority=low").await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid parameter 'priority': Enum 'Priority' has no variant 'low'", "type": "Error", "stack": [] })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generic_structs_in_request_bodies() {
+        let runtime = runtime(
+            r#"
+            struct Item { name: String; }
+            struct Batch<T> { items: List<T>; cursor: String?; }
+
+            fn count<T>(batch: Batch<T>): Int { return batch.items.length; }
+
+            service Items {
+                @post("/items")
+                endpoint load(batch: Batch<Item>): Batch<String> {
+                    var names: List<String> = [];
+                    for item in batch.items { names = names + [item.name]; }
+                    return Batch { items: names, cursor: "" + count(batch) };
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(
+            post(&runtime, "/items", r#"{ "items": [{ "name": "tea" }, { "name": "cake" }] }"#).await.1,
+            json!({ "data": { "items": ["tea", "cake"], "cursor": "2" } })
+        );
+        // Type arguments decide how the body is read
+        assert_eq!(
+            post(&runtime, "/items", r#"{ "items": [{ "title": "tea" }] }"#).await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Invalid request body for 'batch': field 'items': [0]: Missing field 'name' of struct 'Item'", "type": "Error", "stack": [] })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uncaught_errors_are_server_errors() {
+        let runtime = runtime(
+            r#"
+            class NotFound extends Error { }
+            class Store {
+                fn get(key: String): String { throw NotFound("no item '" + key + "'"); }
+            }
+
+            service Items {
+                @get("/items")
+                endpoint item(key: String): String { return Store().get(key); }
+
+                @get("/safe")
+                endpoint safe(key: String): String {
+                    try { return Store().get(key); } catch (e) { return e.message; }
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(
+            get(&runtime, "/items?key=tea").await,
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                json!({ "error": "no item 'tea'", "type": "NotFound", "stack": ["Store.get"] })
+            )
+        );
+        assert_eq!(get(&runtime, "/safe?key=tea").await, (StatusCode::OK, json!({ "data": "no item 'tea'" })));
+    }
+
+    #[tokio::test]
+    async fn test_results_become_responses() {
+        let runtime = runtime(
+            r#"
+            struct NotFound { message: String; status: Int = 404; }
+            fn find(id: Int): Result<String, NotFound> {
+                if id == 1 { return Result.Ok("tea"); }
+                return Result.Err(NotFound { message: "no item " + id });
+            }
+
+            service Items {
+                @get("/items")
+                endpoint item(id: Int): Result<String, NotFound> {
+                    let name = find(id)?;
+                    return Result.Ok(name.toUpper());
+                }
+
+                @get("/checked")
+                endpoint checked(id: Int): Result<String, String> {
+                    if id == 0 { return Result.Err("bad id"); }
+                    return Result.Ok("ok");
+                }
+
+                @get("/maybe")
+                endpoint maybe(id: Int): Option<Int> {
+                    if id == 0 { return Option.None; }
+                    return Option.Some(id);
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/items?id=1").await, (StatusCode::OK, json!({ "data": "TEA" })));
+        assert_eq!(
+            get(&runtime, "/items?id=2").await,
+            (StatusCode::NOT_FOUND, json!({ "error": "no item 2", "type": "NotFound", "stack": [] }))
+        );
+        assert_eq!(
+            get(&runtime, "/checked?id=0").await,
+            (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": "bad id", "type": "String", "stack": [] }))
+        );
+        assert_eq!(get(&runtime, "/maybe?id=3").await, (StatusCode::OK, json!({ "data": 3 })));
+        assert_eq!(get(&runtime, "/maybe?id=0").await, (StatusCode::OK, json!({ "data": null })));
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_await_concurrent_work() {
+        // A single-threaded test runtime would deadlock if the endpoint
+        // blocked its worker while waiting for the timers it started
+        let runtime = runtime(
+            r#"
+            async fn price(item: String, ms: Int): Int { await sleep(ms); return item.length * 10; }
+
+            service Shop {
+                @get("/total")
+                endpoint total(): Int {
+                    let prices = await all([price("tea", 20), price("coffee", 20)]);
+                    return prices[0] + prices[1];
+                }
+
+                @get("/quick")
+                endpoint quick(ms: Int): String {
+                    let n = await timeout(price("tea", ms), 50);
+                    return "got " + n;
+                }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/total").await, (StatusCode::OK, json!({ "data": 90 })));
+        assert_eq!(get(&runtime, "/quick?ms=1").await, (StatusCode::OK, json!({ "data": "got 30" })));
+        let (status, body) = get(&runtime, "/quick?ms=1000").await;
+        assert_eq!((status, &body["error"]), (StatusCode::INTERNAL_SERVER_ERROR, &json!("Timed out after 50 ms")));
+    }
+
+    #[tokio::test]
+    async fn test_async_recursion_past_the_blocking_pool() {
+        // Each pending call waits on the next; with a thread blocked per
+        // call this deadlocked past tokio's 512 blocking threads
+        let runtime = runtime(
+            r#"
+            async fn depth(n: Int): Int {
+                if n == 0 { return 0; }
+                return await depth(n - 1) + 1;
+            }
+
+            service Recursion {
+                @get("/depth")
+                endpoint measure(n: Int): Int { return await depth(n); }
+            }
+            "#,
+        );
+
+        assert_eq!(get(&runtime, "/depth?n=1000").await, (StatusCode::OK, json!({ "data": 1000 })));
+    }
+
+    #[tokio::test]
+    async fn test_many_requests_wait_at_once() {
+        // Requests share a fixed set of threads, so waiting ones do not
+        // each hold one
+        let runtime = runtime(
+            r#"
+            service Slow {
+                @get("/slow")
+                endpoint slow(): Int { await sleep(50); return 1; }
+            }
+            "#,
+        );
+
+        let responses = futures::future::join_all((0..600).map(|_| get(&runtime, "/slow"))).await;
+        assert!(responses.iter().all(|response| *response == (StatusCode::OK, json!({ "data": 1 }))));
+    }
+}